@@ -17,6 +17,7 @@ fn main() {
         ("WGPUComputePassEncoder", "WGPUComputePassEncoderImpl"),
         ("WGPUComputePipeline", "WGPUComputePipelineImpl"),
         ("WGPUDevice", "WGPUDeviceImpl"),
+        ("WGPUGpuProfiler", "WGPUGpuProfilerImpl"),
         ("WGPUInstance", "WGPUInstanceImpl"),
         ("WGPUPipelineLayout", "WGPUPipelineLayoutImpl"),
         ("WGPUQuerySet", "WGPUQuerySetImpl"),
@@ -26,6 +27,7 @@ fn main() {
         ("WGPURenderPassEncoder", "WGPURenderPassEncoderImpl"),
         ("WGPURenderPipeline", "WGPURenderPipelineImpl"),
         ("WGPUSampler", "WGPUSamplerImpl"),
+        ("WGPUStagingBelt", "WGPUStagingBeltImpl"),
         ("WGPUShaderModule", "WGPUShaderModuleImpl"),
         ("WGPUSurface", "WGPUSurfaceImpl"),
         ("WGPUTexture", "WGPUTextureImpl"),
@@ -0,0 +1,189 @@
+#include "framework.h"
+#include "webgpu-headers/webgpu.h"
+#include <stdbool.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#define LOG_PREFIX "[generate_mipmaps]"
+
+// Level 0 is red on its left half and blue on its right half, so level 1 keeps
+// both colors and level 2, a single texel, blends them.
+#define SIZE 4
+#define MIP_LEVEL_COUNT 3
+// bytesPerRow must be a multiple of 256 when copying to a buffer. Level 1
+// takes the first two rows of the output buffer, level 2 the third one.
+#define BYTES_PER_ROW 256
+#define OUTPUT_SIZE (BYTES_PER_ROW * 3)
+
+static void handle_request_adapter(WGPURequestAdapterStatus status,
+                                   WGPUAdapter adapter, char const *message,
+                                   void *userdata) {
+  UNUSED(status)
+  UNUSED(message)
+  *(WGPUAdapter *)userdata = adapter;
+}
+static void handle_request_device(WGPURequestDeviceStatus status,
+                                  WGPUDevice device, char const *message,
+                                  void *userdata) {
+  UNUSED(status)
+  UNUSED(message)
+  *(WGPUDevice *)userdata = device;
+}
+static void handle_buffer_map(WGPUBufferMapAsyncStatus status, void *userdata) {
+  UNUSED(userdata)
+  printf(LOG_PREFIX " buffer_map status=%#.8x\n", status);
+}
+
+static bool texel_matches(const uint8_t *texel, const uint8_t *expected) {
+  for (int i = 0; i < 4; i++) {
+    // Filtering may round either way.
+    if (abs((int)texel[i] - (int)expected[i]) > 2) {
+      return false;
+    }
+  }
+  return true;
+}
+
+int main(int argc, char *argv[]) {
+  UNUSED(argc)
+  UNUSED(argv)
+  frmwrk_setup_logging(WGPULogLevel_Warn);
+
+  WGPUInstance instance = wgpuCreateInstance(NULL);
+  assert(instance);
+
+  WGPUAdapter adapter = NULL;
+  wgpuInstanceRequestAdapter(instance, NULL, handle_request_adapter,
+                             (void *)&adapter);
+  assert(adapter);
+
+  WGPUDevice device = NULL;
+  wgpuAdapterRequestDevice(adapter, NULL, handle_request_device,
+                           (void *)&device);
+  assert(device);
+
+  WGPUQueue queue = wgpuDeviceGetQueue(device);
+  assert(queue);
+
+  WGPUTexture texture = wgpuDeviceCreateTexture(
+      device, &(const WGPUTextureDescriptor){
+                  .label = "texture",
+                  .usage = WGPUTextureUsage_TextureBinding |
+                           WGPUTextureUsage_RenderAttachment |
+                           WGPUTextureUsage_CopyDst | WGPUTextureUsage_CopySrc,
+                  .dimension = WGPUTextureDimension_2D,
+                  .size = (const WGPUExtent3D){SIZE, SIZE, 1},
+                  .format = WGPUTextureFormat_RGBA8Unorm,
+                  .mipLevelCount = MIP_LEVEL_COUNT,
+                  .sampleCount = 1,
+              });
+  assert(texture);
+
+  uint8_t texels[SIZE * SIZE * 4];
+  for (int y = 0; y < SIZE; y++) {
+    for (int x = 0; x < SIZE; x++) {
+      uint8_t *texel = &texels[(y * SIZE + x) * 4];
+      texel[0] = x < SIZE / 2 ? 255 : 0;
+      texel[1] = 0;
+      texel[2] = x < SIZE / 2 ? 0 : 255;
+      texel[3] = 255;
+    }
+  }
+  wgpuQueueWriteTexture(
+      queue,
+      &(const WGPUImageCopyTexture){
+          .texture = texture,
+          .mipLevel = 0,
+          .aspect = WGPUTextureAspect_All,
+      },
+      texels, sizeof(texels),
+      &(const WGPUTextureDataLayout){
+          .bytesPerRow = SIZE * 4,
+          .rowsPerImage = SIZE,
+      },
+      &(const WGPUExtent3D){SIZE, SIZE, 1});
+
+  WGPUBuffer output_buffer = wgpuDeviceCreateBuffer(
+      device, &(const WGPUBufferDescriptor){
+                  .label = "output_buffer",
+                  .usage = WGPUBufferUsage_MapRead | WGPUBufferUsage_CopyDst,
+                  .size = OUTPUT_SIZE,
+                  .mappedAtCreation = false,
+              });
+  assert(output_buffer);
+
+  WGPUCommandEncoder command_encoder = wgpuDeviceCreateCommandEncoder(
+      device, &(const WGPUCommandEncoderDescriptor){
+                  .label = "command_encoder",
+              });
+  assert(command_encoder);
+
+  wgpuCommandEncoderGenerateMipmaps(command_encoder, texture, 0,
+                                    WGPU_MIP_LEVEL_COUNT_UNDEFINED);
+
+  for (uint32_t level = 1; level < MIP_LEVEL_COUNT; level++) {
+    uint32_t size = SIZE >> level;
+    wgpuCommandEncoderCopyTextureToBuffer(
+        command_encoder,
+        &(const WGPUImageCopyTexture){
+            .texture = texture,
+            .mipLevel = level,
+            .aspect = WGPUTextureAspect_All,
+        },
+        &(const WGPUImageCopyBuffer){
+            .buffer = output_buffer,
+            .layout =
+                (const WGPUTextureDataLayout){
+                    .offset = (level - 1) * BYTES_PER_ROW * 2,
+                    .bytesPerRow = BYTES_PER_ROW,
+                    .rowsPerImage = WGPU_COPY_STRIDE_UNDEFINED,
+                },
+        },
+        &(const WGPUExtent3D){size, size, 1});
+  }
+
+  WGPUCommandBuffer command_buffer = wgpuCommandEncoderFinish(
+      command_encoder, &(const WGPUCommandBufferDescriptor){
+                           .label = "command_buffer",
+                       });
+  assert(command_buffer);
+
+  wgpuQueueSubmit(queue, 1, &command_buffer);
+
+  wgpuBufferMapAsync(output_buffer, WGPUMapMode_Read, 0, OUTPUT_SIZE,
+                     handle_buffer_map, NULL);
+  wgpuDevicePoll(device, true, NULL);
+
+  const uint8_t *output = (const uint8_t *)wgpuBufferGetMappedRange(
+      output_buffer, 0, OUTPUT_SIZE);
+  assert(output);
+
+  const uint8_t red[4] = {255, 0, 0, 255};
+  const uint8_t blue[4] = {0, 0, 255, 255};
+  const uint8_t purple[4] = {128, 0, 128, 255};
+  bool success = true;
+  for (int y = 0; y < 2; y++) {
+    success &= texel_matches(&output[y * BYTES_PER_ROW], red);
+    success &= texel_matches(&output[y * BYTES_PER_ROW + 4], blue);
+  }
+  const uint8_t *level_2 = &output[BYTES_PER_ROW * 2];
+  success &= texel_matches(level_2, purple);
+  printf(LOG_PREFIX " level 2 texel: [%d, %d, %d, %d]\n", level_2[0],
+         level_2[1], level_2[2], level_2[3]);
+
+  wgpuBufferUnmap(output_buffer);
+  wgpuCommandBufferRelease(command_buffer);
+  wgpuCommandEncoderRelease(command_encoder);
+  wgpuBufferRelease(output_buffer);
+  wgpuTextureRelease(texture);
+  wgpuQueueRelease(queue);
+  wgpuDeviceRelease(device);
+  wgpuAdapterRelease(adapter);
+  wgpuInstanceRelease(instance);
+
+  if (!success) {
+    printf(LOG_PREFIX " generated mip levels don't match\n");
+    return EXIT_FAILURE;
+  }
+  return EXIT_SUCCESS;
+}
@@ -3,6 +3,9 @@
 
 #include "webgpu.h"
 
+typedef struct WGPUGpuProfilerImpl* WGPUGpuProfiler WGPU_OBJECT_ATTRIBUTE;
+typedef struct WGPUStagingBeltImpl* WGPUStagingBelt WGPU_OBJECT_ATTRIBUTE;
+
 typedef enum WGPUNativeSType {
     // Start at 0003 since that's allocated range for wgpu-native
     WGPUSType_DeviceExtras = 0x00030001,
@@ -15,6 +18,11 @@ typedef enum WGPUNativeSType {
     WGPUSType_BindGroupLayoutEntryExtras = 0x00030008,
     WGPUSType_QuerySetDescriptorExtras = 0x00030009,
     WGPUSType_SurfaceConfigurationExtras = 0x0003000A,
+    WGPUSType_ShaderModuleSPIRVOptions = 0x0003000B,
+    WGPUSType_ShaderModuleWGSLPreprocessorOptions = 0x0003000C,
+    WGPUSType_ShaderModuleNagaIRDescriptor = 0x0003000D,
+    WGPUSType_RequiredSubgroupLimits = 0x0003000E,
+    WGPUSType_SupportedSubgroupLimits = 0x0003000F,
     WGPUNativeSType_Force32 = 0x7FFFFFFF
 } WGPUNativeSType;
 
@@ -45,7 +53,8 @@ typedef enum WGPUNativeFeature {
     // WGPUNativeFeature_Multiview = 0x00030018,
     WGPUNativeFeature_VertexAttribute64bit = 0x00030019,
     WGPUNativeFeature_TextureFormatNv12 = 0x0003001A,
-    WGPUNativeFeature_RayTracingAccelerationStructure = 0x0003001B,
+    // Not supported: wgpu-core has no API to create, build or bind acceleration structures.
+    // WGPUNativeFeature_RayTracingAccelerationStructure = 0x0003001B,
     WGPUNativeFeature_RayQuery = 0x0003001C,
     WGPUNativeFeature_ShaderF64 = 0x0003001D,
     WGPUNativeFeature_ShaderI16 = 0x0003001E,
@@ -90,6 +99,9 @@ typedef enum WGPUInstanceFlag {
     WGPUInstanceFlag_Debug = 1 << 0,
     WGPUInstanceFlag_Validation = 1 << 1,
     WGPUInstanceFlag_DiscardHalLabels = 1 << 2,
+    // wgpu-native only: report objects that are still alive when the last
+    // reference to the instance is released, see WGPUInstanceExtras::leakCallback.
+    WGPUInstanceFlag_LeakDetection = 1 << 16,
     WGPUInstanceFlag_Force32 = 0x7FFFFFFF
 } WGPUInstanceFlag;
 typedef WGPUFlags WGPUInstanceFlags;
@@ -123,6 +135,51 @@ typedef enum WGPUNativeQueryType {
     WGPUNativeQueryType_Force32 = 0x7FFFFFFF
 } WGPUNativeQueryType WGPU_ENUM_ATTRIBUTE;
 
+typedef enum WGPUTranslatedSourceStatus {
+    WGPUTranslatedSourceStatus_Success = 0x00000000,
+    // The module was created from SPIR-V passed through to the backend,
+    // or wgpu-native was built without the shader_inspection feature.
+    WGPUTranslatedSourceStatus_Unavailable = 0x00000001,
+    WGPUTranslatedSourceStatus_Error = 0x00000002,
+    WGPUTranslatedSourceStatus_Force32 = 0x7FFFFFFF
+} WGPUTranslatedSourceStatus WGPU_ENUM_ATTRIBUTE;
+
+typedef enum WGPUShaderReflectionStatus {
+    WGPUShaderReflectionStatus_Success = 0x00000000,
+    // The module was created from SPIR-V passed through to the backend,
+    // or wgpu-native was built without the shader_inspection feature.
+    WGPUShaderReflectionStatus_Unavailable = 0x00000001,
+    WGPUShaderReflectionStatus_Error = 0x00000002,
+    WGPUShaderReflectionStatus_Force32 = 0x7FFFFFFF
+} WGPUShaderReflectionStatus WGPU_ENUM_ATTRIBUTE;
+
+typedef enum WGPUShaderBindingType {
+    WGPUShaderBindingType_Undefined = 0x00000000,
+    WGPUShaderBindingType_Buffer = 0x00000001,
+    WGPUShaderBindingType_Sampler = 0x00000002,
+    WGPUShaderBindingType_Texture = 0x00000003,
+    WGPUShaderBindingType_StorageTexture = 0x00000004,
+    WGPUShaderBindingType_AccelerationStructure = 0x00000005,
+    WGPUShaderBindingType_Force32 = 0x7FFFFFFF
+} WGPUShaderBindingType WGPU_ENUM_ATTRIBUTE;
+
+typedef enum WGPUShaderOverrideType {
+    WGPUShaderOverrideType_Undefined = 0x00000000,
+    WGPUShaderOverrideType_Bool = 0x00000001,
+    WGPUShaderOverrideType_Float16 = 0x00000002,
+    WGPUShaderOverrideType_Float32 = 0x00000003,
+    WGPUShaderOverrideType_Float64 = 0x00000004,
+    WGPUShaderOverrideType_Sint32 = 0x00000005,
+    WGPUShaderOverrideType_Uint32 = 0x00000006,
+    WGPUShaderOverrideType_Force32 = 0x7FFFFFFF
+} WGPUShaderOverrideType WGPU_ENUM_ATTRIBUTE;
+
+// Called once per leaked object, `label` is NULL for unlabeled objects.
+typedef void (*WGPULeakCallback)(char const * objectType, char const * label, void * userdata);
+typedef void (*WGPUCallbackTaskProc)(void * task);
+// Must call run(task) exactly once, from any thread.
+typedef void (*WGPUCallbackExecutor)(WGPUCallbackTaskProc run, void * task, void * userdata);
+
 typedef struct WGPUInstanceExtras {
     WGPUChainedStruct chain;
     WGPUInstanceBackendFlags backends;
@@ -131,6 +188,13 @@ typedef struct WGPUInstanceExtras {
     WGPUGles3MinorVersion gles3MinorVersion;
     const char * dxilPath;
     const char * dxcPath;
+    // Only used with WGPUInstanceFlag_LeakDetection, leaks are logged as warnings when NULL.
+    WGPULeakCallback leakCallback;
+    void * leakUserdata;
+    // wgpuBufferMapAsync callbacks are handed to this executor instead of being
+    // called from wgpuDevicePoll, wgpuQueueSubmit and the like. NULL calls them in place.
+    WGPUCallbackExecutor callbackExecutor;
+    void * callbackExecutorUserdata;
 } WGPUInstanceExtras;
 
 typedef struct WGPUDeviceExtras {
@@ -153,6 +217,22 @@ typedef struct WGPUSupportedLimitsExtras {
     WGPUNativeLimits limits;
 } WGPUSupportedLimitsExtras;
 
+// Range of subgroup sizes, chained on WGPURequiredLimits. 0 is treated like
+// WGPU_LIMIT_U32_UNDEFINED, so that a zero-initialized struct keeps the default range.
+typedef struct WGPURequiredSubgroupLimits {
+    WGPUChainedStruct chain;
+    uint32_t minSubgroupSize;
+    uint32_t maxSubgroupSize;
+} WGPURequiredSubgroupLimits;
+
+// Range of subgroup sizes, chained on WGPUSupportedLimits. Both are 0 if the
+// adapter doesn't support subgroups.
+typedef struct WGPUSupportedSubgroupLimits {
+    WGPUChainedStructOut chain;
+    uint32_t minSubgroupSize;
+    uint32_t maxSubgroupSize;
+} WGPUSupportedSubgroupLimits;
+
 typedef struct WGPUPushConstantRange {
     WGPUShaderStageFlags stages;
     uint32_t start;
@@ -177,6 +257,10 @@ typedef struct WGPUShaderDefine {
     char const * value;
 } WGPUShaderDefine;
 
+// `code` may hold several stages, each starting with `#pragma shader_stage(vertex)`,
+// `(fragment)` or `(compute)`. Only the section matching `stage` is compiled, along
+// with the code before the first section. Errors are reported with their location
+// in `code` by wgpuShaderModuleGetCompilationInfo.
 typedef struct WGPUShaderModuleGLSLDescriptor {
     WGPUChainedStruct chain;
     WGPUShaderStage stage;
@@ -185,6 +269,46 @@ typedef struct WGPUShaderModuleGLSLDescriptor {
     WGPUShaderDefine * defines;
 } WGPUShaderModuleGLSLDescriptor;
 
+// Options for naga's SPIR-V frontend, chained to a WGPUShaderModuleDescriptor
+// along with a WGPUShaderModuleSPIRVDescriptor. Without it, the coordinate
+// space is left as is and capabilities are checked strictly. It is ignored when
+// WGPUNativeFeature_SpirvShaderPassthrough is enabled, since naga does not
+// parse the SPIR-V then.
+typedef struct WGPUShaderModuleSPIRVOptions {
+    WGPUChainedStruct chain;
+    WGPUBool adjustCoordinateSpace;
+    WGPUBool strictCapabilities;
+    // Directory to dump the parsed block contexts to, or NULL.
+    char const * blockCtxDumpPrefix;
+} WGPUShaderModuleSPIRVOptions;
+
+// Returns the code of the file included with `#include "path"`, or NULL if it
+// cannot be found. The string only needs to stay valid until the callback returns again.
+typedef char const * (*WGPUShaderIncludeCallback)(char const * path, void * userdata);
+
+// Enables preprocessing of WGSL code, chained to a WGPUShaderModuleDescriptor
+// along with a WGPUShaderModuleWGSLDescriptor. Supports #define, #undef,
+// #ifdef, #ifndef, #else, #endif and #include "path" at the start of a line.
+// Errors point at the original files and lines.
+typedef struct WGPUShaderModuleWGSLPreprocessorOptions {
+    WGPUChainedStruct chain;
+    uint32_t defineCount;
+    // `value` may be NULL for defines only used by #ifdef.
+    WGPUShaderDefine * defines;
+    WGPU_NULLABLE WGPUShaderIncludeCallback includeCallback;
+    void * includeUserdata;
+} WGPUShaderModuleWGSLPreprocessorOptions;
+
+// A naga module as written by the `naga-ir` tool, skipping the parsing of
+// the shader source. Only accepted when wgpu-native is built with the `serde`
+// feature. Blobs written with a different naga version than wgpu-native's
+// are rejected with a validation error.
+typedef struct WGPUShaderModuleNagaIRDescriptor {
+    WGPUChainedStruct chain;
+    size_t size;
+    void const * data;
+} WGPUShaderModuleNagaIRDescriptor;
+
 typedef struct WGPUShaderModuleDescriptorSpirV {
     char const * label;
     uint32_t sourceSize;
@@ -258,6 +382,177 @@ typedef struct WGPUSurfaceConfigurationExtras {
     uint32_t desiredMaximumFrameLatency;
 } WGPUSurfaceConfigurationExtras WGPU_STRUCTURE_ATTRIBUTE;
 
+// `source` holds `sourceSize` bytes: SPIR-V words for Vulkan, and text that is
+// not null-terminated for the other backends. `message` is set on failure.
+typedef void (*WGPUShaderModuleGetTranslatedSourceCallback)(WGPUTranslatedSourceStatus status, void const * source, size_t sourceSize, char const * message, void * userdata);
+
+// A resource binding used by an entry point. Only the fields matching `type`
+// are set, the others are left undefined.
+typedef struct WGPUShaderBindingReflection {
+    // NULL for unnamed bindings.
+    char const * name;
+    uint32_t group;
+    uint32_t binding;
+    WGPUShaderBindingType type;
+    // WGPUBufferBindingType_Storage for read-write storage buffers.
+    WGPUBufferBindingType bufferType;
+    uint64_t minBindingSize;
+    WGPUSamplerBindingType samplerType;
+    // Sampled float textures are reported as WGPUTextureSampleType_Float.
+    WGPUTextureSampleType textureSampleType;
+    WGPUTextureViewDimension viewDimension;
+    WGPUBool multisampled;
+    WGPUStorageTextureAccess storageTextureAccess;
+    WGPUTextureFormat storageTextureFormat;
+    // Element count of a binding array: 0 if the binding isn't an array,
+    // WGPU_LIMIT_U32_UNDEFINED if the array is runtime-sized.
+    uint32_t count;
+} WGPUShaderBindingReflection;
+
+typedef struct WGPUShaderVertexAttributeReflection {
+    char const * name;
+    uint32_t shaderLocation;
+    // The 32-bit format matching the shader type, WGPUVertexFormat_Undefined
+    // if there is none.
+    WGPUVertexFormat format;
+} WGPUShaderVertexAttributeReflection;
+
+typedef struct WGPUShaderEntryPointReflection {
+    char const * name;
+    WGPUShaderStage stage;
+    // All zeros for non-compute stages.
+    uint32_t workgroupSize[3];
+    size_t bindingCount;
+    WGPUShaderBindingReflection const * bindings;
+    // Only set for vertex entry points.
+    size_t vertexAttributeCount;
+    WGPUShaderVertexAttributeReflection const * vertexAttributes;
+} WGPUShaderEntryPointReflection;
+
+typedef struct WGPUShaderOverrideReflection {
+    // NULL for overrides only known by their id.
+    char const * name;
+    // WGPU_LIMIT_U32_UNDEFINED if the override has no id.
+    uint32_t id;
+    WGPUShaderOverrideType type;
+    // Set if the default value is a constant, bools are 0.0 or 1.0.
+    WGPUBool hasDefaultValue;
+    double defaultValue;
+} WGPUShaderOverrideReflection;
+
+typedef struct WGPUShaderReflection {
+    size_t entryPointCount;
+    WGPUShaderEntryPointReflection const * entryPoints;
+    size_t overrideCount;
+    WGPUShaderOverrideReflection const * overrides;
+} WGPUShaderReflection;
+
+// `reflection` and everything it points to are only valid until the callback
+// returns. `message` is set on failure.
+typedef void (*WGPUShaderModuleGetReflectionCallback)(WGPUShaderReflectionStatus status, WGPUShaderReflection const * reflection, char const * message, void * userdata);
+
+typedef enum WGPUGpuProfilerResultsStatus {
+    WGPUGpuProfilerResultsStatus_Success = 0x00000000,
+    WGPUGpuProfilerResultsStatus_Error = 0x00000001,
+    WGPUGpuProfilerResultsStatus_Force32 = 0x7FFFFFFF
+} WGPUGpuProfilerResultsStatus WGPU_ENUM_ATTRIBUTE;
+
+typedef struct WGPUGpuProfilerDescriptor {
+    WGPUChainedStruct const * nextInChain;
+    WGPU_NULLABLE char const * label;
+    // Number of scopes that can be recorded before the results are read.
+    // Must be at least 1, otherwise a validation error is reported and NULL is returned.
+    uint32_t maxScopes;
+} WGPUGpuProfilerDescriptor WGPU_STRUCTURE_ATTRIBUTE;
+
+typedef struct WGPUGpuProfilerScope {
+    char const * label;
+    // Number of scopes this one is nested in.
+    uint32_t depth;
+    double durationNs;
+} WGPUGpuProfilerScope WGPU_STRUCTURE_ATTRIBUTE;
+
+// Scopes are listed in the order they began, and are only valid until the
+// callback returns. `message` is set on failure.
+typedef void (*WGPUGpuProfilerGetResultsCallback)(WGPUGpuProfilerResultsStatus status, size_t scopeCount, WGPUGpuProfilerScope const * scopes, char const * message, void * userdata);
+
+typedef struct WGPUStagingBeltDescriptor {
+    WGPUChainedStruct const * nextInChain;
+    WGPU_NULLABLE char const * label;
+    // Size of the staging buffers, larger writes get a buffer of their own.
+    uint64_t chunkSize;
+} WGPUStagingBeltDescriptor WGPU_STRUCTURE_ATTRIBUTE;
+
+// Futures returned by the *2 variants of the async functions. Their callback is
+// called according to the mode it was registered with:
+// - WaitAnyOnly: from wgpuInstanceWaitAny, once the future is waited on.
+// - AllowProcessEvents: also from wgpuInstanceProcessEvents.
+// - AllowSpontaneous: as soon as the operation completes, from whatever thread
+//   completes it, through WGPUInstanceExtras::callbackExecutor if set.
+typedef struct WGPUFuture {
+    uint64_t id;
+} WGPUFuture WGPU_STRUCTURE_ATTRIBUTE;
+
+typedef enum WGPUCallbackMode {
+    WGPUCallbackMode_WaitAnyOnly = 0x00000001,
+    WGPUCallbackMode_AllowProcessEvents = 0x00000002,
+    WGPUCallbackMode_AllowSpontaneous = 0x00000003,
+    WGPUCallbackMode_Force32 = 0x7FFFFFFF
+} WGPUCallbackMode WGPU_ENUM_ATTRIBUTE;
+
+typedef enum WGPUWaitStatus {
+    WGPUWaitStatus_Success = 0x00000001,
+    WGPUWaitStatus_TimedOut = 0x00000002,
+    WGPUWaitStatus_UnsupportedTimeout = 0x00000003,
+    WGPUWaitStatus_UnsupportedCount = 0x00000004,
+    WGPUWaitStatus_UnsupportedMixedSources = 0x00000005,
+    WGPUWaitStatus_Unknown = 0x00000006,
+    WGPUWaitStatus_Force32 = 0x7FFFFFFF
+} WGPUWaitStatus WGPU_ENUM_ATTRIBUTE;
+
+typedef struct WGPUFutureWaitInfo {
+    WGPUFuture future;
+    // Set by wgpuInstanceWaitAny when the future completed.
+    WGPUBool completed;
+} WGPUFutureWaitInfo WGPU_STRUCTURE_ATTRIBUTE;
+
+typedef void (*WGPUBufferMapCallback2)(WGPUBufferMapAsyncStatus status, void * userdata1, void * userdata2);
+typedef void (*WGPUQueueWorkDoneCallback2)(WGPUQueueWorkDoneStatus status, void * userdata1, void * userdata2);
+typedef void (*WGPURequestAdapterCallback2)(WGPURequestAdapterStatus status, WGPUAdapter adapter, char const * message, void * userdata1, void * userdata2);
+typedef void (*WGPURequestDeviceCallback2)(WGPURequestDeviceStatus status, WGPUDevice device, char const * message, void * userdata1, void * userdata2);
+
+typedef struct WGPUBufferMapCallbackInfo2 {
+    WGPUChainedStruct const * nextInChain;
+    WGPUCallbackMode mode;
+    WGPUBufferMapCallback2 callback;
+    void * userdata1;
+    void * userdata2;
+} WGPUBufferMapCallbackInfo2 WGPU_STRUCTURE_ATTRIBUTE;
+
+typedef struct WGPUQueueWorkDoneCallbackInfo2 {
+    WGPUChainedStruct const * nextInChain;
+    WGPUCallbackMode mode;
+    WGPUQueueWorkDoneCallback2 callback;
+    void * userdata1;
+    void * userdata2;
+} WGPUQueueWorkDoneCallbackInfo2 WGPU_STRUCTURE_ATTRIBUTE;
+
+typedef struct WGPURequestAdapterCallbackInfo2 {
+    WGPUChainedStruct const * nextInChain;
+    WGPUCallbackMode mode;
+    WGPURequestAdapterCallback2 callback;
+    void * userdata1;
+    void * userdata2;
+} WGPURequestAdapterCallbackInfo2 WGPU_STRUCTURE_ATTRIBUTE;
+
+typedef struct WGPURequestDeviceCallbackInfo2 {
+    WGPUChainedStruct const * nextInChain;
+    WGPUCallbackMode mode;
+    WGPURequestDeviceCallback2 callback;
+    void * userdata1;
+    void * userdata2;
+} WGPURequestDeviceCallbackInfo2 WGPU_STRUCTURE_ATTRIBUTE;
+
 typedef void (*WGPULogCallback)(WGPULogLevel level, char const * message, void * userdata);
 
 typedef enum WGPUNativeTextureFormat {
@@ -279,12 +574,25 @@ extern "C" {
 void wgpuGenerateReport(WGPUInstance instance, WGPUGlobalReport * report);
 size_t wgpuInstanceEnumerateAdapters(WGPUInstance instance, WGPU_NULLABLE WGPUInstanceEnumerateAdapterOptions const * options, WGPUAdapter * adapters);
 
+// Like wgpuQueueSubmit, returning the index of the submission. When the submission
+// fails, the error is reported to the device and the returned index is the one of an
+// empty submission made in its place, which can still be waited on.
 WGPUSubmissionIndex wgpuQueueSubmitForIndex(WGPUQueue queue, size_t commandCount, WGPUCommandBuffer const * commands);
 
 // Returns true if the queue is empty, or false if there are more queue submissions still in flight.
 WGPUBool wgpuDevicePoll(WGPUDevice device, WGPUBool wait, WGPU_NULLABLE WGPUWrappedSubmissionIndex const * wrappedSubmissionIndex);
 WGPUShaderModule wgpuDeviceCreateShaderModuleSpirV(WGPUDevice device, WGPUShaderModuleDescriptorSpirV const * descriptor);
 
+// Translates the module to the shading language used by `backend` with naga, for debugging.
+// `entryPoint` may be NULL to translate every entry point, except for OpenGL(ES) which needs one.
+// naga's default options for each language are used (SPIR-V 1.0, MSL 1.0, HLSL SM 5.1, GLSL 4.30
+// or ES 3.10) rather than the device's, so the output can differ from what the driver compiles.
+// Only available when wgpu-native is built with the shader_inspection cargo feature.
+void wgpuShaderModuleGetTranslatedSource(WGPUShaderModule shaderModule, WGPUBackendType backend, WGPU_NULLABLE char const * entryPoint, WGPUShaderModuleGetTranslatedSourceCallback callback, void * userdata);
+// Reflects the entry points, bindings and overrides of the module with naga.
+// Only available when wgpu-native is built with the shader_inspection cargo feature.
+void wgpuShaderModuleGetReflection(WGPUShaderModule shaderModule, WGPUShaderModuleGetReflectionCallback callback, void * userdata);
+
 void wgpuSetLogCallback(WGPULogCallback callback, void * userdata);
 
 void wgpuSetLogLevel(WGPULogLevel level);
@@ -304,10 +612,61 @@ void wgpuComputePassEncoderBeginPipelineStatisticsQuery(WGPUComputePassEncoder c
 void wgpuComputePassEncoderEndPipelineStatisticsQuery(WGPUComputePassEncoder computePassEncoder);
 void wgpuRenderPassEncoderBeginPipelineStatisticsQuery(WGPURenderPassEncoder renderPassEncoder, WGPUQuerySet querySet, uint32_t queryIndex);
 void wgpuRenderPassEncoderEndPipelineStatisticsQuery(WGPURenderPassEncoder renderPassEncoder);
+// Writes the statistic resolved in each uint64_t slot of a pipeline statistics query
+// to `statistics` and returns their count. Pass NULL to only query the count.
+size_t wgpuQuerySetGetPipelineStatistics(WGPUQuerySet querySet, WGPUPipelineStatisticName * statistics);
 
 void wgpuComputePassEncoderWriteTimestamp(WGPUComputePassEncoder computePassEncoder, WGPUQuerySet querySet, uint32_t queryIndex);
 void wgpuRenderPassEncoderWriteTimestamp(WGPURenderPassEncoder renderPassEncoder, WGPUQuerySet querySet, uint32_t queryIndex);
 
+// Nanoseconds per timestamp query tick.
+float wgpuQueueGetTimestampPeriod(WGPUQueue queue);
+
+// Measures the GPU time spent in labeled, possibly nested, scopes of command encoders.
+// Needs WGPUFeatureName_TimestampQuery and WGPUNativeFeature_TimestampQueryInsideEncoders.
+// Once all scopes are recorded, resolve them in the last command encoder, submit it
+// and read the results, which arrive once the device is polled. Scopes can be
+// recorded again when the results callback has been called.
+WGPUGpuProfiler wgpuDeviceCreateGpuProfiler(WGPUDevice device, WGPUGpuProfilerDescriptor const * descriptor);
+void wgpuGpuProfilerBeginScope(WGPUGpuProfiler profiler, WGPUCommandEncoder commandEncoder, char const * label);
+void wgpuGpuProfilerEndScope(WGPUGpuProfiler profiler, WGPUCommandEncoder commandEncoder);
+void wgpuGpuProfilerResolve(WGPUGpuProfiler profiler, WGPUCommandEncoder commandEncoder);
+void wgpuGpuProfilerGetResults(WGPUGpuProfiler profiler, WGPUGpuProfilerGetResultsCallback callback, void * userdata);
+void wgpuGpuProfilerReference(WGPUGpuProfiler profiler);
+void wgpuGpuProfilerRelease(WGPUGpuProfiler profiler);
+
+// Uploads data to buffers through a set of reused, mapped staging buffers.
+// Write the data to the pointer returned by WriteBuffer, which is valid until
+// Finish records the copies in a command encoder. Once that encoder's command
+// buffer is submitted, Recall makes the staging buffers available again as
+// soon as their mapping completes, which happens when the device is polled.
+WGPUStagingBelt wgpuDeviceCreateStagingBelt(WGPUDevice device, WGPUStagingBeltDescriptor const * descriptor);
+// Returns NULL on error. `size` must be a non-zero multiple of 4.
+void * wgpuStagingBeltWriteBuffer(WGPUStagingBelt belt, WGPUBuffer buffer, uint64_t offset, uint64_t size);
+void wgpuStagingBeltFinish(WGPUStagingBelt belt, WGPUCommandEncoder commandEncoder);
+void wgpuStagingBeltRecall(WGPUStagingBelt belt);
+void wgpuStagingBeltReference(WGPUStagingBelt belt);
+void wgpuStagingBeltRelease(WGPUStagingBelt belt);
+
+// Fills mip levels baseMipLevel + 1 up to baseMipLevel + mipLevelCount - 1 of every
+// array layer, each from the level above it, with a linear filter. Pass
+// WGPU_MIP_LEVEL_COUNT_UNDEFINED as mipLevelCount to fill all levels after the base one.
+// The texture must be a single-sampled 2D texture of a filterable color format,
+// with the TextureBinding and RenderAttachment usages.
+void wgpuCommandEncoderGenerateMipmaps(WGPUCommandEncoder commandEncoder, WGPUTexture texture, uint32_t baseMipLevel, uint32_t mipLevelCount);
+
+WGPUFuture wgpuAdapterRequestDevice2(WGPUAdapter adapter, WGPU_NULLABLE WGPUDeviceDescriptor const * descriptor, WGPURequestDeviceCallbackInfo2 callbackInfo);
+WGPUFuture wgpuBufferMapAsync2(WGPUBuffer buffer, WGPUMapModeFlags mode, size_t offset, size_t size, WGPUBufferMapCallbackInfo2 callbackInfo);
+WGPUFuture wgpuInstanceRequestAdapter2(WGPUInstance instance, WGPU_NULLABLE WGPURequestAdapterOptions const * options, WGPURequestAdapterCallbackInfo2 callbackInfo);
+// Polls the devices of the instance until at least one of the futures completed or
+// timeoutNS nanoseconds passed, without waiting for unrelated GPU work to finish.
+// Calls the callbacks of the futures that completed and sets their `completed` flag.
+// Returns WGPUWaitStatus_TimedOut when none did, a timeoutNS of 0 only checks once.
+// Returns WGPUWaitStatus_Unknown without waiting if one of the futures is null or
+// wasn't created by this instance.
+WGPUWaitStatus wgpuInstanceWaitAny(WGPUInstance instance, size_t futureCount, WGPUFutureWaitInfo * futures, uint64_t timeoutNS);
+WGPUFuture wgpuQueueOnSubmittedWorkDone2(WGPUQueue queue, WGPUQueueWorkDoneCallbackInfo2 callbackInfo);
+
 #ifdef __cplusplus
 } // extern "C"
 #endif
@@ -1,9 +1,13 @@
-use crate::utils::{make_slice, ptr_into_label, ptr_into_pathbuf};
+use crate::handles::LeakDetector;
+use crate::utils::{
+    get_base_device_limits_from_adapter_limits, make_slice, ptr_into_label, ptr_into_pathbuf,
+    CallbackExecutor,
+};
 use crate::{follow_chain, map_enum};
 use crate::{native, UncapturedErrorCallback};
 use std::num::{NonZeroIsize, NonZeroU32, NonZeroU64};
 use std::ptr::NonNull;
-use std::{borrow::Cow, ffi::CStr};
+use std::{borrow::Cow, collections::HashMap, ffi::CStr};
 
 map_enum!(map_load_op, WGPULoadOp, wgc::command::LoadOp, Clear, Load);
 map_enum!(
@@ -199,6 +203,16 @@ map_enum!(
     Version2
 );
 
+map_enum!(
+    map_callback_mode,
+    WGPUCallbackMode,
+    crate::futures::CallbackMode,
+    "invalid callback mode",
+    WaitAnyOnly,
+    AllowProcessEvents,
+    AllowSpontaneous
+);
+
 map_enum!(
     map_storage_texture_access,
     WGPUStorageTextureAccess,
@@ -273,10 +287,14 @@ pub fn map_instance_flags(flags: native::WGPUInstanceFlag) -> wgt::InstanceFlags
 }
 
 #[inline]
-pub fn map_instance_descriptor(
+pub(crate) fn map_instance_descriptor(
     _base: &native::WGPUInstanceDescriptor,
     extras: Option<&native::WGPUInstanceExtras>,
-) -> wgt::InstanceDescriptor {
+) -> (
+    wgt::InstanceDescriptor,
+    Option<LeakDetector>,
+    CallbackExecutor,
+) {
     if let Some(extras) = extras {
         let dx12_shader_compiler = match extras.dx12ShaderCompiler {
             native::WGPUDx12Compiler_Fxc => wgt::Dx12Compiler::Fxc,
@@ -287,49 +305,74 @@ pub fn map_instance_descriptor(
             _ => wgt::Dx12Compiler::default(),
         };
 
-        wgt::InstanceDescriptor {
-            backends: map_instance_backend_flags(extras.backends as native::WGPUInstanceBackend),
-            dx12_shader_compiler,
-            gles_minor_version: map_gles3_minor_version(extras.gles3MinorVersion),
-            flags: match extras.flags as native::WGPUInstanceFlag {
-                native::WGPUInstanceFlag_Default => wgt::InstanceFlags::default(),
-                flags => map_instance_flags(flags),
+        let flags = extras.flags as native::WGPUInstanceFlag;
+        let leak_detector = match flags & native::WGPUInstanceFlag_LeakDetection {
+            0 => None,
+            _ => Some(LeakDetector::new(extras.leakCallback, extras.leakUserdata)),
+        };
+
+        (
+            wgt::InstanceDescriptor {
+                backends: map_instance_backend_flags(
+                    extras.backends as native::WGPUInstanceBackend,
+                ),
+                dx12_shader_compiler,
+                gles_minor_version: map_gles3_minor_version(extras.gles3MinorVersion),
+                // wgpu-native only flags are not forwarded to wgpu-core
+                flags: match flags & !native::WGPUInstanceFlag_LeakDetection {
+                    native::WGPUInstanceFlag_Default => wgt::InstanceFlags::default(),
+                    flags => map_instance_flags(flags),
+                },
             },
-        }
+            leak_detector,
+            CallbackExecutor::new(extras.callbackExecutor, extras.callbackExecutorUserdata),
+        )
     } else {
-        wgt::InstanceDescriptor::default()
+        (
+            wgt::InstanceDescriptor::default(),
+            None,
+            CallbackExecutor::new(None, std::ptr::null_mut()),
+        )
     }
 }
 
+/// The device descriptor, its limits, the trace path and the uncaptured error callback.
+type MappedDeviceDescriptor<'a> = (
+    wgt::DeviceDescriptor<wgc::Label<'a>>,
+    RequiredLimits,
+    *const std::ffi::c_char,
+    Option<UncapturedErrorCallback>,
+);
+
 #[inline]
 pub(crate) fn map_device_descriptor<'a>(
     des: &native::WGPUDeviceDescriptor,
-    base_limits: wgt::Limits,
+    adapter_limits: &wgt::Limits,
     extras: Option<&native::WGPUDeviceExtras>,
-) -> (
-    wgt::DeviceDescriptor<wgc::Label<'a>>,
-    *const std::ffi::c_char,
-    Option<UncapturedErrorCallback>,
-) {
-    (
+) -> Result<MappedDeviceDescriptor<'a>, RequiredLimitsError> {
+    let required_limits = match unsafe { des.requiredLimits.as_ref() } {
+        Some(required_limits) => unsafe {
+            follow_chain!(
+                map_required_limits((required_limits, adapter_limits),
+                WGPUSType_RequiredLimitsExtras => native::WGPURequiredLimitsExtras,
+                WGPUSType_RequiredSubgroupLimits => native::WGPURequiredSubgroupLimits)
+            )?
+        },
+        None => RequiredLimits::base(adapter_limits),
+    };
+
+    Ok((
         wgt::DeviceDescriptor {
             label: ptr_into_label(des.label),
             required_features: map_features(make_slice(
                 des.requiredFeatures,
                 des.requiredFeatureCount,
             )),
-            required_limits: match unsafe { des.requiredLimits.as_ref() } {
-                Some(required_limits) => unsafe {
-                    follow_chain!(
-                        map_required_limits((required_limits, base_limits),
-                        WGPUSType_RequiredLimitsExtras => native::WGPURequiredLimitsExtras)
-                    )
-                },
-                None => base_limits,
-            },
+            required_limits: required_limits.limits.clone(),
             // TODO(wgpu.h)
             memory_hints: Default::default(),
         },
+        required_limits,
         match extras {
             Some(extras) => extras.tracePath,
             None => std::ptr::null(),
@@ -341,23 +384,24 @@ pub(crate) fn map_device_descriptor<'a>(
                 userdata: des.uncapturedErrorCallbackInfo.userdata,
             }),
         },
-    )
+    ))
 }
 
 #[inline]
 pub unsafe fn map_pipeline_layout_descriptor<'a>(
     des: &native::WGPUPipelineLayoutDescriptor,
     extras: Option<&native::WGPUPipelineLayoutExtras>,
-) -> wgc::binding_model::PipelineLayoutDescriptor<'a> {
-    let bind_group_layouts = make_slice(des.bindGroupLayouts, des.bindGroupLayoutCount)
-        .iter()
-        .map(|layout| {
-            layout
-                .as_ref()
-                .expect("invalid bind group layout for pipeline layout descriptor")
-                .id
-        })
-        .collect::<Vec<_>>();
+) -> Option<wgc::binding_model::PipelineLayoutDescriptor<'a>> {
+    let mut bind_group_layouts = Vec::with_capacity(des.bindGroupLayoutCount);
+    for &layout in make_slice(des.bindGroupLayouts, des.bindGroupLayoutCount) {
+        bind_group_layouts.push(
+            crate::validate_handle!(
+                layout,
+                "invalid bind group layout for pipeline layout descriptor"
+            )
+            .id,
+        );
+    }
 
     let push_constant_ranges = extras.map_or(Vec::new(), |extras| {
         make_slice(extras.pushConstantRanges, extras.pushConstantRangeCount)
@@ -370,11 +414,11 @@ pub unsafe fn map_pipeline_layout_descriptor<'a>(
             .collect()
     });
 
-    return wgc::binding_model::PipelineLayoutDescriptor {
+    return Some(wgc::binding_model::PipelineLayoutDescriptor {
         label: ptr_into_label(des.label),
         bind_group_layouts: Cow::from(bind_group_layouts),
         push_constant_ranges: Cow::from(push_constant_ranges),
-    };
+    });
 }
 
 #[inline]
@@ -388,8 +432,7 @@ pub fn write_limits_struct(
     limits.maxTextureDimension3D = wgt_limits.max_texture_dimension_3d;
     limits.maxTextureArrayLayers = wgt_limits.max_texture_array_layers;
     limits.maxBindGroups = wgt_limits.max_bind_groups;
-    // TODO: not yet in wgt
-    // limits.maxBindGroupsPlusVertexBuffers = wgt_limits.max_bind_groups_plus_vertex_buffers;
+    limits.maxBindGroupsPlusVertexBuffers = supported_bind_groups_plus_vertex_buffers(&wgt_limits);
     limits.maxBindingsPerBindGroup = wgt_limits.max_bindings_per_bind_group;
     limits.maxDynamicUniformBuffersPerPipelineLayout =
         wgt_limits.max_dynamic_uniform_buffers_per_pipeline_layout;
@@ -409,12 +452,9 @@ pub fn write_limits_struct(
     limits.minUniformBufferOffsetAlignment = wgt_limits.min_uniform_buffer_offset_alignment;
     limits.minStorageBufferOffsetAlignment = wgt_limits.min_storage_buffer_offset_alignment;
     limits.maxInterStageShaderComponents = wgt_limits.max_inter_stage_shader_components;
-    // TODO: not yet in wgt
-    // limits.maxInterStageShaderVariables = wgt_limits.max_inter_stage_shader_variables;
-    // TODO: not yet in wgt
-    // limits.maxColorAttachments = wgt_limits.max_color_attachments;
-    // TODO: not yet in wgt
-    // limits.maxColorAttachmentBytesPerSample = wgt_limits.max_color_attachment_bytes_per_sample;
+    limits.maxInterStageShaderVariables = supported_inter_stage_shader_variables(&wgt_limits);
+    limits.maxColorAttachments = wgt_limits.max_color_attachments;
+    limits.maxColorAttachmentBytesPerSample = wgt_limits.max_color_attachment_bytes_per_sample;
     limits.maxComputeWorkgroupStorageSize = wgt_limits.max_compute_workgroup_storage_size;
     limits.maxComputeInvocationsPerWorkgroup = wgt_limits.max_compute_invocations_per_workgroup;
     limits.maxComputeWorkgroupSizeX = wgt_limits.max_compute_workgroup_size_x;
@@ -423,32 +463,96 @@ pub fn write_limits_struct(
     limits.maxComputeWorkgroupsPerDimension = wgt_limits.max_compute_workgroups_per_dimension;
     supported_limits.limits = limits;
 
-    if let Some(native::WGPUChainedStructOut {
-        sType: native::WGPUSType_SupportedLimitsExtras,
-        ..
-    }) = unsafe { supported_limits.nextInChain.as_ref() }
-    {
-        unsafe {
-            let extras = std::mem::transmute::<
-                *mut native::WGPUChainedStructOut,
-                *mut native::WGPUSupportedLimitsExtras,
-            >(supported_limits.nextInChain);
-            (*extras).limits = native::WGPUNativeLimits {
-                maxPushConstantSize: wgt_limits.max_push_constant_size,
-                maxNonSamplerBindings: wgt_limits.max_non_sampler_bindings,
-            };
+    let mut chain = supported_limits.nextInChain;
+    while let Some(&native::WGPUChainedStructOut { sType, next }) = unsafe { chain.as_ref() } {
+        match sType {
+            native::WGPUSType_SupportedLimitsExtras => unsafe {
+                let extras = chain as *mut native::WGPUSupportedLimitsExtras;
+                (*extras).limits = native::WGPUNativeLimits {
+                    maxPushConstantSize: wgt_limits.max_push_constant_size,
+                    maxNonSamplerBindings: wgt_limits.max_non_sampler_bindings,
+                };
+            },
+            native::WGPUSType_SupportedSubgroupLimits => unsafe {
+                let subgroup_limits = chain as *mut native::WGPUSupportedSubgroupLimits;
+                (*subgroup_limits).minSubgroupSize = wgt_limits.min_subgroup_size;
+                (*subgroup_limits).maxSubgroupSize = wgt_limits.max_subgroup_size;
+            },
+            _ => (),
         }
-    };
+        chain = next;
+    }
+}
+
+/// WebGPU default for `maxBindGroupsPlusVertexBuffers`.
+const DEFAULT_MAX_BIND_GROUPS_PLUS_VERTEX_BUFFERS: u32 = 24;
+
+// wgpu-core has no `maxBindGroupsPlusVertexBuffers` nor `maxInterStageShaderVariables`,
+// the values supported by an adapter are derived from the limits it does have.
+
+#[inline]
+fn supported_bind_groups_plus_vertex_buffers(limits: &wgt::Limits) -> u32 {
+    limits.max_bind_groups + limits.max_vertex_buffers
+}
+
+/// wgpu-core's components limit leaves out the position output, so its
+/// default of 60 components stands for the 16 variables WebGPU guarantees.
+#[inline]
+fn supported_inter_stage_shader_variables(limits: &wgt::Limits) -> u32 {
+    (limits.max_inter_stage_shader_components / 4).max(16)
+}
+
+/// Limits requested for a device.
+#[derive(Clone, Debug)]
+pub struct RequiredLimits {
+    pub limits: wgt::Limits,
+    /// Not enforced by wgpu-core, checked when creating render pipelines.
+    pub max_bind_groups_plus_vertex_buffers: u32,
+}
+
+impl RequiredLimits {
+    pub fn base(adapter_limits: &wgt::Limits) -> RequiredLimits {
+        RequiredLimits {
+            limits: get_base_device_limits_from_adapter_limits(adapter_limits),
+            max_bind_groups_plus_vertex_buffers: DEFAULT_MAX_BIND_GROUPS_PLUS_VERTEX_BUFFERS
+                .min(supported_bind_groups_plus_vertex_buffers(adapter_limits)),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Limit '{name}' value {requested} is better than allowed {allowed}")]
+pub struct RequiredLimitsError {
+    name: &'static str,
+    requested: u32,
+    allowed: u32,
 }
 
 #[inline]
 pub fn map_required_limits(
     required_limits: &native::WGPURequiredLimits,
-    base_limits: wgt::Limits,
+    adapter_limits: &wgt::Limits,
     extras: Option<&native::WGPURequiredLimitsExtras>,
-) -> wgt::Limits {
+    subgroup_limits: Option<&native::WGPURequiredSubgroupLimits>,
+) -> Result<RequiredLimits, RequiredLimitsError> {
     let limits = required_limits.limits;
-    let mut wgt_limits = base_limits;
+    let RequiredLimits {
+        limits: mut wgt_limits,
+        mut max_bind_groups_plus_vertex_buffers,
+    } = RequiredLimits::base(adapter_limits);
+
+    // The other limits are checked by wgpu-core when creating the device.
+    let check = |name, requested, allowed| {
+        if requested > allowed {
+            Err(RequiredLimitsError {
+                name,
+                requested,
+                allowed,
+            })
+        } else {
+            Ok(requested)
+        }
+    };
     if limits.maxTextureDimension1D != native::WGPU_LIMIT_U32_UNDEFINED {
         wgt_limits.max_texture_dimension_1d = limits.maxTextureDimension1D;
     }
@@ -464,10 +568,13 @@ pub fn map_required_limits(
     if limits.maxBindGroups != native::WGPU_LIMIT_U32_UNDEFINED {
         wgt_limits.max_bind_groups = limits.maxBindGroups;
     }
-    // TODO: not yet in wgt
-    // if limits.maxBindGroupsPlusVertexBuffers != native::WGPU_LIMIT_U32_UNDEFINED {
-    //     wgt_limits.max_bind_groups_plus_vertex_buffers = limits.maxBindGroupsPlusVertexBuffers;
-    // }
+    if limits.maxBindGroupsPlusVertexBuffers != native::WGPU_LIMIT_U32_UNDEFINED {
+        max_bind_groups_plus_vertex_buffers = check(
+            "maxBindGroupsPlusVertexBuffers",
+            limits.maxBindGroupsPlusVertexBuffers,
+            supported_bind_groups_plus_vertex_buffers(adapter_limits),
+        )?;
+    }
     if limits.maxBindingsPerBindGroup != native::WGPU_LIMIT_U32_UNDEFINED {
         wgt_limits.max_bindings_per_bind_group = limits.maxBindingsPerBindGroup;
     }
@@ -521,18 +628,31 @@ pub fn map_required_limits(
     if limits.maxInterStageShaderComponents != native::WGPU_LIMIT_U32_UNDEFINED {
         wgt_limits.max_inter_stage_shader_components = limits.maxInterStageShaderComponents;
     }
-    // TODO: not yet in wgt
-    // if limits.maxInterStageShaderVariables != native::WGPU_LIMIT_U32_UNDEFINED {
-    //     wgt_limits.max_inter_stage_shader_variables = limits.maxInterStageShaderVariables;
-    // }
-    // TODO: not yet in wgt
-    // if limits.maxColorAttachments != native::WGPU_LIMIT_U32_UNDEFINED {
-    //     wgt_limits.max_color_attachments = limits.maxColorAttachments;
-    // }
-    // TODO: not yet in wgt
-    // if limits.maxColorAttachmentBytesPerSample != native::WGPU_LIMIT_U32_UNDEFINED {
-    //     wgt_limits.max_color_attachment_bytes_per_sample = limits.maxColorAttachmentBytesPerSample;
-    // }
+    if limits.maxInterStageShaderVariables != native::WGPU_LIMIT_U32_UNDEFINED {
+        let variables = check(
+            "maxInterStageShaderVariables",
+            limits.maxInterStageShaderVariables,
+            supported_inter_stage_shader_variables(adapter_limits),
+        )?;
+        // Each variable takes up to four components.
+        wgt_limits.max_inter_stage_shader_components = wgt_limits
+            .max_inter_stage_shader_components
+            .min(variables * 4);
+    }
+    if limits.maxColorAttachments != native::WGPU_LIMIT_U32_UNDEFINED {
+        wgt_limits.max_color_attachments = check(
+            "maxColorAttachments",
+            limits.maxColorAttachments,
+            adapter_limits.max_color_attachments,
+        )?;
+    }
+    if limits.maxColorAttachmentBytesPerSample != native::WGPU_LIMIT_U32_UNDEFINED {
+        wgt_limits.max_color_attachment_bytes_per_sample = check(
+            "maxColorAttachmentBytesPerSample",
+            limits.maxColorAttachmentBytesPerSample,
+            adapter_limits.max_color_attachment_bytes_per_sample,
+        )?;
+    }
     if limits.maxComputeWorkgroupStorageSize != native::WGPU_LIMIT_U32_UNDEFINED {
         wgt_limits.max_compute_workgroup_storage_size = limits.maxComputeWorkgroupStorageSize;
     }
@@ -560,7 +680,21 @@ pub fn map_required_limits(
             wgt_limits.max_non_sampler_bindings = limits.maxNonSamplerBindings;
         }
     }
-    wgt_limits
+    if let Some(subgroup_limits) = subgroup_limits {
+        // No adapter has subgroups of size 0, so 0 means undefined as well.
+        match subgroup_limits.minSubgroupSize {
+            0 | native::WGPU_LIMIT_U32_UNDEFINED => (),
+            size => wgt_limits.min_subgroup_size = size,
+        }
+        match subgroup_limits.maxSubgroupSize {
+            0 | native::WGPU_LIMIT_U32_UNDEFINED => (),
+            size => wgt_limits.max_subgroup_size = size,
+        }
+    }
+    Ok(RequiredLimits {
+        limits: wgt_limits,
+        max_bind_groups_plus_vertex_buffers,
+    })
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -570,37 +704,152 @@ pub enum ShaderParseError {
     Spirv(#[from] naga::front::spv::Error),
     #[cfg(feature = "glsl")]
     #[error(transparent)]
-    Glsl(#[from] naga::front::glsl::ParseErrors),
+    Glsl(#[from] crate::shader::GlslParseErrors),
+    #[cfg(feature = "wgsl")]
+    #[error(transparent)]
+    Preprocess(#[from] crate::preprocessor::PreprocessError),
+    #[cfg(feature = "wgsl")]
+    #[error("the name or value of the preprocessor define {0:?} is not valid UTF-8")]
+    InvalidDefine(String),
+    #[cfg(feature = "serde")]
+    #[error(transparent)]
+    NagaIr(#[from] crate::naga_ir::NagaIrError),
+    #[cfg(not(feature = "serde"))]
+    #[error("naga IR shader modules need the `serde` feature")]
+    NagaIrUnsupported,
+}
+
+impl ShaderParseError {
+    /// Messages for `wgpuShaderModuleGetCompilationInfo`, one per GLSL error.
+    pub(crate) fn compilation_messages(&self) -> Vec<crate::shader::CompilationMessage> {
+        match self {
+            #[cfg(feature = "glsl")]
+            ShaderParseError::Glsl(errors) => errors.compilation_messages(),
+            #[allow(unreachable_patterns)]
+            _ => vec![crate::shader::CompilationMessage::new(self.to_string())],
+        }
+    }
+}
+
+#[cfg(feature = "wgsl")]
+pub use crate::preprocessor::LineMap;
+
+/// Only WGSL can be preprocessed, so there is never a line map without it.
+#[cfg(not(feature = "wgsl"))]
+pub enum LineMap {}
+
+#[cfg(not(feature = "wgsl"))]
+impl LineMap {
+    pub(crate) fn remap_error(
+        &self,
+        _: &wgc::pipeline::CreateShaderModuleError,
+    ) -> Option<(std::convert::Infallible, crate::shader::CompilationMessage)> {
+        match *self {}
+    }
 }
 
 #[inline]
+#[cfg_attr(
+    not(all(feature = "wgsl", feature = "spirv", feature = "glsl")),
+    allow(unused_variables)
+)]
 pub fn map_shader_module<'a>(
     _: &native::WGPUShaderModuleDescriptor,
     spirv: Option<&native::WGPUShaderModuleSPIRVDescriptor>,
     wgsl: Option<&native::WGPUShaderModuleWGSLDescriptor>,
     glsl: Option<&native::WGPUShaderModuleGLSLDescriptor>,
-) -> Result<wgc::pipeline::ShaderModuleSource<'a>, ShaderParseError> {
+    spirv_options: Option<&native::WGPUShaderModuleSPIRVOptions>,
+    wgsl_preprocessor: Option<&native::WGPUShaderModuleWGSLPreprocessorOptions>,
+    naga_ir: Option<&native::WGPUShaderModuleNagaIRDescriptor>,
+) -> Result<(wgc::pipeline::ShaderModuleSource<'a>, Option<LineMap>), ShaderParseError> {
+    #[cfg(feature = "serde")]
+    if let Some(naga_ir) = naga_ir {
+        let data = make_slice(naga_ir.data as *const u8, naga_ir.size);
+        let module = crate::naga_ir::decode(data)?;
+        return Ok((
+            wgc::pipeline::ShaderModuleSource::Naga(Cow::Owned(module)),
+            None,
+        ));
+    }
+    #[cfg(not(feature = "serde"))]
+    if naga_ir.is_some() {
+        return Err(ShaderParseError::NagaIrUnsupported);
+    }
+
     #[cfg(feature = "wgsl")]
     if let Some(wgsl) = wgsl {
         let c_str: &CStr = unsafe { CStr::from_ptr(wgsl.code) };
         let str_slice: &str = c_str.to_str().expect("not a valid utf-8 string");
-        return Ok(wgc::pipeline::ShaderModuleSource::Wgsl(Cow::Borrowed(
-            str_slice,
-        )));
+
+        if let Some(preprocessor) = wgsl_preprocessor {
+            let defines = make_slice(preprocessor.defines, preprocessor.defineCount as usize)
+                .iter()
+                .map(|define| {
+                    let name = unsafe { CStr::from_ptr(define.name) };
+                    let value = match define.value.is_null() {
+                        true => Ok(""),
+                        false => unsafe { CStr::from_ptr(define.value) }.to_str(),
+                    };
+                    match (name.to_str(), value) {
+                        (Ok(name), Ok(value)) => Ok((name.to_string(), value.to_string())),
+                        _ => Err(ShaderParseError::InvalidDefine(
+                            name.to_string_lossy().into_owned(),
+                        )),
+                    }
+                })
+                .collect::<Result<_, _>>()?;
+            let mut include = |path: &str| {
+                let callback = preprocessor.includeCallback?;
+                let path = std::ffi::CString::new(path).ok()?;
+                let code = unsafe { callback(path.as_ptr(), preprocessor.includeUserdata) };
+                match code.is_null() {
+                    true => None,
+                    false => Some(
+                        unsafe { CStr::from_ptr(code) }
+                            .to_string_lossy()
+                            .into_owned(),
+                    ),
+                }
+            };
+
+            let (code, line_map) =
+                crate::preprocessor::preprocess(str_slice, defines, &mut include)?;
+            return Ok((
+                wgc::pipeline::ShaderModuleSource::Wgsl(Cow::Owned(code)),
+                Some(line_map),
+            ));
+        }
+
+        return Ok((
+            wgc::pipeline::ShaderModuleSource::Wgsl(Cow::Borrowed(str_slice)),
+            None,
+        ));
     }
 
     #[cfg(feature = "spirv")]
     if let Some(spirv) = spirv {
         let slice = make_slice(spirv.code, spirv.codeSize as usize);
         // Parse the given shader code and store its representation.
-        let options = naga::front::spv::Options {
-            adjust_coordinate_space: false, // we require NDC_Y_UP feature
-            strict_capabilities: true,
-            block_ctx_dump_prefix: None,
+        let options = match spirv_options {
+            Some(spirv_options) => naga::front::spv::Options {
+                adjust_coordinate_space: spirv_options.adjustCoordinateSpace != 0,
+                strict_capabilities: spirv_options.strictCapabilities != 0,
+                block_ctx_dump_prefix: ptr_into_pathbuf(spirv_options.blockCtxDumpPrefix),
+            },
+            None => naga::front::spv::Options {
+                adjust_coordinate_space: false, // we require NDC_Y_UP feature
+                strict_capabilities: true,
+                block_ctx_dump_prefix: None,
+            },
         };
         let frontend = naga::front::spv::Frontend::new(slice.iter().cloned(), &options);
         match frontend.parse() {
-            Ok(module) => return Ok(wgc::pipeline::ShaderModuleSource::Naga(Cow::Owned(module))),
+            Ok(module) => {
+                return Ok((
+                    wgc::pipeline::ShaderModuleSource::Naga(Cow::Owned(module)),
+                    None,
+                ))
+            }
             Err(cause) => return Err(ShaderParseError::Spirv(cause)),
         };
     }
@@ -627,59 +876,158 @@ pub fn map_shader_module<'a>(
                 .insert(String::from(name_str_slice), String::from(value_str_slice));
         }
 
+        let code =
+            crate::shader::select_glsl_stage(str_slice, options.stage).map_err(|causes| {
+                ShaderParseError::Glsl(crate::shader::GlslParseErrors::new(causes, str_slice))
+            })?;
         let mut frontend = naga::front::glsl::Frontend::default();
-        match frontend.parse(&options, str_slice) {
-            Ok(module) => return Ok(wgc::pipeline::ShaderModuleSource::Naga(Cow::Owned(module))),
-            Err(causes) => return Err(ShaderParseError::Glsl(causes)),
+        match frontend.parse(&options, &code) {
+            Ok(module) => {
+                return Ok((
+                    wgc::pipeline::ShaderModuleSource::Naga(Cow::Owned(module)),
+                    None,
+                ))
+            }
+            Err(causes) => {
+                return Err(ShaderParseError::Glsl(crate::shader::GlslParseErrors::new(
+                    causes, str_slice,
+                )))
+            }
         };
     }
 
     panic!("Shader not provided.");
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineConstantErrorKind {
+    #[error("key {0:?} is not valid UTF-8")]
+    InvalidKey(String),
+    #[error("the shader has no override {0:?}")]
+    UnknownKey(String),
+    #[error("value {value} of override {key:?} doesn't fit in {ty}")]
+    OutOfRange {
+        key: String,
+        value: f64,
+        ty: &'static str,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid constants for the {stage} stage: {kind}")]
+pub struct PipelineConstantError {
+    stage: &'static str,
+    kind: PipelineConstantErrorKind,
+}
+
+/// Maps the constants of a pipeline stage, checking them against the overrides
+/// of the shader when they are known. Otherwise keys that aren't UTF-8 are
+/// converted lossily, and left for wgpu-core to ignore.
+pub(crate) fn map_pipeline_constants(
+    stage: naga::ShaderStage,
+    overrides: Option<&HashMap<String, naga::Scalar>>,
+    entries: &[native::WGPUConstantEntry],
+) -> Result<HashMap<String, f64>, PipelineConstantError> {
+    let error = |kind| PipelineConstantError {
+        stage: match stage {
+            naga::ShaderStage::Vertex => "vertex",
+            naga::ShaderStage::Fragment => "fragment",
+            naga::ShaderStage::Compute => "compute",
+        },
+        kind,
+    };
+
+    let mut constants = HashMap::with_capacity(entries.len());
+    for entry in entries {
+        let key = unsafe { CStr::from_ptr(entry.key) }.to_string_lossy();
+
+        // Without a naga module, e.g. for SPIR-V passed through to the
+        // backend, there are no overrides to check the constants against.
+        if let Some(overrides) = overrides {
+            if let Cow::Owned(ref key) = key {
+                return Err(error(PipelineConstantErrorKind::InvalidKey(key.clone())));
+            }
+            let scalar = overrides
+                .get(&*key)
+                .ok_or_else(|| error(PipelineConstantErrorKind::UnknownKey(key.to_string())))?;
+            // Same conversions as naga's `process_overrides`.
+            let value = entry.value;
+            let (fits, ty) = match *scalar {
+                naga::Scalar::BOOL => (true, "bool"),
+                naga::Scalar::I32 => (
+                    value.is_finite()
+                        && (f64::from(i32::MIN)..=f64::from(i32::MAX)).contains(&value.trunc()),
+                    "i32",
+                ),
+                naga::Scalar::U32 => (
+                    value.is_finite()
+                        && (f64::from(u32::MIN)..=f64::from(u32::MAX)).contains(&value.trunc()),
+                    "u32",
+                ),
+                naga::Scalar::F32 => ((value as f32).is_finite(), "f32"),
+                naga::Scalar::F64 => (value.is_finite(), "f64"),
+                _ => (true, ""),
+            };
+            if !fits {
+                return Err(error(PipelineConstantErrorKind::OutOfRange {
+                    key: key.to_string(),
+                    value,
+                    ty,
+                }));
+            }
+        }
+        constants.insert(key.into_owned(), entry.value);
+    }
+    Ok(constants)
+}
+
+/// Returns the raw SPIR-V words of the descriptor, if it is a SPIR-V one,
+/// for `device_create_shader_module_spirv`.
+#[inline]
+pub fn map_shader_module_spirv_passthrough<'a>(
+    _: &native::WGPUShaderModuleDescriptor,
+    spirv: Option<&native::WGPUShaderModuleSPIRVDescriptor>,
+) -> Option<Cow<'a, [u32]>> {
+    spirv.map(|spirv| Cow::Borrowed(make_slice(spirv.code, spirv.codeSize as usize)))
+}
+
 #[inline]
 pub unsafe fn map_image_copy_texture(
     native: &native::WGPUImageCopyTexture,
-) -> wgc::command::ImageCopyTexture {
-    wgt::ImageCopyTexture {
-        texture: native
-            .texture
-            .as_ref()
-            .expect("invalid texture for image copy texture")
+) -> Option<wgc::command::ImageCopyTexture> {
+    Some(wgt::ImageCopyTexture {
+        texture: crate::validate_handle!(native.texture, "invalid texture for image copy texture")
             .id,
         mip_level: native.mipLevel,
         origin: map_origin3d(&native.origin),
         aspect: map_texture_aspect(native.aspect),
-    }
+    })
 }
 
 #[inline]
 pub unsafe fn map_image_copy_buffer(
     native: &native::WGPUImageCopyBuffer,
-) -> wgc::command::ImageCopyBuffer {
-    wgt::ImageCopyBuffer {
-        buffer: native
-            .buffer
-            .as_ref()
-            .expect("invalid buffer for image copy buffer")
-            .id,
+) -> Option<wgc::command::ImageCopyBuffer> {
+    Some(wgt::ImageCopyBuffer {
+        buffer: crate::validate_handle!(native.buffer, "invalid buffer for image copy buffer").id,
         layout: map_texture_data_layout(&native.layout),
-    }
+    })
 }
 
+/// Only `WGPU_COPY_STRIDE_UNDEFINED` leaves a stride unspecified. Any other value,
+/// zero included, is passed on for wgpu-core to validate against the copy size
+/// in blocks of the texture format.
 #[inline]
 pub fn map_texture_data_layout(native: &native::WGPUTextureDataLayout) -> wgt::ImageDataLayout {
     wgt::ImageDataLayout {
         offset: native.offset,
         bytes_per_row: match native.bytesPerRow {
-            0 => panic!("invalid bytesPerRow"),
             native::WGPU_COPY_STRIDE_UNDEFINED => None,
-            _ => Some(native.bytesPerRow),
+            bytes_per_row => Some(bytes_per_row),
         },
         rows_per_image: match native.rowsPerImage {
-            0 => panic!("invalid rowsPerImage"),
             native::WGPU_COPY_STRIDE_UNDEFINED => None,
-            _ => Some(native.rowsPerImage),
+            rows_per_image => Some(rows_per_image),
         },
     }
 }
@@ -1201,9 +1549,9 @@ pub fn features_to_native(features: wgt::Features) -> Vec<native::WGPUFeatureNam
     if features.contains(wgt::Features::TEXTURE_FORMAT_NV12) {
         temp.push(native::WGPUNativeFeature_TextureFormatNv12);
     }
-    if features.contains(wgt::Features::RAY_TRACING_ACCELERATION_STRUCTURE) {
-        temp.push(native::WGPUNativeFeature_RayTracingAccelerationStructure);
-    }
+    // if features.contains(wgt::Features::RAY_TRACING_ACCELERATION_STRUCTURE) {
+    //     temp.push(native::WGPUNativeFeature_RayTracingAccelerationStructure);
+    // }
     if features.contains(wgt::Features::RAY_QUERY) {
         temp.push(native::WGPUNativeFeature_RayQuery);
     }
@@ -1279,7 +1627,7 @@ pub fn map_feature(feature: native::WGPUFeatureName) -> Option<wgt::Features> {
         // native::WGPUNativeFeature_Multiview => Some(Features::MULTIVIEW),
         native::WGPUNativeFeature_VertexAttribute64bit => Some(Features::VERTEX_ATTRIBUTE_64BIT),
         native::WGPUNativeFeature_TextureFormatNv12 => Some(Features::TEXTURE_FORMAT_NV12),
-        native::WGPUNativeFeature_RayTracingAccelerationStructure => Some(Features::RAY_TRACING_ACCELERATION_STRUCTURE),
+        // native::WGPUNativeFeature_RayTracingAccelerationStructure => Some(Features::RAY_TRACING_ACCELERATION_STRUCTURE),
         native::WGPUNativeFeature_RayQuery => Some(Features::RAY_QUERY),
         native::WGPUNativeFeature_ShaderF64 => Some(Features::SHADER_F64),
         native::WGPUNativeFeature_ShaderPrimitiveIndex => Some(Features::SHADER_PRIMITIVE_INDEX),
@@ -1317,12 +1665,12 @@ pub fn to_native_composite_alpha_mode(
 }
 
 #[inline]
-pub fn map_bind_group_entry<'a>(
+pub unsafe fn map_bind_group_entry<'a>(
     entry: &'a native::WGPUBindGroupEntry,
     extras: Option<&native::WGPUBindGroupEntryExtras>,
-) -> wgc::binding_model::BindGroupEntry<'a> {
-    if let Some(buffer) = unsafe { entry.buffer.as_ref() } {
-        return wgc::binding_model::BindGroupEntry {
+) -> Option<wgc::binding_model::BindGroupEntry<'a>> {
+    if let Some(buffer) = entry.buffer.as_ref() {
+        return Some(wgc::binding_model::BindGroupEntry {
             binding: entry.binding,
             resource: wgc::binding_model::BindingResource::Buffer(
                 wgc::binding_model::BufferBinding {
@@ -1331,63 +1679,62 @@ pub fn map_bind_group_entry<'a>(
                     size: match entry.size {
                         0 => panic!("invalid size"),
                         WGPU_WHOLE_SIZE => None,
-                        _ => Some(unsafe { NonZeroU64::new_unchecked(entry.size) }),
+                        _ => Some(NonZeroU64::new_unchecked(entry.size)),
                     },
                 },
             ),
-        };
-    } else if let Some(sampler) = unsafe { entry.sampler.as_ref() } {
-        return wgc::binding_model::BindGroupEntry {
+        });
+    } else if let Some(sampler) = entry.sampler.as_ref() {
+        return Some(wgc::binding_model::BindGroupEntry {
             binding: entry.binding,
             resource: wgc::binding_model::BindingResource::Sampler(sampler.id),
-        };
-    } else if let Some(texture_view) = unsafe { entry.textureView.as_ref() } {
-        return wgc::binding_model::BindGroupEntry {
+        });
+    } else if let Some(texture_view) = entry.textureView.as_ref() {
+        return Some(wgc::binding_model::BindGroupEntry {
             binding: entry.binding,
             resource: wgc::binding_model::BindingResource::TextureView(texture_view.id),
-        };
+        });
     } else if let Some(extras) = extras {
-        if let Some(texture_views) = unsafe { extras.textureViews.as_ref() } {
-            let arr = make_slice(texture_views, extras.textureViewCount)
-                .iter()
-                .map(|v| {
-                    unsafe { v.as_ref() }
-                        .expect("invalid texture views for bind group entry extras")
-                        .id
-                })
-                .collect();
-            return wgc::binding_model::BindGroupEntry {
+        if let Some(texture_views) = extras.textureViews.as_ref() {
+            let mut arr = Vec::with_capacity(extras.textureViewCount);
+            for &v in make_slice(texture_views, extras.textureViewCount) {
+                arr.push(
+                    crate::validate_handle!(v, "invalid texture views for bind group entry extras")
+                        .id,
+                );
+            }
+            return Some(wgc::binding_model::BindGroupEntry {
                 binding: entry.binding,
-                resource: wgc::binding_model::BindingResource::TextureViewArray(arr),
-            };
-        } else if let Some(samplers) = unsafe { extras.samplers.as_ref() } {
-            let arr = make_slice(samplers, extras.samplerCount)
-                .iter()
-                .map(|v| {
-                    unsafe { v.as_ref() }
-                        .expect("invalid sampler for bind group entry extras")
-                        .id
-                })
-                .collect();
-            return wgc::binding_model::BindGroupEntry {
+                resource: wgc::binding_model::BindingResource::TextureViewArray(Cow::Owned(arr)),
+            });
+        } else if let Some(samplers) = extras.samplers.as_ref() {
+            let mut arr = Vec::with_capacity(extras.samplerCount);
+            for &v in make_slice(samplers, extras.samplerCount) {
+                arr.push(
+                    crate::validate_handle!(v, "invalid sampler for bind group entry extras").id,
+                );
+            }
+            return Some(wgc::binding_model::BindGroupEntry {
                 binding: entry.binding,
-                resource: wgc::binding_model::BindingResource::SamplerArray(arr),
-            };
-        } else if let Some(buffers) = unsafe { extras.buffers.as_ref() } {
-            let arr = make_slice(buffers, extras.bufferCount)
-                .iter()
-                .map(|v| wgc::binding_model::BufferBinding {
-                    buffer_id: unsafe { v.as_ref() }
-                        .expect("invalid buffers for bind group entry extras")
-                        .id,
+                resource: wgc::binding_model::BindingResource::SamplerArray(Cow::Owned(arr)),
+            });
+        } else if let Some(buffers) = extras.buffers.as_ref() {
+            let mut arr = Vec::with_capacity(extras.bufferCount);
+            for &v in make_slice(buffers, extras.bufferCount) {
+                arr.push(wgc::binding_model::BufferBinding {
+                    buffer_id: crate::validate_handle!(
+                        v,
+                        "invalid buffers for bind group entry extras"
+                    )
+                    .id,
                     offset: entry.offset,
                     size: std::num::NonZeroU64::new(entry.size),
-                })
-                .collect();
-            return wgc::binding_model::BindGroupEntry {
+                });
+            }
+            return Some(wgc::binding_model::BindGroupEntry {
                 binding: entry.binding,
-                resource: wgc::binding_model::BindingResource::BufferArray(arr),
-            };
+                resource: wgc::binding_model::BindingResource::BufferArray(Cow::Owned(arr)),
+            });
         }
     }
 
@@ -1503,12 +1850,52 @@ pub fn map_query_set_index(index: u32) -> Option<u32> {
     }
 }
 
-#[inline]
+/// Pipeline statistics in the order their values are resolved for each query.
+const PIPELINE_STATISTICS: [(
+    native::WGPUPipelineStatisticName,
+    wgt::PipelineStatisticsTypes,
+); 5] = [
+    (
+        native::WGPUPipelineStatisticName_VertexShaderInvocations,
+        wgt::PipelineStatisticsTypes::VERTEX_SHADER_INVOCATIONS,
+    ),
+    (
+        native::WGPUPipelineStatisticName_ClipperInvocations,
+        wgt::PipelineStatisticsTypes::CLIPPER_INVOCATIONS,
+    ),
+    (
+        native::WGPUPipelineStatisticName_ClipperPrimitivesOut,
+        wgt::PipelineStatisticsTypes::CLIPPER_PRIMITIVES_OUT,
+    ),
+    (
+        native::WGPUPipelineStatisticName_FragmentShaderInvocations,
+        wgt::PipelineStatisticsTypes::FRAGMENT_SHADER_INVOCATIONS,
+    ),
+    (
+        native::WGPUPipelineStatisticName_ComputeShaderInvocations,
+        wgt::PipelineStatisticsTypes::COMPUTE_SHADER_INVOCATIONS,
+    ),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum QuerySetDescriptorError {
+    #[error("Unknown query type {0:#x}")]
+    UnknownQueryType(native::WGPUQueryType),
+    #[error(
+        "Pipeline statistics query sets need WGPUQuerySetDescriptorExtras listing the statistics"
+    )]
+    MissingPipelineStatistics,
+    #[error("Unknown pipeline statistic name {0:#x}")]
+    UnknownPipelineStatistic(native::WGPUPipelineStatisticName),
+    #[error("Pipeline statistic {0:?} is requested more than once")]
+    DuplicatePipelineStatistic(wgt::PipelineStatisticsTypes),
+}
+
 pub fn map_query_set_descriptor<'a>(
     desc: &native::WGPUQuerySetDescriptor,
     extras: Option<&native::WGPUQuerySetDescriptorExtras>,
-) -> wgt::QuerySetDescriptor<wgc::Label<'a>> {
-    wgt::QuerySetDescriptor {
+) -> Result<wgt::QuerySetDescriptor<wgc::Label<'a>>, QuerySetDescriptorError> {
+    Ok(wgt::QuerySetDescriptor {
         label: ptr_into_label(desc.label),
         count: desc.count,
         ty: match (desc.type_, extras) {
@@ -1517,34 +1904,40 @@ pub fn map_query_set_descriptor<'a>(
             (native::WGPUNativeQueryType_PipelineStatistics, Some(extras)) => {
                 let mut types = wgt::PipelineStatisticsTypes::empty();
 
-                make_slice(extras.pipelineStatistics, extras.pipelineStatisticCount)
-                    .iter()
-                    .for_each(|f| {
-                        types.insert(match *f {
-                            native::WGPUPipelineStatisticName_VertexShaderInvocations => {
-                                wgt::PipelineStatisticsTypes::VERTEX_SHADER_INVOCATIONS
-                            }
-                            native::WGPUPipelineStatisticName_ClipperInvocations => {
-                                wgt::PipelineStatisticsTypes::CLIPPER_INVOCATIONS
-                            }
-                            native::WGPUPipelineStatisticName_ClipperPrimitivesOut => {
-                                wgt::PipelineStatisticsTypes::CLIPPER_PRIMITIVES_OUT
-                            }
-                            native::WGPUPipelineStatisticName_FragmentShaderInvocations => {
-                                wgt::PipelineStatisticsTypes::FRAGMENT_SHADER_INVOCATIONS
-                            }
-                            native::WGPUPipelineStatisticName_ComputeShaderInvocations => {
-                                wgt::PipelineStatisticsTypes::COMPUTE_SHADER_INVOCATIONS
-                            }
-                            _ => panic!("invalid pipeline statistics name"),
-                        });
-                    });
+                for &name in make_slice(extras.pipelineStatistics, extras.pipelineStatisticCount) {
+                    let statistic = PIPELINE_STATISTICS
+                        .iter()
+                        .find(|(native_name, _)| *native_name == name)
+                        .map(|&(_, statistic)| statistic)
+                        .ok_or(QuerySetDescriptorError::UnknownPipelineStatistic(name))?;
+                    if types.contains(statistic) {
+                        return Err(QuerySetDescriptorError::DuplicatePipelineStatistic(
+                            statistic,
+                        ));
+                    }
+                    types.insert(statistic);
+                }
 
                 wgt::QueryType::PipelineStatistics(types)
             }
-            _ => panic!("invalid query type"),
+            (native::WGPUNativeQueryType_PipelineStatistics, None) => {
+                return Err(QuerySetDescriptorError::MissingPipelineStatistics)
+            }
+            (query_type, _) => return Err(QuerySetDescriptorError::UnknownQueryType(query_type)),
         },
-    }
+    })
+}
+
+/// The statistics resolved for each query of a pipeline statistics query set,
+/// one `u64` per statistic, in order.
+pub fn map_pipeline_statistics_layout(
+    types: wgt::PipelineStatisticsTypes,
+) -> Vec<native::WGPUPipelineStatisticName> {
+    PIPELINE_STATISTICS
+        .iter()
+        .filter(|(_, statistic)| types.contains(*statistic))
+        .map(|&(name, _)| name)
+        .collect()
 }
 
 #[inline]
@@ -1706,6 +2099,23 @@ pub fn map_backend_type(backend: wgt::Backend) -> native::WGPUBackendType {
     }
 }
 
+#[cfg(feature = "shader_inspection")]
+pub(crate) fn map_translation_target(
+    backend: native::WGPUBackendType,
+) -> Option<crate::shader::TranslationTarget> {
+    use crate::shader::TranslationTarget;
+    match backend {
+        native::WGPUBackendType_Vulkan => Some(TranslationTarget::SpirV),
+        native::WGPUBackendType_Metal => Some(TranslationTarget::Msl),
+        native::WGPUBackendType_D3D11 | native::WGPUBackendType_D3D12 => {
+            Some(TranslationTarget::Hlsl)
+        }
+        native::WGPUBackendType_OpenGL => Some(TranslationTarget::Glsl { es: false }),
+        native::WGPUBackendType_OpenGLES => Some(TranslationTarget::Glsl { es: true }),
+        _ => None,
+    }
+}
+
 pub fn map_adapter_type(device_type: wgt::DeviceType) -> native::WGPUAdapterType {
     match device_type {
         wgt::DeviceType::Other => native::WGPUAdapterType_Unknown,
@@ -1715,3 +2125,282 @@ pub fn map_adapter_type(device_type: wgt::DeviceType) -> native::WGPUAdapterType
         wgt::DeviceType::Cpu => native::WGPUAdapterType_CPU,
     }
 }
+
+#[cfg(feature = "wgsl")]
+#[test]
+pub fn test_map_pipeline_constants() {
+    let overrides =
+        crate::shader::source_overrides(&wgc::pipeline::ShaderModuleSource::Wgsl(Cow::Borrowed(
+            "@id(0) override gain: f32;
+            override count: u32 = 4u;
+            override enabled: bool;
+            @compute @workgroup_size(1) fn main() { _ = gain; _ = count; _ = enabled; }",
+        )))
+        .unwrap();
+    // WGSL without overrides isn't parsed.
+    assert_eq!(
+        crate::shader::source_overrides(&wgc::pipeline::ShaderModuleSource::Wgsl(Cow::Borrowed(
+            "not parsed"
+        ))),
+        Some(HashMap::new())
+    );
+
+    let entry = |key: &CStr, value| native::WGPUConstantEntry {
+        nextInChain: std::ptr::null(),
+        key: key.as_ptr(),
+        value,
+    };
+    let map = |source, entries: &[native::WGPUConstantEntry]| {
+        map_pipeline_constants(naga::ShaderStage::Compute, source, entries)
+            .map_err(|error| error.to_string())
+    };
+
+    assert_eq!(
+        map(
+            Some(&overrides),
+            &[
+                entry(c"0", 0.5),
+                entry(c"count", 8.0),
+                entry(c"enabled", 1.0)
+            ]
+        ),
+        Ok(HashMap::from([
+            ("0".to_string(), 0.5),
+            ("count".to_string(), 8.0),
+            ("enabled".to_string(), 1.0),
+        ]))
+    );
+    assert_eq!(
+        map(Some(&overrides), &[entry(c"gain", 1.0)]),
+        Err(
+            "Invalid constants for the compute stage: the shader has no override \"gain\""
+                .to_string()
+        )
+    );
+    assert_eq!(
+        map(Some(&overrides), &[entry(c"count", -1.0)]),
+        Err("Invalid constants for the compute stage: value -1 of override \"count\" doesn't fit in u32".to_string())
+    );
+    assert_eq!(
+        map(Some(&overrides), &[entry(c"\xff", 1.0)]),
+        Err(
+            "Invalid constants for the compute stage: key \"\u{fffd}\" is not valid UTF-8"
+                .to_string()
+        )
+    );
+    // Without a naga module, the constants are left for wgpu-core.
+    assert_eq!(
+        map(None, &[entry(c"gain", 1.0)]),
+        Ok(HashMap::from([("gain".to_string(), 1.0)]))
+    );
+}
+
+#[test]
+pub fn test_map_query_set_descriptor() {
+    let desc = |type_| native::WGPUQuerySetDescriptor {
+        nextInChain: std::ptr::null(),
+        label: std::ptr::null(),
+        type_,
+        count: 2,
+    };
+    let extras =
+        |statistics: &[native::WGPUPipelineStatisticName]| native::WGPUQuerySetDescriptorExtras {
+            chain: native::WGPUChainedStruct {
+                next: std::ptr::null(),
+                sType: native::WGPUSType_QuerySetDescriptorExtras,
+            },
+            pipelineStatistics: statistics.as_ptr(),
+            pipelineStatisticCount: statistics.len(),
+        };
+    let map = |type_, extras: Option<&native::WGPUQuerySetDescriptorExtras>| {
+        map_query_set_descriptor(&desc(type_), extras)
+            .map(|desc| format!("{:?}", desc.ty))
+            .map_err(|error| error.to_string())
+    };
+
+    assert_eq!(
+        map(native::WGPUQueryType_Occlusion, None),
+        Ok("Occlusion".to_string())
+    );
+    assert_eq!(
+        map(native::WGPUNativeQueryType_PipelineStatistics, None),
+        Err(QuerySetDescriptorError::MissingPipelineStatistics.to_string())
+    );
+    assert_eq!(
+        map(0x1234, None),
+        Err("Unknown query type 0x1234".to_string())
+    );
+    assert_eq!(
+        map(
+            native::WGPUNativeQueryType_PipelineStatistics,
+            Some(&extras(&[
+                native::WGPUPipelineStatisticName_ClipperInvocations,
+                native::WGPUPipelineStatisticName_ClipperInvocations,
+            ]))
+        ),
+        Err("Pipeline statistic PipelineStatisticsTypes(CLIPPER_INVOCATIONS) is requested more than once".to_string())
+    );
+
+    // The results follow wgpu-core's order, not the order of the request.
+    let ty = map_query_set_descriptor(
+        &desc(native::WGPUNativeQueryType_PipelineStatistics),
+        Some(&extras(&[
+            native::WGPUPipelineStatisticName_ComputeShaderInvocations,
+            native::WGPUPipelineStatisticName_VertexShaderInvocations,
+            native::WGPUPipelineStatisticName_FragmentShaderInvocations,
+        ])),
+    )
+    .unwrap()
+    .ty;
+    let wgt::QueryType::PipelineStatistics(types) = ty else {
+        panic!("unexpected query type {ty:?}");
+    };
+    assert_eq!(
+        map_pipeline_statistics_layout(types),
+        [
+            native::WGPUPipelineStatisticName_VertexShaderInvocations,
+            native::WGPUPipelineStatisticName_FragmentShaderInvocations,
+            native::WGPUPipelineStatisticName_ComputeShaderInvocations,
+        ]
+    );
+}
+
+#[test]
+pub fn test_map_required_limits() {
+    let adapter_limits = wgt::Limits::default();
+    let supported = |native_limits: &mut native::WGPUSupportedLimits| {
+        write_limits_struct(adapter_limits.clone(), native_limits);
+        native_limits.limits
+    };
+    let mut native_limits: native::WGPUSupportedLimits = unsafe { std::mem::zeroed() };
+    let adapter = supported(&mut native_limits);
+    assert_eq!(adapter.maxInterStageShaderVariables, 16);
+    assert_eq!(adapter.maxBindGroupsPlusVertexBuffers, 12);
+
+    let required = |update: fn(&mut native::WGPULimits)| {
+        let mut limits = native::WGPURequiredLimits {
+            nextInChain: std::ptr::null(),
+            limits: unsafe { std::mem::zeroed() },
+        };
+        // Every limit is undefined, including the ones added to WGPULimits later.
+        unsafe {
+            std::ptr::write_bytes(
+                &mut limits.limits as *mut native::WGPULimits as *mut u8,
+                0xff,
+                std::mem::size_of::<native::WGPULimits>(),
+            )
+        };
+        update(&mut limits.limits);
+        map_required_limits(&limits, &adapter_limits, None, None).map_err(|error| error.to_string())
+    };
+
+    let base = required(|_| ()).unwrap();
+    assert_eq!(base.max_bind_groups_plus_vertex_buffers, 12);
+    assert_eq!(base.limits.max_inter_stage_shader_components, 60);
+
+    let limits = required(|limits| {
+        limits.maxBindGroupsPlusVertexBuffers = 10;
+        limits.maxInterStageShaderVariables = 8;
+    })
+    .unwrap();
+    assert_eq!(limits.max_bind_groups_plus_vertex_buffers, 10);
+    assert_eq!(limits.limits.max_inter_stage_shader_components, 32);
+    // Requesting all the variables keeps every component.
+    let limits = required(|limits| limits.maxInterStageShaderVariables = 16).unwrap();
+    assert_eq!(limits.limits.max_inter_stage_shader_components, 60);
+
+    assert_eq!(
+        required(|limits| limits.maxBindGroupsPlusVertexBuffers = 13).unwrap_err(),
+        "Limit 'maxBindGroupsPlusVertexBuffers' value 13 is better than allowed 12"
+    );
+    assert_eq!(
+        required(|limits| limits.maxInterStageShaderVariables = 17).unwrap_err(),
+        "Limit 'maxInterStageShaderVariables' value 17 is better than allowed 16"
+    );
+
+    let subgroup_limits = native::WGPURequiredSubgroupLimits {
+        chain: native::WGPUChainedStruct {
+            next: std::ptr::null(),
+            sType: native::WGPUSType_RequiredSubgroupLimits,
+        },
+        minSubgroupSize: 0,
+        maxSubgroupSize: 32,
+    };
+    let adapter_required = native::WGPURequiredLimits {
+        nextInChain: std::ptr::null(),
+        limits: adapter,
+    };
+    let limits = map_required_limits(
+        &adapter_required,
+        &adapter_limits,
+        None,
+        Some(&subgroup_limits),
+    )
+    .unwrap();
+    assert_eq!(
+        (
+            limits.limits.min_subgroup_size,
+            limits.limits.max_subgroup_size
+        ),
+        (adapter_limits.min_subgroup_size, 32)
+    );
+
+    // Every struct chained on the supported limits is written.
+    let subgroup_adapter_limits = wgt::Limits {
+        min_subgroup_size: 32,
+        max_subgroup_size: 64,
+        ..adapter_limits.clone()
+    };
+    let mut subgroup_limits = native::WGPUSupportedSubgroupLimits {
+        chain: native::WGPUChainedStructOut {
+            next: std::ptr::null_mut(),
+            sType: native::WGPUSType_SupportedSubgroupLimits,
+        },
+        minSubgroupSize: 0,
+        maxSubgroupSize: 0,
+    };
+    let mut extras = native::WGPUSupportedLimitsExtras {
+        chain: native::WGPUChainedStructOut {
+            next: &mut subgroup_limits.chain,
+            sType: native::WGPUSType_SupportedLimitsExtras,
+        },
+        limits: unsafe { std::mem::zeroed() },
+    };
+    native_limits.nextInChain = &mut extras.chain;
+    write_limits_struct(subgroup_adapter_limits, &mut native_limits);
+    assert_eq!(
+        extras.limits.maxNonSamplerBindings,
+        adapter_limits.max_non_sampler_bindings
+    );
+    assert_eq!(
+        (
+            subgroup_limits.minSubgroupSize,
+            subgroup_limits.maxSubgroupSize
+        ),
+        (32, 64)
+    );
+}
+
+#[test]
+pub fn test_map_texture_data_layout() {
+    let layout = |bytes_per_row, rows_per_image| {
+        let layout = map_texture_data_layout(&native::WGPUTextureDataLayout {
+            nextInChain: std::ptr::null(),
+            offset: 16,
+            bytesPerRow: bytes_per_row,
+            rowsPerImage: rows_per_image,
+        });
+        (layout.offset, layout.bytes_per_row, layout.rows_per_image)
+    };
+
+    assert_eq!(
+        layout(
+            native::WGPU_COPY_STRIDE_UNDEFINED,
+            native::WGPU_COPY_STRIDE_UNDEFINED
+        ),
+        (16, None, None)
+    );
+    assert_eq!(layout(256, 4), (16, Some(256), Some(4)));
+    // Zero strides are left for wgpu-core to reject when they are too small.
+    assert_eq!(layout(0, 0), (16, Some(0), Some(0)));
+}
@@ -0,0 +1,218 @@
+//! Bookkeeping for `WGPUFuture`s.
+//!
+//! The `*2` async functions create a future with the callback mode they are
+//! given. Once its operation completes, the callback is either handed back to
+//! be called right away (`AllowSpontaneous`), or kept until the future is
+//! waited on with `wgpuInstanceWaitAny` or, for `AllowProcessEvents`, until
+//! `wgpuInstanceProcessEvents` is called.
+//!
+//! Waiting with a timeout polls the devices between waits for the next
+//! completion, as callbacks are only called from device polls. The waits get
+//! longer the longer nothing completes, up to [`MAX_POLL_INTERVAL`].
+
+use parking_lot::{Condvar, Mutex};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Numbers the instances, whose futures take their ids from the range of
+/// `2^32` ids starting at their number shifted left by 32, so that a future
+/// is never mistaken for one of another instance. Instance 0 would include
+/// the null future.
+static NEXT_INSTANCE: AtomicU64 = AtomicU64::new(1);
+
+/// How long to wait for a completion before polling the devices the first time.
+pub(crate) const MIN_POLL_INTERVAL: Duration = Duration::from_micros(100);
+/// The longest wait between device polls, as long as a frame at 60Hz.
+pub(crate) const MAX_POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+pub(crate) type Callback = Box<dyn FnOnce() + Send>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallbackMode {
+    WaitAnyOnly,
+    AllowProcessEvents,
+    AllowSpontaneous,
+}
+
+struct Future {
+    mode: CallbackMode,
+    /// Set once the operation completed.
+    callback: Option<Callback>,
+}
+
+pub(crate) enum Poll {
+    Pending,
+    /// The callback to call, if it wasn't called already.
+    Completed(Option<Callback>),
+}
+
+struct State {
+    next_id: u64,
+    /// Number of completed operations, to tell when waiting can stop.
+    completions: u64,
+    futures: HashMap<u64, Future>,
+}
+
+pub(crate) struct Futures {
+    /// The id of the first future.
+    first_id: u64,
+    state: Mutex<State>,
+    completed: Condvar,
+}
+
+impl Futures {
+    pub(crate) fn new() -> Futures {
+        let first_id = NEXT_INSTANCE.fetch_add(1, Ordering::Relaxed) << 32;
+        Futures {
+            first_id,
+            state: Mutex::new(State {
+                next_id: first_id,
+                completions: 0,
+                futures: HashMap::new(),
+            }),
+            completed: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn create(&self, mode: CallbackMode) -> u64 {
+        let mut state = self.state.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.futures.insert(
+            id,
+            Future {
+                mode,
+                callback: None,
+            },
+        );
+        id
+    }
+
+    /// Records that the operation of `id` completed, returning the callback
+    /// if it has to be called now.
+    pub(crate) fn complete(&self, id: u64, callback: Callback) -> Option<Callback> {
+        let mut state = self.state.lock();
+        let mode = state.futures.get(&id)?.mode;
+        state.completions += 1;
+        self.completed.notify_all();
+        match mode {
+            CallbackMode::AllowSpontaneous => {
+                state.futures.remove(&id);
+                Some(callback)
+            }
+            CallbackMode::WaitAnyOnly | CallbackMode::AllowProcessEvents => {
+                state.futures.get_mut(&id)?.callback = Some(callback);
+                None
+            }
+        }
+    }
+
+    /// Number of operations completed so far, to pass to [`Futures::wait`].
+    pub(crate) fn completions(&self) -> u64 {
+        self.state.lock().completions
+    }
+
+    /// Waits for `timeout` at most, or until an operation completes if none did
+    /// since `completions` was returned by [`Futures::completions`].
+    pub(crate) fn wait(&self, completions: u64, timeout: Duration) {
+        let mut state = self.state.lock();
+        if state.completions == completions {
+            self.completed.wait_for(&mut state, timeout);
+        }
+    }
+
+    /// Whether `id` was returned by [`Futures::create`].
+    pub(crate) fn is_valid(&self, id: u64) -> bool {
+        (self.first_id..self.state.lock().next_id).contains(&id)
+    }
+
+    /// Takes the callback of `id` if its operation completed. `id` has to be
+    /// valid, see [`Futures::is_valid`].
+    pub(crate) fn poll(&self, id: u64) -> Poll {
+        let mut state = self.state.lock();
+        match state.futures.get(&id) {
+            Some(Future { callback: None, .. }) => Poll::Pending,
+            Some(_) => {
+                Poll::Completed(state.futures.remove(&id).and_then(|future| future.callback))
+            }
+            // Its callback was already called.
+            None => Poll::Completed(None),
+        }
+    }
+
+    /// Takes the callbacks of the completed `AllowProcessEvents` futures.
+    pub(crate) fn take_process_events(&self) -> Vec<Callback> {
+        let mut state = self.state.lock();
+        let futures = &mut state.futures;
+        let ids: Vec<u64> = futures
+            .iter()
+            .filter(|(_, future)| {
+                future.mode == CallbackMode::AllowProcessEvents && future.callback.is_some()
+            })
+            .map(|(&id, _)| id)
+            .collect();
+        ids.into_iter()
+            .filter_map(|id| futures.remove(&id).and_then(|future| future.callback))
+            .collect()
+    }
+}
+
+#[test]
+pub fn test_futures_callback_modes() {
+    let futures = Futures::new();
+    let wait_any_only = futures.create(CallbackMode::WaitAnyOnly);
+    let process_events = futures.create(CallbackMode::AllowProcessEvents);
+    let spontaneous = futures.create(CallbackMode::AllowSpontaneous);
+
+    assert!(matches!(futures.poll(wait_any_only), Poll::Pending));
+    assert!(futures.complete(wait_any_only, Box::new(|| ())).is_none());
+    assert!(futures.complete(process_events, Box::new(|| ())).is_none());
+    assert!(futures.complete(spontaneous, Box::new(|| ())).is_some());
+
+    assert_eq!(futures.take_process_events().len(), 1);
+    assert!(matches!(
+        futures.poll(wait_any_only),
+        Poll::Completed(Some(_))
+    ));
+    assert!(matches!(futures.poll(wait_any_only), Poll::Completed(None)));
+    assert!(matches!(futures.poll(spontaneous), Poll::Completed(None)));
+
+    assert!(futures.is_valid(spontaneous));
+    assert!(!futures.is_valid(0));
+    assert!(!futures.is_valid(spontaneous + 1));
+    assert!(!Futures::new().is_valid(wait_any_only));
+}
+
+#[test]
+pub fn test_futures_wait() {
+    use std::{sync::Arc, thread, time::Instant};
+
+    let futures = Arc::new(Futures::new());
+    let id = futures.create(CallbackMode::WaitAnyOnly);
+    let completions = futures.completions();
+
+    // Nothing completes, so it waits for the whole timeout.
+    let start = Instant::now();
+    futures.wait(completions, Duration::from_millis(10));
+    assert!(start.elapsed() >= Duration::from_millis(10));
+
+    // Completing from another thread wakes it up.
+    let thread = thread::spawn({
+        let futures = futures.clone();
+        move || {
+            thread::sleep(Duration::from_millis(10));
+            futures.complete(id, Box::new(|| ()));
+        }
+    });
+    futures.wait(completions, Duration::from_secs(60));
+    assert_eq!(futures.completions(), completions + 1);
+    thread.join().unwrap();
+
+    // It returns right away once something completed since `completions`.
+    let start = Instant::now();
+    futures.wait(completions, Duration::from_secs(60));
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
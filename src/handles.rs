@@ -0,0 +1,501 @@
+use crate::{native, Context};
+use parking_lot::Mutex;
+use std::{collections::HashMap, ffi::CString, sync::Arc};
+
+/// Implemented by every `*Impl` struct that is handed out through the C API.
+pub(crate) trait Handle {
+    /// Name of the `webgpu.h` type, used in reports.
+    const TYPE_NAME: &'static str;
+
+    fn context(&self) -> &Context;
+}
+
+struct LiveHandle {
+    serial: u64,
+    type_name: &'static str,
+    label: Option<String>,
+}
+
+/// Keeps track of the handles created from an instance created with
+/// `WGPUInstanceFlag_LeakDetection`, so that the ones that are still alive
+/// when the last instance reference is released can be reported.
+pub(crate) struct LeakDetector {
+    callback: native::WGPULeakCallback,
+    userdata: *mut std::os::raw::c_void,
+    live: Mutex<(u64, HashMap<usize, LiveHandle>)>,
+}
+// The userdata pointer is owned by the user and only passed back to the callback.
+unsafe impl Send for LeakDetector {}
+unsafe impl Sync for LeakDetector {}
+
+impl LeakDetector {
+    pub(crate) fn new(
+        callback: native::WGPULeakCallback,
+        userdata: *mut std::os::raw::c_void,
+    ) -> LeakDetector {
+        LeakDetector {
+            callback,
+            userdata,
+            live: Mutex::new((0, HashMap::new())),
+        }
+    }
+
+    fn track(&self, address: usize, type_name: &'static str, label: Option<&str>) {
+        let mut guard = self.live.lock();
+        let (next_serial, live) = &mut *guard;
+        live.insert(
+            address,
+            LiveHandle {
+                serial: *next_serial,
+                type_name,
+                label: label.map(str::to_string),
+            },
+        );
+        *next_serial += 1;
+    }
+
+    fn untrack(&self, address: usize) {
+        self.live.lock().1.remove(&address);
+    }
+
+    /// Reports every handle that is still alive, in creation order.
+    pub(crate) fn report(&self) {
+        let mut leaks = {
+            let guard = self.live.lock();
+            guard
+                .1
+                .values()
+                .map(|handle| (handle.serial, handle.type_name, handle.label.clone()))
+                .collect::<Vec<_>>()
+        };
+        leaks.sort_by_key(|(serial, ..)| *serial);
+
+        for (_, type_name, label) in leaks {
+            match self.callback {
+                Some(callback) => {
+                    let type_name = CString::new(type_name).unwrap();
+                    let label = label.and_then(|label| CString::new(label).ok());
+                    unsafe {
+                        callback(
+                            type_name.as_ptr(),
+                            label
+                                .as_ref()
+                                .map_or(std::ptr::null(), |label| label.as_ptr()),
+                            self.userdata,
+                        );
+                    }
+                }
+                None => log::warn!(
+                    "{type_name} {:?} is still alive after the instance was released",
+                    label.unwrap_or_default()
+                ),
+            }
+        }
+    }
+}
+
+/// Turns a freshly created object into a handle for the C API,
+/// registering it with the instance's leak detector if there is one.
+#[inline]
+pub(crate) fn into_raw<T: Handle>(handle: Arc<T>, label: Option<&str>) -> *const T {
+    if let Some(leak_detector) = &handle.context().leak_detector {
+        leak_detector.track(Arc::as_ptr(&handle) as usize, T::TYPE_NAME, label);
+    }
+    register(Arc::as_ptr(&handle));
+    Arc::into_raw(handle)
+}
+
+/// Must be called when the object behind a handle is dropped.
+#[inline]
+pub(crate) fn untrack<T: Handle>(handle: &T) {
+    if let Some(leak_detector) = &handle.context().leak_detector {
+        leak_detector.untrack(handle as *const T as usize);
+    }
+    unregister(handle as *const T);
+}
+
+/// Dereferences a handle passed in through the C API.
+///
+/// By default this panics with the given message if the handle is null.
+/// With the `validate_handles` feature, the handle is first looked up in
+/// the handle registry: a null, released or wrongly typed handle is
+/// reported through the log and makes the calling function return early
+/// with a neutral value (null, zero, false or nothing).
+#[cfg(not(feature = "validate_handles"))]
+#[macro_export]
+macro_rules! validate_handle {
+    ($handle:expr, $msg:literal) => {
+        $handle.as_ref().expect($msg)
+    };
+}
+
+#[cfg(feature = "validate_handles")]
+#[macro_export]
+macro_rules! validate_handle {
+    ($handle:expr, $msg:literal) => {
+        match $crate::handles::validate($handle, $msg) {
+            Some(handle) => handle,
+            None => return $crate::handles::Fallback::fallback(),
+        }
+    };
+}
+
+#[cfg(not(feature = "validate_handles"))]
+#[inline]
+pub(crate) fn register<T: Handle>(_handle: *const T) {}
+
+#[cfg(not(feature = "validate_handles"))]
+#[inline]
+fn unregister<T: Handle>(_handle: *const T) {}
+
+#[cfg(feature = "validate_handles")]
+use validation::unregister;
+#[cfg(feature = "validate_handles")]
+pub(crate) use validation::{register, validate, Fallback};
+
+/// Handle validation for debug builds.
+///
+/// Handles are plain pointers to `Arc` allocations, so reading through a
+/// dangling one is already undefined behavior. Instead of tagging the
+/// allocations themselves, every handle is tagged in a side table keyed by
+/// its address, holding the type of the object and a generation that is
+/// bumped whenever an address is handed out, so nothing has to be read
+/// from the pointer before it is known to be alive.
+#[cfg(feature = "validate_handles")]
+mod validation {
+    use super::Handle;
+    use parking_lot::Mutex;
+    use std::{
+        collections::{HashMap, VecDeque},
+        sync::OnceLock,
+    };
+
+    /// Number of released handles that are remembered to report a use after
+    /// release. Older ones are reported as unknown handles.
+    const MAX_RELEASED: usize = 4096;
+
+    #[derive(Clone, Copy)]
+    struct Tag {
+        type_name: &'static str,
+        generation: u64,
+    }
+
+    #[derive(Default)]
+    struct Registry {
+        next_generation: u64,
+        live: HashMap<usize, Tag>,
+        released: HashMap<usize, Tag>,
+        /// Release order of the entries in `released`, oldest first.
+        released_order: VecDeque<(usize, u64)>,
+    }
+
+    fn registry() -> &'static Mutex<Registry> {
+        static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+        REGISTRY.get_or_init(Default::default)
+    }
+
+    pub(crate) fn register<T: Handle>(handle: *const T) {
+        let mut registry = registry().lock();
+        let tag = Tag {
+            type_name: T::TYPE_NAME,
+            generation: registry.next_generation,
+        };
+        registry.next_generation += 1;
+        registry.released.remove(&(handle as usize));
+        registry.live.insert(handle as usize, tag);
+    }
+
+    pub(crate) fn unregister<T: Handle>(handle: *const T) {
+        let mut registry = registry().lock();
+        if let Some(tag) = registry.live.remove(&(handle as usize)) {
+            registry.released.insert(handle as usize, tag);
+            registry
+                .released_order
+                .push_back((handle as usize, tag.generation));
+
+            while registry.released_order.len() > MAX_RELEASED {
+                let (address, generation) = registry.released_order.pop_front().unwrap();
+                // The address may have been handed out and released again since.
+                if registry
+                    .released
+                    .get(&address)
+                    .is_some_and(|tag| tag.generation == generation)
+                {
+                    registry.released.remove(&address);
+                }
+            }
+        }
+    }
+
+    enum Lookup {
+        Live,
+        WrongType(Tag),
+        Released(Tag),
+        Unknown,
+    }
+
+    fn lookup<T: Handle>(registry: &Registry, handle: *const T) -> Lookup {
+        match registry.live.get(&(handle as usize)) {
+            Some(tag) if tag.type_name == T::TYPE_NAME => Lookup::Live,
+            Some(&tag) => Lookup::WrongType(tag),
+            None => match registry.released.get(&(handle as usize)) {
+                Some(&tag) => Lookup::Released(tag),
+                None => Lookup::Unknown,
+            },
+        }
+    }
+
+    /// Returns the object behind `handle` if it is a live `T`,
+    /// otherwise logs why it is not and returns `None`.
+    ///
+    /// The handle is dereferenced with the registry locked, but nothing keeps
+    /// the object alive once this returns. Releasing the last reference to a
+    /// handle on one thread while it is used on another is still undefined
+    /// behavior, which this can't detect.
+    pub(crate) fn validate<'a, T: Handle>(handle: *const T, msg: &str) -> Option<&'a T> {
+        if handle.is_null() {
+            log::error!("{msg}: expected a {}, got null", T::TYPE_NAME);
+            return None;
+        }
+
+        let registry = registry().lock();
+        match lookup(&registry, handle) {
+            Lookup::Live => return Some(unsafe { &*handle }),
+            Lookup::WrongType(tag) => log::error!(
+                "{msg}: expected a {}, got a {} (generation {})",
+                T::TYPE_NAME,
+                tag.type_name,
+                tag.generation,
+            ),
+            Lookup::Released(tag) => log::error!(
+                "{msg}: {} {handle:p} (generation {}) was used after it was released",
+                tag.type_name,
+                tag.generation,
+            ),
+            Lookup::Unknown => log::error!(
+                "{msg}: {handle:p} is not a handle created by wgpu-native, expected a {}",
+                T::TYPE_NAME,
+            ),
+        }
+        None
+    }
+
+    /// Value returned by an entry point that was given an invalid handle.
+    pub(crate) trait Fallback {
+        fn fallback() -> Self;
+    }
+
+    impl Fallback for () {
+        fn fallback() -> Self {}
+    }
+
+    impl Fallback for bool {
+        fn fallback() -> Self {
+            false
+        }
+    }
+
+    impl<T> Fallback for *const T {
+        fn fallback() -> Self {
+            std::ptr::null()
+        }
+    }
+
+    impl<T> Fallback for *mut T {
+        fn fallback() -> Self {
+            std::ptr::null_mut()
+        }
+    }
+
+    impl<T> Fallback for Option<T> {
+        fn fallback() -> Self {
+            None
+        }
+    }
+
+    impl Fallback for f32 {
+        fn fallback() -> Self {
+            0.0
+        }
+    }
+
+    impl Fallback for crate::native::WGPUFuture {
+        fn fallback() -> Self {
+            crate::native::WGPUFuture { id: 0 }
+        }
+    }
+
+    macro_rules! impl_fallback_zero {
+        ($($ty:ty),+) => {
+            $(
+                impl Fallback for $ty {
+                    fn fallback() -> Self {
+                        0
+                    }
+                }
+            )+
+        };
+    }
+    impl_fallback_zero!(i32, u32, i64, u64, usize);
+
+    #[test]
+    pub fn test_registry() {
+        struct Buffer;
+        struct Texture;
+        impl Handle for Buffer {
+            const TYPE_NAME: &'static str = "WGPUBuffer";
+
+            fn context(&self) -> &crate::Context {
+                unreachable!()
+            }
+        }
+        impl Handle for Texture {
+            const TYPE_NAME: &'static str = "WGPUTexture";
+
+            fn context(&self) -> &crate::Context {
+                unreachable!()
+            }
+        }
+        // Nothing is read through the handles, so made up addresses will do.
+        // They are far below any allocation made by the other tests.
+        let lookup_buffer = |address: usize| lookup(&registry().lock(), address as *const Buffer);
+
+        let buffer = 0x1000 as *const Buffer;
+        register(buffer);
+        assert!(matches!(lookup_buffer(0x1000), Lookup::Live));
+        let generation = match lookup(&registry().lock(), buffer as *const Texture) {
+            Lookup::WrongType(tag) => {
+                assert_eq!(tag.type_name, "WGPUBuffer");
+                tag.generation
+            }
+            _ => panic!("a buffer is not a texture"),
+        };
+
+        unregister(buffer);
+        assert!(matches!(
+            lookup_buffer(0x1000),
+            Lookup::Released(tag) if tag.generation == generation
+        ));
+        // Handing the address out again makes it live with a new generation.
+        register(buffer);
+        assert!(matches!(lookup_buffer(0x1000), Lookup::Live));
+        unregister(buffer);
+        assert!(matches!(
+            lookup_buffer(0x1000),
+            Lookup::Released(tag) if tag.generation > generation
+        ));
+        assert!(matches!(lookup_buffer(0x1008), Lookup::Unknown));
+
+        // Only the last `MAX_RELEASED` released handles are remembered.
+        for address in (0..MAX_RELEASED).map(|i| 0x2000 + 8 * i) {
+            register(address as *const Buffer);
+            unregister(address as *const Buffer);
+        }
+        assert!(matches!(lookup_buffer(0x1000), Lookup::Unknown));
+        assert!(matches!(
+            lookup_buffer(0x2000 + 8 * (MAX_RELEASED - 1)),
+            Lookup::Released(_)
+        ));
+    }
+}
+
+#[cfg(test)]
+type Leaks = Mutex<Vec<(String, Option<String>)>>;
+
+#[cfg(test)]
+unsafe extern "C" fn record_leak(
+    object_type: *const std::os::raw::c_char,
+    label: *const std::os::raw::c_char,
+    userdata: *mut std::os::raw::c_void,
+) {
+    use std::ffi::CStr;
+
+    let leaks = unsafe { &*(userdata as *const Leaks) };
+    let object_type = unsafe { CStr::from_ptr(object_type) };
+    let label = (!label.is_null()).then(|| unsafe { CStr::from_ptr(label) });
+    leaks.lock().push((
+        object_type.to_string_lossy().into_owned(),
+        label.map(|label| label.to_string_lossy().into_owned()),
+    ));
+}
+
+#[test]
+pub fn test_leak_detector() {
+    let leaks = Leaks::default();
+    let leak_detector = LeakDetector::new(
+        Some(record_leak),
+        &leaks as *const Leaks as *mut std::os::raw::c_void,
+    );
+
+    leak_detector.track(1, "WGPUTexture", Some("released"));
+    leak_detector.track(2, "WGPUBuffer", None);
+    leak_detector.track(3, "WGPUSampler", Some("reused"));
+    leak_detector.untrack(1);
+    // The address of a released object can be handed out again.
+    leak_detector.untrack(3);
+    leak_detector.track(3, "WGPUTextureView", Some("leaked"));
+    leak_detector.untrack(4);
+
+    leak_detector.report();
+    assert_eq!(
+        *leaks.lock(),
+        [
+            ("WGPUBuffer".to_string(), None),
+            ("WGPUTextureView".to_string(), Some("leaked".to_string())),
+        ]
+    );
+}
+
+#[test]
+pub fn test_leak_report_on_instance_release() {
+    struct Object {
+        context: Arc<Context>,
+    }
+    impl Handle for Object {
+        const TYPE_NAME: &'static str = "WGPUObject";
+
+        fn context(&self) -> &Context {
+            &self.context
+        }
+    }
+    impl Drop for Object {
+        fn drop(&mut self) {
+            untrack(self);
+        }
+    }
+
+    let leaks = Leaks::default();
+    let mut extras: native::WGPUInstanceExtras = unsafe { std::mem::zeroed() };
+    extras.chain.sType = native::WGPUSType_InstanceExtras as native::WGPUSType;
+    extras.backends = native::WGPUInstanceBackend_BrowserWebGPU as native::WGPUInstanceBackendFlags;
+    extras.flags = native::WGPUInstanceFlag_LeakDetection as native::WGPUInstanceFlags;
+    extras.leakCallback = Some(record_leak);
+    extras.leakUserdata = &leaks as *const Leaks as *mut std::os::raw::c_void;
+    let descriptor = native::WGPUInstanceDescriptor {
+        nextInChain: &extras.chain,
+    };
+
+    unsafe {
+        let instance = crate::wgpuCreateInstance(Some(&descriptor));
+        let context = (*instance).context.clone();
+        drop(Arc::from_raw(into_raw(
+            Arc::new(Object {
+                context: context.clone(),
+            }),
+            Some("released"),
+        )));
+        let leaked = into_raw(Arc::new(Object { context }), Some("leaked"));
+
+        // Only the last reference to the instance reports the leaks.
+        crate::wgpuInstanceReference(instance);
+        crate::wgpuInstanceRelease(instance);
+        assert!(leaks.lock().is_empty());
+        crate::wgpuInstanceRelease(instance);
+        assert_eq!(
+            *leaks.lock(),
+            [("WGPUObject".to_string(), Some("leaked".to_string()))]
+        );
+
+        drop(Arc::from_raw(leaked));
+    }
+}
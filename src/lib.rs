@@ -2,13 +2,14 @@ use conv::{
     map_adapter_type, map_backend_type, map_bind_group_entry, map_bind_group_layout_entry,
     map_device_descriptor, map_instance_backend_flags, map_instance_descriptor,
     map_pipeline_layout_descriptor, map_primitive_state, map_query_set_descriptor,
-    map_query_set_index, map_shader_module, map_surface, map_surface_configuration,
-    CreateSurfaceParams,
+    map_query_set_index, map_shader_module, map_shader_module_spirv_passthrough, map_surface,
+    map_surface_configuration, CreateSurfaceParams,
 };
 use parking_lot::Mutex;
 use smallvec::SmallVec;
 use std::{
     borrow::Cow,
+    collections::HashMap,
     error,
     ffi::{CStr, CString},
     fmt::Display,
@@ -16,17 +17,26 @@ use std::{
     num::NonZeroU64,
     sync::{atomic, Arc},
     thread,
+    time::{Duration, Instant},
 };
-use utils::{
-    get_base_device_limits_from_adapter_limits, make_slice, ptr_into_label, ptr_into_path,
-};
+use utils::{make_slice, ptr_into_label, ptr_into_path};
 use wgc::{
     command::{bundle_ffi, DynComputePass, DynRenderPass},
     gfx_select, id, resource, Label,
 };
 
 pub mod conv;
+mod futures;
+mod handles;
 pub mod logging;
+mod mipmaps;
+#[cfg(feature = "serde")]
+mod naga_ir;
+#[cfg(feature = "wgsl")]
+mod preprocessor;
+mod profiler;
+mod shader;
+mod staging_belt;
 pub mod unimplemented;
 pub mod utils;
 
@@ -38,7 +48,35 @@ pub mod native {
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 }
 
-pub type Context = wgc::global::Global;
+pub struct Context {
+    global: wgc::global::Global,
+    leak_detector: Option<handles::LeakDetector>,
+    callbacks: Callbacks,
+}
+
+/// What callbacks need from the `Context`. The ones handed to wgpu-core are
+/// kept in its `Global` until they are called, so they capture a clone of
+/// this instead of the `Context` to not keep it alive.
+#[derive(Clone)]
+struct Callbacks {
+    executor: utils::CallbackExecutor,
+    futures: Arc<futures::Futures>,
+}
+impl Callbacks {
+    /// Completes a future, calling its callback if its mode allows it.
+    fn complete_future(&self, id: u64, callback: futures::Callback) {
+        if let Some(callback) = self.futures.complete(id, callback) {
+            self.executor.execute(callback);
+        }
+    }
+}
+impl std::ops::Deref for Context {
+    type Target = wgc::global::Global;
+
+    fn deref(&self) -> &Self::Target {
+        &self.global
+    }
+}
 
 pub struct WGPUAdapterImpl {
     context: Arc<Context>,
@@ -46,6 +84,7 @@ pub struct WGPUAdapterImpl {
 }
 impl Drop for WGPUAdapterImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             let context = &self.context;
             gfx_select!(self.id => context.adapter_drop(self.id));
@@ -59,6 +98,7 @@ pub struct WGPUBindGroupImpl {
 }
 impl Drop for WGPUBindGroupImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             let context = &self.context;
             gfx_select!(self.id => context.bind_group_drop(self.id));
@@ -72,6 +112,7 @@ pub struct WGPUBindGroupLayoutImpl {
 }
 impl Drop for WGPUBindGroupLayoutImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             let context = &self.context;
             gfx_select!(self.id => context.bind_group_layout_drop(self.id));
@@ -91,6 +132,7 @@ pub struct WGPUBufferImpl {
 }
 impl Drop for WGPUBufferImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             let context = &self.context;
             gfx_select!(self.id => context.buffer_drop(self.id, false));
@@ -102,9 +144,18 @@ pub struct WGPUCommandBufferImpl {
     context: Arc<Context>,
     id: id::CommandBufferId,
     open: atomic::AtomicBool,
+    used_query_sets: Vec<Arc<atomic::AtomicBool>>,
+}
+impl WGPUCommandBufferImpl {
+    fn uses_destroyed_query_set(&self) -> bool {
+        self.used_query_sets
+            .iter()
+            .any(|destroyed| destroyed.load(atomic::Ordering::SeqCst))
+    }
 }
 impl Drop for WGPUCommandBufferImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if self.open.load(atomic::Ordering::SeqCst) && !thread::panicking() {
             let context = &self.context;
             gfx_select!(self.id => context.command_buffer_drop(self.id));
@@ -117,9 +168,12 @@ pub struct WGPUCommandEncoderImpl {
     id: id::CommandEncoderId,
     error_sink: ErrorSink,
     open: atomic::AtomicBool,
+    mipmap_pipelines: Arc<MipmapPipelines>,
+    used_query_sets: UsedQuerySets,
 }
 impl Drop for WGPUCommandEncoderImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if self.open.load(atomic::Ordering::SeqCst) && !thread::panicking() {
             let context = &self.context;
             gfx_select!(self.id => context.command_encoder_drop(self.id));
@@ -131,9 +185,11 @@ pub struct WGPUComputePassEncoderImpl {
     context: Arc<Context>,
     encoder: *mut dyn DynComputePass,
     error_sink: ErrorSink,
+    used_query_sets: UsedQuerySets,
 }
 impl Drop for WGPUComputePassEncoderImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             drop(unsafe { Box::from_raw(self.encoder) });
         }
@@ -145,14 +201,18 @@ unsafe impl Sync for WGPUComputePassEncoderImpl {}
 
 pub struct WGPUComputePipelineImpl {
     context: Arc<Context>,
-    id: id::ComputePipelineId,
+    /// `None` if the descriptor was rejected before reaching wgpu-core.
+    id: Option<id::ComputePipelineId>,
     error_sink: ErrorSink,
 }
 impl Drop for WGPUComputePipelineImpl {
     fn drop(&mut self) {
-        if !thread::panicking() {
-            let context = &self.context;
-            gfx_select!(self.id => context.compute_pipeline_drop(self.id));
+        handles::untrack(self);
+        if let Some(id) = self.id {
+            if !thread::panicking() {
+                let context = &self.context;
+                gfx_select!(id => context.compute_pipeline_drop(id));
+            }
         }
     }
 }
@@ -175,9 +235,12 @@ pub struct WGPUDeviceImpl {
     id: id::DeviceId,
     queue: Arc<QueueId>,
     error_sink: ErrorSink,
+    max_bind_groups_plus_vertex_buffers: u32,
+    mipmap_pipelines: Arc<MipmapPipelines>,
 }
 impl Drop for WGPUDeviceImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             let context = &self.context;
 
@@ -191,16 +254,100 @@ impl Drop for WGPUDeviceImpl {
     }
 }
 
+/// Objects used to generate the mipmaps of a device's textures.
+struct MipmapObjects {
+    shader_module_id: id::ShaderModuleId,
+    bind_group_layout_id: id::BindGroupLayoutId,
+    pipeline_layout_id: id::PipelineLayoutId,
+    sampler_id: id::SamplerId,
+    pipelines: HashMap<wgt::TextureFormat, id::RenderPipelineId>,
+}
+
+/// Created on the first `wgpuCommandEncoderGenerateMipmaps` call, with a
+/// pipeline per texture format.
+struct MipmapPipelines {
+    context: Arc<Context>,
+    device_id: id::DeviceId,
+    objects: Mutex<Option<MipmapObjects>>,
+}
+impl MipmapObjects {
+    fn release(self, context: &Context) {
+        for id in self.pipelines.into_values() {
+            gfx_select!(id => context.render_pipeline_drop(id));
+        }
+        gfx_select!(self.pipeline_layout_id => context.pipeline_layout_drop(self.pipeline_layout_id));
+        gfx_select!(self.bind_group_layout_id => context.bind_group_layout_drop(self.bind_group_layout_id));
+        gfx_select!(self.sampler_id => context.sampler_drop(self.sampler_id));
+        gfx_select!(self.shader_module_id => context.shader_module_drop(self.shader_module_id));
+    }
+}
+impl Drop for MipmapPipelines {
+    fn drop(&mut self) {
+        if let Some(objects) = self.objects.get_mut().take() {
+            if !thread::panicking() {
+                objects.release(&self.context);
+            }
+        }
+    }
+}
+
+pub struct WGPUGpuProfilerImpl {
+    context: Arc<Context>,
+    query_set_id: id::QuerySetId,
+    resolve_buffer_id: id::BufferId,
+    read_buffer_id: id::BufferId,
+    timestamp_period: f32,
+    error_sink: ErrorSink,
+    scopes: Arc<Mutex<profiler::Scopes>>,
+}
+impl Drop for WGPUGpuProfilerImpl {
+    fn drop(&mut self) {
+        handles::untrack(self);
+        if !thread::panicking() {
+            let context = &self.context;
+            gfx_select!(self.query_set_id => context.query_set_drop(self.query_set_id));
+            gfx_select!(self.resolve_buffer_id => context.buffer_drop(self.resolve_buffer_id, false));
+            gfx_select!(self.read_buffer_id => context.buffer_drop(self.read_buffer_id, false));
+        }
+    }
+}
+
+pub struct WGPUStagingBeltImpl {
+    context: Arc<Context>,
+    device_id: id::DeviceId,
+    label: Option<String>,
+    error_sink: ErrorSink,
+    chunks: Arc<Mutex<staging_belt::Chunks>>,
+}
+impl Drop for WGPUStagingBeltImpl {
+    fn drop(&mut self) {
+        handles::untrack(self);
+        if !thread::panicking() {
+            let context = &self.context;
+            for buffer_id in self.chunks.lock().buffer_ids() {
+                gfx_select!(buffer_id => context.buffer_drop(buffer_id, false));
+            }
+        }
+    }
+}
+
 pub struct WGPUInstanceImpl {
     context: Arc<Context>,
 }
+impl Drop for WGPUInstanceImpl {
+    fn drop(&mut self) {
+        handles::untrack(self);
+    }
+}
 
 pub struct WGPUPipelineLayoutImpl {
     context: Arc<Context>,
     id: id::PipelineLayoutId,
+    bind_group_layout_count: usize,
 }
 impl Drop for WGPUPipelineLayoutImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             let context = &self.context;
             gfx_select!(self.id => context.pipeline_layout_drop(self.id));
@@ -211,18 +358,92 @@ impl Drop for WGPUPipelineLayoutImpl {
 struct QuerySetData {
     query_type: native::WGPUQueryType,
     query_count: u32,
+    pipeline_statistics: Vec<native::WGPUPipelineStatisticName>,
 }
+/// The `destroyed` flags of the query sets used by a command encoder and its
+/// passes, checked again when the finished command buffer is submitted.
+type UsedQuerySets = Arc<Mutex<Vec<Arc<atomic::AtomicBool>>>>;
+
 pub struct WGPUQuerySetImpl {
     context: Arc<Context>,
-    id: id::QuerySetId,
+    /// `None` if the descriptor was rejected before reaching wgpu-core, or
+    /// once the query set is destroyed.
+    id: Mutex<Option<id::QuerySetId>>,
     data: QuerySetData,
+    destroyed: Arc<atomic::AtomicBool>,
+}
+impl WGPUQuerySetImpl {
+    /// Every use of a query set goes through here, so that using a destroyed
+    /// or rejected one is reported right away, and so that submitting commands
+    /// recorded before it was destroyed fails.
+    ///
+    /// Destroying the query set from another thread while a use is recorded
+    /// is a race the caller has to avoid, as wgpu-core panics on dropped ids.
+    fn record_use(
+        &self,
+        used_query_sets: &UsedQuerySets,
+        error_sink: &ErrorSink,
+        fn_ident: &'static str,
+    ) -> Option<id::QuerySetId> {
+        let Some(id) = *self.id.lock() else {
+            handle_error(error_sink, InvalidQuerySetError, None, fn_ident);
+            return None;
+        };
+        let mut used_query_sets = used_query_sets.lock();
+        if !used_query_sets
+            .iter()
+            .any(|destroyed| Arc::ptr_eq(destroyed, &self.destroyed))
+        {
+            used_query_sets.push(self.destroyed.clone());
+        }
+        Some(id)
+    }
 }
 impl Drop for WGPUQuerySetImpl {
     fn drop(&mut self) {
-        if !thread::panicking() {
-            let context = &self.context;
-            gfx_select!(self.id => context.query_set_drop(self.id));
+        handles::untrack(self);
+        if let Some(id) = self.id.get_mut().take() {
+            if !thread::panicking() {
+                let context = &self.context;
+                gfx_select!(id => context.query_set_drop(id));
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Command buffer uses a destroyed query set")]
+struct DestroyedQuerySetError;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Query set is invalid")]
+struct InvalidQuerySetError;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Pipeline is invalid")]
+struct InvalidPipelineError;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Pipeline uses {bind_groups} bind groups and {vertex_buffers} vertex buffers, more than the maxBindGroupsPlusVertexBuffers limit of {limit}")]
+struct BindGroupsPlusVertexBuffersError {
+    bind_groups: usize,
+    vertex_buffers: usize,
+    limit: u32,
+}
+impl BindGroupsPlusVertexBuffersError {
+    fn check(
+        bind_groups: usize,
+        vertex_buffers: usize,
+        limit: u32,
+    ) -> Result<(), BindGroupsPlusVertexBuffersError> {
+        if bind_groups + vertex_buffers > limit as usize {
+            return Err(BindGroupsPlusVertexBuffersError {
+                bind_groups,
+                vertex_buffers,
+                limit,
+            });
         }
+        Ok(())
     }
 }
 
@@ -230,6 +451,11 @@ pub struct WGPUQueueImpl {
     queue: Arc<QueueId>,
     error_sink: ErrorSink,
 }
+impl Drop for WGPUQueueImpl {
+    fn drop(&mut self) {
+        handles::untrack(self);
+    }
+}
 
 pub struct WGPURenderBundleImpl {
     context: Arc<Context>,
@@ -237,6 +463,7 @@ pub struct WGPURenderBundleImpl {
 }
 impl Drop for WGPURenderBundleImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             let context = &self.context;
             gfx_select!(self.id => context.render_bundle_drop(self.id));
@@ -250,6 +477,7 @@ pub struct WGPURenderBundleEncoderImpl {
 }
 impl Drop for WGPURenderBundleEncoderImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             let encoder = unsafe { Box::from_raw(self.encoder) };
             if let Some(encoder) = *encoder {
@@ -266,9 +494,11 @@ pub struct WGPURenderPassEncoderImpl {
     context: Arc<Context>,
     encoder: *mut dyn DynRenderPass,
     error_sink: ErrorSink,
+    used_query_sets: UsedQuerySets,
 }
 impl Drop for WGPURenderPassEncoderImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             drop(unsafe { Box::from_raw(self.encoder) });
         }
@@ -280,14 +510,18 @@ unsafe impl Sync for WGPURenderPassEncoderImpl {}
 
 pub struct WGPURenderPipelineImpl {
     context: Arc<Context>,
-    id: id::RenderPipelineId,
+    /// `None` if the descriptor was rejected before reaching wgpu-core.
+    id: Option<id::RenderPipelineId>,
     error_sink: ErrorSink,
 }
 impl Drop for WGPURenderPipelineImpl {
     fn drop(&mut self) {
-        if !thread::panicking() {
-            let context = &self.context;
-            gfx_select!(self.id => context.render_pipeline_drop(self.id));
+        handles::untrack(self);
+        if let Some(id) = self.id {
+            if !thread::panicking() {
+                let context = &self.context;
+                gfx_select!(id => context.render_pipeline_drop(id));
+            }
         }
     }
 }
@@ -298,6 +532,7 @@ pub struct WGPUSamplerImpl {
 }
 impl Drop for WGPUSamplerImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             let context = &self.context;
             gfx_select!(self.id => context.sampler_drop(self.id));
@@ -308,9 +543,15 @@ impl Drop for WGPUSamplerImpl {
 pub struct WGPUShaderModuleImpl {
     context: Arc<Context>,
     id: Option<id::ShaderModuleId>,
+    #[cfg(feature = "shader_inspection")]
+    source: Option<shader::ShaderSource>,
+    /// Scalar types of the overrides, keyed like pipeline constants.
+    overrides: Option<HashMap<String, naga::Scalar>>,
+    compilation_messages: Vec<shader::CompilationMessage>,
 }
 impl Drop for WGPUShaderModuleImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if let Some(id) = self.id {
             if !thread::panicking() {
                 let context = &self.context;
@@ -335,6 +576,7 @@ pub struct WGPUSurfaceImpl {
 }
 impl Drop for WGPUSurfaceImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             self.context.surface_drop(self.id);
         }
@@ -362,6 +604,7 @@ pub struct WGPUTextureImpl {
 }
 impl Drop for WGPUTextureImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if thread::panicking() {
             return;
         }
@@ -389,6 +632,7 @@ pub struct WGPUTextureViewImpl {
 }
 impl Drop for WGPUTextureViewImpl {
     fn drop(&mut self) {
+        handles::untrack(self);
         if !thread::panicking() {
             let context = &self.context;
             let _ = gfx_select!(self.id => context.texture_view_drop(self.id, false));
@@ -396,6 +640,52 @@ impl Drop for WGPUTextureViewImpl {
     }
 }
 
+macro_rules! impl_handle {
+    ($($ty:ident => $type_name:literal),+ $(,)?) => {
+        $(
+            impl handles::Handle for $ty {
+                const TYPE_NAME: &'static str = $type_name;
+
+                fn context(&self) -> &Context {
+                    &self.context
+                }
+            }
+        )+
+    };
+}
+impl_handle!(
+    WGPUAdapterImpl => "WGPUAdapter",
+    WGPUBindGroupImpl => "WGPUBindGroup",
+    WGPUBindGroupLayoutImpl => "WGPUBindGroupLayout",
+    WGPUBufferImpl => "WGPUBuffer",
+    WGPUCommandBufferImpl => "WGPUCommandBuffer",
+    WGPUCommandEncoderImpl => "WGPUCommandEncoder",
+    WGPUComputePassEncoderImpl => "WGPUComputePassEncoder",
+    WGPUComputePipelineImpl => "WGPUComputePipeline",
+    WGPUDeviceImpl => "WGPUDevice",
+    WGPUGpuProfilerImpl => "WGPUGpuProfiler",
+    WGPUInstanceImpl => "WGPUInstance",
+    WGPUPipelineLayoutImpl => "WGPUPipelineLayout",
+    WGPUQuerySetImpl => "WGPUQuerySet",
+    WGPURenderBundleImpl => "WGPURenderBundle",
+    WGPURenderBundleEncoderImpl => "WGPURenderBundleEncoder",
+    WGPURenderPassEncoderImpl => "WGPURenderPassEncoder",
+    WGPURenderPipelineImpl => "WGPURenderPipeline",
+    WGPUSamplerImpl => "WGPUSampler",
+    WGPUStagingBeltImpl => "WGPUStagingBelt",
+    WGPUShaderModuleImpl => "WGPUShaderModule",
+    WGPUSurfaceImpl => "WGPUSurface",
+    WGPUTextureImpl => "WGPUTexture",
+    WGPUTextureViewImpl => "WGPUTextureView",
+);
+impl handles::Handle for WGPUQueueImpl {
+    const TYPE_NAME: &'static str = "WGPUQueue";
+
+    fn context(&self) -> &Context {
+        &self.queue.context
+    }
+}
+
 struct DeviceCallback<T> {
     callback: T,
     userdata: *mut std::os::raw::c_void,
@@ -405,6 +695,15 @@ unsafe impl<T> Send for DeviceCallback<T> {}
 type UncapturedErrorCallback = DeviceCallback<native::WGPUErrorCallback>;
 type DeviceLostCallback = DeviceCallback<native::WGPUDeviceLostCallback>;
 
+impl DeviceLostCallback {
+    fn call(self, reason: native::WGPUDeviceLostReason, message: &str) {
+        if let Some(callback) = self.callback {
+            let msg = CString::new(message).unwrap();
+            unsafe { callback(reason, msg.as_ptr(), self.userdata) };
+        }
+    }
+}
+
 unsafe extern "C" fn default_uncaptured_error_handler(
     _typ: native::WGPUErrorType,
     message: *const ::std::os::raw::c_char,
@@ -420,10 +719,13 @@ const DEFAULT_UNCAPTURED_ERROR_HANDLER: UncapturedErrorCallback = UncapturedErro
 };
 
 unsafe extern "C" fn default_device_lost_handler(
-    _reason: native::WGPUDeviceLostReason,
+    reason: native::WGPUDeviceLostReason,
     message: *const ::std::os::raw::c_char,
     _userdata: *mut ::std::os::raw::c_void,
 ) {
+    if reason == native::WGPUDeviceLostReason_Destroyed {
+        return;
+    }
     let message = unsafe { CStr::from_ptr(message) }.to_str().unwrap();
     log::warn!("Handling wgpu device lost errors as fatal by default");
     panic!("wgpu device lost error:\n{message}\n");
@@ -486,6 +788,9 @@ struct ErrorSinkRaw {
     scopes: Vec<ErrorScope>,
     uncaptured_handler: UncapturedErrorCallback,
     device_lost_handler: DeviceLostCallback,
+    /// Set by `wgpuDeviceDestroy`, so that errors noticing the loss before
+    /// wgpu-core calls its device lost closure report the right reason.
+    destroyed: bool,
 }
 
 impl ErrorSinkRaw {
@@ -494,6 +799,15 @@ impl ErrorSinkRaw {
             scopes: Vec::new(),
             uncaptured_handler: DEFAULT_UNCAPTURED_ERROR_HANDLER,
             device_lost_handler,
+            destroyed: false,
+        }
+    }
+
+    /// The device lost callback is only called once, whichever way the loss is noticed first.
+    fn take_device_lost_handler(&mut self) -> DeviceLostCallback {
+        DeviceLostCallback {
+            callback: self.device_lost_handler.callback.take(),
+            userdata: self.device_lost_handler.userdata,
         }
     }
 
@@ -501,17 +815,13 @@ impl ErrorSinkRaw {
         let (typ, filter) = match err {
             crate::Error::DeviceLost { .. } => {
                 // handle device lost error early
-                if let Some(callback) = self.device_lost_handler.callback {
-                    let userdata = self.device_lost_handler.userdata;
-                    let msg = CString::new(err.to_string()).unwrap();
-                    unsafe {
-                        callback(
-                            native::WGPUDeviceLostReason_Destroyed,
-                            msg.as_ptr(),
-                            userdata,
-                        );
-                    };
-                }
+                let reason = if self.destroyed {
+                    native::WGPUDeviceLostReason_Destroyed
+                } else {
+                    native::WGPUDeviceLostReason_Unknown
+                };
+                self.take_device_lost_handler()
+                    .call(reason, &err.to_string());
                 return;
             }
             crate::Error::OutOfMemory { .. } => (
@@ -625,17 +935,31 @@ fn handle_error(
 pub unsafe extern "C" fn wgpuCreateInstance(
     descriptor: Option<&native::WGPUInstanceDescriptor>,
 ) -> native::WGPUInstance {
-    let instance_desc = match descriptor {
+    let (instance_desc, leak_detector, callback_executor) = match descriptor {
         Some(descriptor) => follow_chain!(map_instance_descriptor(
             (descriptor),
             WGPUSType_InstanceExtras => native::WGPUInstanceExtras
         )),
-        None => wgt::InstanceDescriptor::default(),
+        None => (
+            wgt::InstanceDescriptor::default(),
+            None,
+            utils::CallbackExecutor::new(None, std::ptr::null_mut()),
+        ),
     };
 
-    Arc::into_raw(Arc::new(WGPUInstanceImpl {
-        context: Arc::new(Context::new("wgpu", instance_desc)),
-    }))
+    // Not handed to `handles::into_raw`, the instance must not show up in its own leak report.
+    let instance = Arc::into_raw(Arc::new(WGPUInstanceImpl {
+        context: Arc::new(Context {
+            global: wgc::global::Global::new("wgpu", instance_desc),
+            leak_detector,
+            callbacks: Callbacks {
+                executor: callback_executor,
+                futures: Arc::new(futures::Futures::new()),
+            },
+        }),
+    }));
+    handles::register(instance);
+    instance
 }
 
 // Adapter methods
@@ -646,7 +970,7 @@ pub unsafe extern "C" fn wgpuAdapterEnumerateFeatures(
     features: *mut native::WGPUFeatureName,
 ) -> usize {
     let (adapter_id, context) = {
-        let adapter = adapter.as_ref().expect("invalid adapter");
+        let adapter = validate_handle!(adapter, "invalid adapter");
         (adapter.id, &adapter.context)
     };
     let adapter_features = match gfx_select!(adapter_id => context.adapter_features(adapter_id)) {
@@ -669,7 +993,7 @@ pub unsafe extern "C" fn wgpuAdapterGetLimits(
     limits: Option<&mut native::WGPUSupportedLimits>,
 ) -> native::WGPUBool {
     let (adapter_id, context) = {
-        let adapter = adapter.as_ref().expect("invalid adapter");
+        let adapter = validate_handle!(adapter, "invalid adapter");
         (adapter.id, &adapter.context)
     };
     let limits = limits.expect("invalid return pointer \"limits\"");
@@ -688,7 +1012,7 @@ pub unsafe extern "C" fn wgpuAdapterGetInfo(
     adapter: native::WGPUAdapter,
     info: Option<&mut native::WGPUAdapterInfo>,
 ) {
-    let adapter = adapter.as_ref().expect("invalid adapter");
+    let adapter = validate_handle!(adapter, "invalid adapter");
     let info = info.expect("invalid return pointer \"info\"");
     let context = adapter.context.as_ref();
     let adapter_id = adapter.id;
@@ -715,7 +1039,7 @@ pub unsafe extern "C" fn wgpuAdapterHasFeature(
     feature: native::WGPUFeatureName,
 ) -> native::WGPUBool {
     let (adapter_id, context) = {
-        let adapter = adapter.as_ref().expect("invalid adapter");
+        let adapter = validate_handle!(adapter, "invalid adapter");
         (adapter.id, &adapter.context)
     };
     let adapter_features = match gfx_select!(adapter_id => context.adapter_features(adapter_id)) {
@@ -753,49 +1077,81 @@ pub unsafe extern "C" fn wgpuAdapterRequestDevice(
     descriptor: Option<&native::WGPUDeviceDescriptor>,
     callback: native::WGPUAdapterRequestDeviceCallback,
     userdata: *mut std::os::raw::c_void,
+) {
+    let callback = callback.expect("invalid callback");
+
+    request_device(adapter, descriptor, |_, status, device, message| {
+        callback(status, device, message.as_ptr(), userdata)
+    });
+}
+
+unsafe fn request_device(
+    adapter: native::WGPUAdapter,
+    descriptor: Option<&native::WGPUDeviceDescriptor>,
+    callback: impl FnOnce(&Context, native::WGPURequestDeviceStatus, native::WGPUDevice, &CStr),
 ) {
     let (adapter_id, context) = {
-        let adapter = adapter.as_ref().expect("invalid adapter");
+        let adapter = validate_handle!(adapter, "invalid adapter");
         (adapter.id, &adapter.context)
     };
-    let callback = callback.expect("invalid callback");
 
     let adapter_limits = match gfx_select!(adapter_id => context.adapter_limits(adapter_id)) {
         Ok(adapter_limits) => adapter_limits,
         Err(cause) => {
             let msg = CString::new(format_error(&cause)).unwrap();
             callback(
+                context,
                 native::WGPURequestDeviceStatus_Error,
                 std::ptr::null(),
-                msg.as_ptr(),
-                userdata,
+                &msg,
             );
             return;
         }
     };
-    let base_limits = get_base_device_limits_from_adapter_limits(&adapter_limits);
 
-    let (desc, trace_str, device_lost_handler, error_callback) = match descriptor {
+    let (desc, required_limits, trace_str, device_lost_handler, error_callback) = match descriptor {
         Some(descriptor) => {
-            let (desc, trace_str, error_callback) = follow_chain!(
-                map_device_descriptor((descriptor, base_limits),
+            let (desc, required_limits, trace_str, error_callback) = match follow_chain!(
+                map_device_descriptor((descriptor, &adapter_limits),
                 WGPUSType_DeviceExtras => native::WGPUDeviceExtras)
-            );
+            ) {
+                Ok(mapped) => mapped,
+                Err(cause) => {
+                    let msg = CString::new(format_error(&cause)).unwrap();
+                    callback(
+                        context,
+                        native::WGPURequestDeviceStatus_Error,
+                        std::ptr::null(),
+                        &msg,
+                    );
+                    return;
+                }
+            };
             let device_lost_handler = DeviceLostCallback {
                 callback: descriptor.deviceLostCallback,
                 userdata: descriptor.deviceLostUserdata,
             };
-            (desc, trace_str, device_lost_handler, error_callback)
+            (
+                desc,
+                required_limits,
+                trace_str,
+                device_lost_handler,
+                error_callback,
+            )
+        }
+        None => {
+            let required_limits = conv::RequiredLimits::base(&adapter_limits);
+            (
+                wgt::DeviceDescriptor {
+                    required_limits: required_limits.limits.clone(),
+                    ..Default::default()
+                },
+                required_limits,
+                std::ptr::null(),
+                DEFAULT_DEVICE_LOST_HANDLER,
+                None,
+            )
         }
-        None => (
-            wgt::DeviceDescriptor {
-                required_limits: base_limits,
-                ..Default::default()
-            },
-            std::ptr::null(),
-            DEFAULT_DEVICE_LOST_HANDLER,
-            None,
-        ),
     };
 
     let (device_id, queue_id, err) = gfx_select!(adapter_id =>
@@ -814,29 +1170,58 @@ pub unsafe extern "C" fn wgpuAdapterRequestDevice(
             if let Some(error_callback) = error_callback {
                 error_sink.uncaptured_handler = error_callback;
             }
+            let error_sink = Arc::new(Mutex::new(error_sink));
+
+            // Called by wgpu-core once the device is lost, even if no call surfaces the error.
+            let device_lost_sink = error_sink.clone();
+            let device_lost_closure =
+                wgc::device::DeviceLostClosure::from_rust(Box::new(move |reason, message| {
+                    let reason = match reason {
+                        wgt::DeviceLostReason::Destroyed => native::WGPUDeviceLostReason_Destroyed,
+                        wgt::DeviceLostReason::Unknown | wgt::DeviceLostReason::DeviceInvalid => {
+                            native::WGPUDeviceLostReason_Unknown
+                        }
+                        // Releasing the device doesn't lose it as far as webgpu.h is concerned.
+                        wgt::DeviceLostReason::Dropped
+                        | wgt::DeviceLostReason::ReplacedCallback => return,
+                    };
+                    let device_lost_handler = device_lost_sink.lock().take_device_lost_handler();
+                    device_lost_handler.call(reason, &message);
+                }));
+            gfx_select!(device_id => context.device_set_device_lost_closure(device_id, device_lost_closure));
 
             callback(
+                context,
                 native::WGPURequestDeviceStatus_Success,
-                Arc::into_raw(Arc::new(WGPUDeviceImpl {
-                    context: context.clone(),
-                    id: device_id,
-                    queue: Arc::new(QueueId {
+                handles::into_raw(
+                    Arc::new(WGPUDeviceImpl {
                         context: context.clone(),
-                        id: queue_id,
+                        id: device_id,
+                        queue: Arc::new(QueueId {
+                            context: context.clone(),
+                            id: queue_id,
+                        }),
+                        error_sink,
+                        max_bind_groups_plus_vertex_buffers: required_limits
+                            .max_bind_groups_plus_vertex_buffers,
+                        mipmap_pipelines: Arc::new(MipmapPipelines {
+                            context: context.clone(),
+                            device_id,
+                            objects: Mutex::new(None),
+                        }),
                     }),
-                    error_sink: Arc::new(Mutex::new(error_sink)),
-                })),
-                message.as_ptr(),
-                userdata,
+                    desc.label.as_deref(),
+                ),
+                &message,
             );
         }
         Some(err) => {
             let message = CString::new(format_error(&err)).unwrap();
             callback(
+                context,
                 native::WGPURequestDeviceStatus_Error,
                 std::ptr::null_mut(),
-                message.as_ptr(),
-                userdata,
+                &message,
             );
         }
     }
@@ -844,12 +1229,12 @@ pub unsafe extern "C" fn wgpuAdapterRequestDevice(
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuAdapterReference(adapter: native::WGPUAdapter) {
-    assert!(!adapter.is_null(), "invalid adapter");
+    validate_handle!(adapter, "invalid adapter");
     Arc::increment_strong_count(adapter);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuAdapterRelease(adapter: native::WGPUAdapter) {
-    assert!(!adapter.is_null(), "invalid adapter");
+    validate_handle!(adapter, "invalid adapter");
     Arc::decrement_strong_count(adapter);
 }
 
@@ -857,12 +1242,12 @@ pub unsafe extern "C" fn wgpuAdapterRelease(adapter: native::WGPUAdapter) {
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuBindGroupReference(bind_group: native::WGPUBindGroup) {
-    assert!(!bind_group.is_null(), "invalid bind group");
+    validate_handle!(bind_group, "invalid bind group");
     Arc::increment_strong_count(bind_group);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuBindGroupRelease(bind_group: native::WGPUBindGroup) {
-    assert!(!bind_group.is_null(), "invalid bind group");
+    validate_handle!(bind_group, "invalid bind group");
     Arc::decrement_strong_count(bind_group);
 }
 
@@ -872,14 +1257,14 @@ pub unsafe extern "C" fn wgpuBindGroupRelease(bind_group: native::WGPUBindGroup)
 pub unsafe extern "C" fn wgpuBindGroupLayoutReference(
     bind_group_layout: native::WGPUBindGroupLayout,
 ) {
-    assert!(!bind_group_layout.is_null(), "invalid bind group layout");
+    validate_handle!(bind_group_layout, "invalid bind group layout");
     Arc::increment_strong_count(bind_group_layout);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuBindGroupLayoutRelease(
     bind_group_layout: native::WGPUBindGroupLayout,
 ) {
-    assert!(!bind_group_layout.is_null(), "invalid bind group layout");
+    validate_handle!(bind_group_layout, "invalid bind group layout");
     Arc::decrement_strong_count(bind_group_layout);
 }
 
@@ -888,7 +1273,7 @@ pub unsafe extern "C" fn wgpuBindGroupLayoutRelease(
 #[no_mangle]
 pub unsafe extern "C" fn wgpuBufferDestroy(buffer: native::WGPUBuffer) {
     let (buffer_id, context) = {
-        let buffer = buffer.as_ref().expect("invalid buffer");
+        let buffer = validate_handle!(buffer, "invalid buffer");
         (buffer.id, &buffer.context)
     };
     // Per spec, no error to report. Even calling destroy multiple times is valid.
@@ -901,9 +1286,9 @@ pub unsafe extern "C" fn wgpuBufferGetConstMappedRange(
     offset: usize,
     size: usize,
 ) -> *const u8 {
-    let (buffer_id, context) = {
-        let buffer = buffer.as_ref().expect("invalid buffer");
-        (buffer.id, &buffer.context)
+    let (buffer_id, context, error_sink) = {
+        let buffer = validate_handle!(buffer, "invalid buffer");
+        (buffer.id, &buffer.context, &buffer.error_sink)
     };
 
     let buf = match gfx_select!(buffer_id => context.buffer_get_mapped_range(
@@ -915,7 +1300,10 @@ pub unsafe extern "C" fn wgpuBufferGetConstMappedRange(
         }
     )) {
         Ok((ptr, _)) => ptr,
-        Err(err) => handle_error_fatal(err, "wgpuBufferGetConstMappedRange"),
+        Err(cause) => {
+            handle_error(error_sink, cause, None, "wgpuBufferGetConstMappedRange");
+            return std::ptr::null();
+        }
     };
 
     buf.as_ptr()
@@ -927,9 +1315,9 @@ pub unsafe extern "C" fn wgpuBufferGetMappedRange(
     offset: usize,
     size: usize,
 ) -> *mut u8 {
-    let (buffer_id, context) = {
-        let buffer = buffer.as_ref().expect("invalid buffer");
-        (buffer.id, &buffer.context)
+    let (buffer_id, context, error_sink) = {
+        let buffer = validate_handle!(buffer, "invalid buffer");
+        (buffer.id, &buffer.context, &buffer.error_sink)
     };
 
     let buf = match gfx_select!(buffer_id => context.buffer_get_mapped_range(
@@ -941,7 +1329,10 @@ pub unsafe extern "C" fn wgpuBufferGetMappedRange(
         }
     )) {
         Ok((ptr, _)) => ptr,
-        Err(err) => handle_error_fatal(err, "wgpuBufferGetMappedRange"),
+        Err(cause) => {
+            handle_error(error_sink, cause, None, "wgpuBufferGetMappedRange");
+            return std::ptr::null_mut();
+        }
     };
 
     buf.as_ptr()
@@ -949,7 +1340,7 @@ pub unsafe extern "C" fn wgpuBufferGetMappedRange(
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuBufferGetSize(buffer: native::WGPUBuffer) -> u64 {
-    let buffer = buffer.as_ref().expect("invalid buffer");
+    let buffer = validate_handle!(buffer, "invalid buffer");
     buffer.data.size
 }
 
@@ -957,7 +1348,7 @@ pub unsafe extern "C" fn wgpuBufferGetSize(buffer: native::WGPUBuffer) -> u64 {
 pub unsafe extern "C" fn wgpuBufferGetUsage(
     buffer: native::WGPUBuffer,
 ) -> native::WGPUBufferUsageFlags {
-    let buffer = buffer.as_ref().expect("invalid buffer");
+    let buffer = validate_handle!(buffer, "invalid buffer");
     buffer.data.usage
 }
 
@@ -970,13 +1361,36 @@ pub unsafe extern "C" fn wgpuBufferMapAsync(
     callback: native::WGPUBufferMapAsyncCallback,
     userdata: *mut std::ffi::c_void,
 ) {
-    let (buffer_id, context, error_sink) = {
-        let buffer = buffer.as_ref().expect("invalid buffer");
-        (buffer.id, &buffer.context, &buffer.error_sink)
-    };
+    let executor = validate_handle!(buffer, "invalid buffer")
+        .context
+        .callbacks
+        .executor
+        .clone();
     let callback = callback.expect("invalid callback");
     let userdata = utils::Userdata::new(userdata);
 
+    buffer_map_async(buffer, mode, offset, size, move |status| {
+        executor.execute(move || callback(status, userdata.as_ptr()))
+    });
+}
+
+unsafe fn buffer_map_async(
+    buffer: native::WGPUBuffer,
+    mode: native::WGPUMapModeFlags,
+    offset: usize,
+    size: usize,
+    callback: impl FnOnce(native::WGPUBufferMapAsyncStatus) + Send + 'static,
+) {
+    let (buffer_id, context, error_sink, buffer_size) = {
+        let buffer = validate_handle!(buffer, "invalid buffer");
+        (
+            buffer.id,
+            &buffer.context,
+            &buffer.error_sink,
+            buffer.data.size,
+        )
+    };
+
     let operation = wgc::resource::BufferMapOperation {
         host: match mode as native::WGPUMapMode {
             native::WGPUMapMode_Write => wgc::device::HostMap::Write,
@@ -997,12 +1411,20 @@ pub unsafe extern "C" fn wgpuBufferMapAsync(
                     | Err(resource::BufferAccessError::DestroyedResource(_)) => {
                         native::WGPUBufferMapAsyncStatus_DestroyedBeforeCallback
                     }
+                    Err(resource::BufferAccessError::OutOfBoundsOverrun { .. })
+                    | Err(resource::BufferAccessError::NegativeRange { .. })
+                        if offset as wgt::BufferAddress > buffer_size =>
+                    {
+                        native::WGPUBufferMapAsyncStatus_OffsetOutOfRange
+                    }
+                    Err(resource::BufferAccessError::OutOfBoundsOverrun { .. })
+                    | Err(resource::BufferAccessError::NegativeRange { .. }) => {
+                        native::WGPUBufferMapAsyncStatus_SizeOutOfRange
+                    }
                     Err(_) => native::WGPUBufferMapAsyncStatus_ValidationError,
-                    // TODO: WGPUBufferMapAsyncStatus_OffsetOutOfRange
-                    // TODO: WGPUBufferMapAsyncStatus_SizeOutOfRange
                 };
 
-                callback(status, userdata.as_ptr());
+                callback(status);
             },
         ))),
     };
@@ -1010,7 +1432,10 @@ pub unsafe extern "C" fn wgpuBufferMapAsync(
     if let Err(cause) = gfx_select!(buffer_id => context.buffer_map_async(
         buffer_id,
         offset as wgt::BufferAddress,
-        Some(size as wgt::BufferAddress),
+        match size {
+            conv::WGPU_WHOLE_MAP_SIZE => None,
+            _ => Some(size as wgt::BufferAddress),
+        },
         operation,
     )) {
         handle_error(error_sink, cause, None, "wgpuBufferMapAsync");
@@ -1020,7 +1445,7 @@ pub unsafe extern "C" fn wgpuBufferMapAsync(
 #[no_mangle]
 pub unsafe extern "C" fn wgpuBufferUnmap(buffer: native::WGPUBuffer) {
     let (buffer_id, context, error_sink) = {
-        let buffer = buffer.as_ref().expect("invalid buffer");
+        let buffer = validate_handle!(buffer, "invalid buffer");
         (buffer.id, &buffer.context, &buffer.error_sink)
     };
 
@@ -1031,12 +1456,12 @@ pub unsafe extern "C" fn wgpuBufferUnmap(buffer: native::WGPUBuffer) {
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuBufferReference(buffer: native::WGPUBuffer) {
-    assert!(!buffer.is_null(), "invalid buffer");
+    validate_handle!(buffer, "invalid buffer");
     Arc::increment_strong_count(buffer);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuBufferRelease(buffer: native::WGPUBuffer) {
-    assert!(!buffer.is_null(), "invalid buffer");
+    validate_handle!(buffer, "invalid buffer");
     Arc::decrement_strong_count(buffer);
 }
 
@@ -1044,12 +1469,12 @@ pub unsafe extern "C" fn wgpuBufferRelease(buffer: native::WGPUBuffer) {
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuCommandBufferReference(command_buffer: native::WGPUCommandBuffer) {
-    assert!(!command_buffer.is_null(), "invalid command buffer");
+    validate_handle!(command_buffer, "invalid command buffer");
     Arc::increment_strong_count(command_buffer);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuCommandBufferRelease(command_buffer: native::WGPUCommandBuffer) {
-    assert!(!command_buffer.is_null(), "invalid command buffer");
+    validate_handle!(command_buffer, "invalid command buffer");
     Arc::decrement_strong_count(command_buffer);
 }
 
@@ -1060,30 +1485,36 @@ pub unsafe extern "C" fn wgpuCommandEncoderBeginComputePass(
     command_encoder: native::WGPUCommandEncoder,
     descriptor: Option<&native::WGPUComputePassDescriptor>,
 ) -> native::WGPUComputePassEncoder {
-    let (command_encoder_id, context, error_sink) = {
-        let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+    let (command_encoder_id, context, error_sink, used_query_sets) = {
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
         (
             command_encoder.id,
             &command_encoder.context,
             &command_encoder.error_sink,
+            &command_encoder.used_query_sets,
         )
     };
 
-    let timestamp_writes = descriptor.and_then(|descriptor| {
-        descriptor.timestampWrites.as_ref().map(|timestamp_write| {
-            wgc::command::PassTimestampWrites {
-                query_set: timestamp_write
-                    .querySet
-                    .as_ref()
-                    .expect("invalid query set in timestamp writes")
-                    .id,
+    let timestamp_writes =
+        match descriptor.and_then(|descriptor| descriptor.timestampWrites.as_ref()) {
+            Some(timestamp_write) => validate_handle!(
+                timestamp_write.querySet,
+                "invalid query set in timestamp writes"
+            )
+            .record_use(
+                used_query_sets,
+                error_sink,
+                "wgpuCommandEncoderBeginComputePass",
+            )
+            .map(|query_set_id| wgc::command::PassTimestampWrites {
+                query_set: query_set_id,
                 beginning_of_pass_write_index: map_query_set_index(
                     timestamp_write.beginningOfPassWriteIndex,
                 ),
                 end_of_pass_write_index: map_query_set_index(timestamp_write.endOfPassWriteIndex),
-            }
-        })
-    });
+            }),
+            None => None,
+        };
 
     let desc = match descriptor {
         Some(descriptor) => wgc::command::ComputePassDescriptor {
@@ -1098,15 +1529,19 @@ pub unsafe extern "C" fn wgpuCommandEncoderBeginComputePass(
         handle_error(
             error_sink,
             cause,
-            desc.label,
+            desc.label.clone(),
             "wgpuCommandEncoderBeginComputePass",
         );
     }
-    Arc::into_raw(Arc::new(WGPUComputePassEncoderImpl {
-        context: context.clone(),
-        encoder: Box::into_raw(pass),
-        error_sink: error_sink.clone(),
-    }))
+    handles::into_raw(
+        Arc::new(WGPUComputePassEncoderImpl {
+            context: context.clone(),
+            encoder: Box::into_raw(pass),
+            error_sink: error_sink.clone(),
+            used_query_sets: used_query_sets.clone(),
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -1114,23 +1549,26 @@ pub unsafe extern "C" fn wgpuCommandEncoderBeginRenderPass(
     command_encoder: native::WGPUCommandEncoder,
     descriptor: Option<&native::WGPURenderPassDescriptor>,
 ) -> native::WGPURenderPassEncoder {
-    let (command_encoder_id, context, error_sink) = {
-        let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+    let (command_encoder_id, context, error_sink, used_query_sets) = {
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
         (
             command_encoder.id,
             &command_encoder.context,
             &command_encoder.error_sink,
+            &command_encoder.used_query_sets,
         )
     };
     let descriptor = descriptor.expect("invalid descriptor");
 
-    let depth_stencil_attachment = descriptor.depthStencilAttachment.as_ref().map(|desc| {
-        wgc::command::RenderPassDepthStencilAttachment {
-            view: desc
-                .view
-                .as_ref()
-                .expect("invalid texture view for depth stencil attachment")
-                .id,
+    // Not a closure, validate_handle! may return early.
+    #[allow(clippy::manual_map)]
+    let depth_stencil_attachment = match descriptor.depthStencilAttachment.as_ref() {
+        Some(desc) => Some(wgc::command::RenderPassDepthStencilAttachment {
+            view: validate_handle!(
+                desc.view,
+                "invalid texture view for depth stencil attachment"
+            )
+            .id,
             depth: wgc::command::PassChannel {
                 load_op: conv::map_load_op(desc.depthLoadOp).unwrap_or(wgc::command::LoadOp::Load),
                 store_op: conv::map_store_op(desc.depthStoreOp)
@@ -1146,22 +1584,38 @@ pub unsafe extern "C" fn wgpuCommandEncoderBeginRenderPass(
                 clear_value: desc.stencilClearValue,
                 read_only: desc.stencilReadOnly != 0,
             },
-        }
-    });
+        }),
+        None => None,
+    };
 
-    let timestamp_writes = descriptor.timestampWrites.as_ref().map(|timestamp_write| {
-        wgc::command::PassTimestampWrites {
-            query_set: timestamp_write
-                .querySet
-                .as_ref()
-                .expect("invalid query set in timestamp writes")
-                .id,
+    let timestamp_writes = match descriptor.timestampWrites.as_ref() {
+        Some(timestamp_write) => validate_handle!(
+            timestamp_write.querySet,
+            "invalid query set in timestamp writes"
+        )
+        .record_use(
+            used_query_sets,
+            error_sink,
+            "wgpuCommandEncoderBeginRenderPass",
+        )
+        .map(|query_set_id| wgc::command::PassTimestampWrites {
+            query_set: query_set_id,
             beginning_of_pass_write_index: map_query_set_index(
                 timestamp_write.beginningOfPassWriteIndex,
             ),
             end_of_pass_write_index: map_query_set_index(timestamp_write.endOfPassWriteIndex),
-        }
-    });
+        }),
+        None => None,
+    };
+    let occlusion_query_set_id = if descriptor.occlusionQuerySet.is_null() {
+        None
+    } else {
+        validate_handle!(descriptor.occlusionQuerySet, "invalid occlusion query set").record_use(
+            used_query_sets,
+            error_sink,
+            "wgpuCommandEncoderBeginRenderPass",
+        )
+    };
 
     let desc = wgc::command::RenderPassDescriptor {
         label: ptr_into_label(descriptor.label),
@@ -1192,7 +1646,7 @@ pub unsafe extern "C" fn wgpuCommandEncoderBeginRenderPass(
         ),
         depth_stencil_attachment: depth_stencil_attachment.as_ref(),
         timestamp_writes: timestamp_writes.as_ref(),
-        occlusion_query_set: descriptor.occlusionQuerySet.as_ref().map(|v| v.id),
+        occlusion_query_set: occlusion_query_set_id,
     };
 
     let (pass, err) = gfx_select!(command_encoder_id => context.command_encoder_create_render_pass_dyn(command_encoder_id, &desc));
@@ -1200,15 +1654,19 @@ pub unsafe extern "C" fn wgpuCommandEncoderBeginRenderPass(
         handle_error(
             error_sink,
             cause,
-            desc.label,
+            desc.label.clone(),
             "wgpuCommandEncoderBeginRenderPass",
         );
     }
-    Arc::into_raw(Arc::new(WGPURenderPassEncoderImpl {
-        context: context.clone(),
-        encoder: Box::into_raw(pass),
-        error_sink: error_sink.clone(),
-    }))
+    handles::into_raw(
+        Arc::new(WGPURenderPassEncoderImpl {
+            context: context.clone(),
+            encoder: Box::into_raw(pass),
+            error_sink: error_sink.clone(),
+            used_query_sets: used_query_sets.clone(),
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -1219,14 +1677,14 @@ pub unsafe extern "C" fn wgpuCommandEncoderClearBuffer(
     size: u64,
 ) {
     let (command_encoder_id, context, error_sink) = {
-        let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
         (
             command_encoder.id,
             &command_encoder.context,
             &command_encoder.error_sink,
         )
     };
-    let buffer_id = buffer.as_ref().expect("invalid buffer").id;
+    let buffer_id = validate_handle!(buffer, "invalid buffer").id;
 
     if let Err(cause) = gfx_select!(command_encoder_id => context.command_encoder_clear_buffer(
         command_encoder_id,
@@ -1252,15 +1710,15 @@ pub unsafe extern "C" fn wgpuCommandEncoderCopyBufferToBuffer(
     size: u64,
 ) {
     let (command_encoder_id, context, error_sink) = {
-        let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
         (
             command_encoder.id,
             &command_encoder.context,
             &command_encoder.error_sink,
         )
     };
-    let source_buffer_id = source.as_ref().expect("invalid source").id;
-    let destination_buffer_id = destination.as_ref().expect("invalid destination").id;
+    let source_buffer_id = validate_handle!(source, "invalid source").id;
+    let destination_buffer_id = validate_handle!(destination, "invalid destination").id;
 
     if let Err(cause) = gfx_select!(command_encoder_id => context.command_encoder_copy_buffer_to_buffer(
         command_encoder_id,
@@ -1287,7 +1745,7 @@ pub unsafe extern "C" fn wgpuCommandEncoderCopyBufferToTexture(
     copy_size: Option<&native::WGPUExtent3D>,
 ) {
     let (command_encoder_id, context, error_sink) = {
-        let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
         (
             command_encoder.id,
             &command_encoder.context,
@@ -1295,10 +1753,18 @@ pub unsafe extern "C" fn wgpuCommandEncoderCopyBufferToTexture(
         )
     };
 
+    let (source, destination) = match (
+        conv::map_image_copy_buffer(source.expect("invalid source")),
+        conv::map_image_copy_texture(destination.expect("invalid destination")),
+    ) {
+        (Some(source), Some(destination)) => (source, destination),
+        _ => return,
+    };
+
     if let Err(cause) = gfx_select!(command_encoder_id => context.command_encoder_copy_buffer_to_texture(
         command_encoder_id,
-        &conv::map_image_copy_buffer(source.expect("invalid source")),
-        &conv::map_image_copy_texture(destination.expect("invalid destination")),
+        &source,
+        &destination,
         &conv::map_extent3d(copy_size.expect("invalid copy size"))
     )) {
         handle_error(
@@ -1318,7 +1784,7 @@ pub unsafe extern "C" fn wgpuCommandEncoderCopyTextureToBuffer(
     copy_size: Option<&native::WGPUExtent3D>,
 ) {
     let (command_encoder_id, context, error_sink) = {
-        let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
         (
             command_encoder.id,
             &command_encoder.context,
@@ -1326,10 +1792,18 @@ pub unsafe extern "C" fn wgpuCommandEncoderCopyTextureToBuffer(
         )
     };
 
+    let (source, destination) = match (
+        conv::map_image_copy_texture(source.expect("invalid source")),
+        conv::map_image_copy_buffer(destination.expect("invalid destination")),
+    ) {
+        (Some(source), Some(destination)) => (source, destination),
+        _ => return,
+    };
+
     if let Err(cause) = gfx_select!(command_encoder_id => context.command_encoder_copy_texture_to_buffer(
         command_encoder_id,
-        &conv::map_image_copy_texture(source.expect("invalid source")),
-        &conv::map_image_copy_buffer(destination.expect("invalid destination")),
+        &source,
+        &destination,
         &conv::map_extent3d(copy_size.expect("invalid copy size"))
     )) {
         handle_error(
@@ -1349,7 +1823,7 @@ pub unsafe extern "C" fn wgpuCommandEncoderCopyTextureToTexture(
     copy_size: Option<&native::WGPUExtent3D>,
 ) {
     let (command_encoder_id, context, error_sink) = {
-        let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
         (
             command_encoder.id,
             &command_encoder.context,
@@ -1357,10 +1831,18 @@ pub unsafe extern "C" fn wgpuCommandEncoderCopyTextureToTexture(
         )
     };
 
+    let (source, destination) = match (
+        conv::map_image_copy_texture(source.expect("invalid source")),
+        conv::map_image_copy_texture(destination.expect("invalid destination")),
+    ) {
+        (Some(source), Some(destination)) => (source, destination),
+        _ => return,
+    };
+
     if let Err(cause) = gfx_select!(command_encoder_id => context.command_encoder_copy_texture_to_texture(
         command_encoder_id,
-        &conv::map_image_copy_texture(source.expect("invalid source")),
-        &conv::map_image_copy_texture(destination.expect("invalid destination")),
+        &source,
+        &destination,
         &conv::map_extent3d(copy_size.expect("invalid copy size"))
     )) {
         handle_error(
@@ -1377,7 +1859,7 @@ pub unsafe extern "C" fn wgpuCommandEncoderFinish(
     command_encoder: native::WGPUCommandEncoder,
     descriptor: Option<&native::WGPUCommandBufferDescriptor>,
 ) -> native::WGPUCommandBuffer {
-    let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+    let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
     let (command_encoder_id, context, error_sink) = (
         command_encoder.id,
         &command_encoder.context,
@@ -1397,11 +1879,15 @@ pub unsafe extern "C" fn wgpuCommandEncoderFinish(
         handle_error(error_sink, cause, None, "wgpuCommandEncoderFinish");
     }
 
-    Arc::into_raw(Arc::new(WGPUCommandBufferImpl {
-        context: context.clone(),
-        id: command_buffer_id,
-        open: atomic::AtomicBool::new(true),
-    }))
+    handles::into_raw(
+        Arc::new(WGPUCommandBufferImpl {
+            context: context.clone(),
+            id: command_buffer_id,
+            open: atomic::AtomicBool::new(true),
+            used_query_sets: std::mem::take(&mut *command_encoder.used_query_sets.lock()),
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -1410,7 +1896,7 @@ pub unsafe extern "C" fn wgpuCommandEncoderInsertDebugMarker(
     marker_label: *const std::ffi::c_char,
 ) {
     let (command_encoder_id, context, error_sink) = {
-        let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
         (
             command_encoder.id,
             &command_encoder.context,
@@ -1434,7 +1920,7 @@ pub unsafe extern "C" fn wgpuCommandEncoderPopDebugGroup(
     command_encoder: native::WGPUCommandEncoder,
 ) {
     let (command_encoder_id, context, error_sink) = {
-        let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
         (
             command_encoder.id,
             &command_encoder.context,
@@ -1454,7 +1940,7 @@ pub unsafe extern "C" fn wgpuCommandEncoderPushDebugGroup(
     group_label: *const std::ffi::c_char,
 ) {
     let (command_encoder_id, context, error_sink) = {
-        let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
         (
             command_encoder.id,
             &command_encoder.context,
@@ -1477,16 +1963,23 @@ pub unsafe extern "C" fn wgpuCommandEncoderResolveQuerySet(
     destination: native::WGPUBuffer,
     destination_offset: u64,
 ) {
-    let (command_encoder_id, context, error_sink) = {
-        let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+    let (command_encoder_id, context, error_sink, used_query_sets) = {
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
         (
             command_encoder.id,
             &command_encoder.context,
             &command_encoder.error_sink,
+            &command_encoder.used_query_sets,
         )
     };
-    let query_set_id = query_set.as_ref().expect("invalid query set").id;
-    let destination_buffer_id = destination.as_ref().expect("invalid destination").id;
+    let Some(query_set_id) = validate_handle!(query_set, "invalid query set").record_use(
+        used_query_sets,
+        error_sink,
+        "wgpuCommandEncoderResolveQuerySet",
+    ) else {
+        return;
+    };
+    let destination_buffer_id = validate_handle!(destination, "invalid destination").id;
 
     if let Err(cause) = gfx_select!(command_encoder_id => context.command_encoder_resolve_query_set(
         command_encoder_id,
@@ -1506,15 +1999,22 @@ pub unsafe extern "C" fn wgpuCommandEncoderWriteTimestamp(
     query_set: native::WGPUQuerySet,
     query_index: u32,
 ) {
-    let (command_encoder_id, context, error_sink) = {
-        let command_encoder = command_encoder.as_ref().expect("invalid command encoder");
+    let (command_encoder_id, context, error_sink, used_query_sets) = {
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
         (
             command_encoder.id,
             &command_encoder.context,
             &command_encoder.error_sink,
+            &command_encoder.used_query_sets,
         )
     };
-    let query_set_id = query_set.as_ref().expect("invalid query set").id;
+    let Some(query_set_id) = validate_handle!(query_set, "invalid query set").record_use(
+        used_query_sets,
+        error_sink,
+        "wgpuCommandEncoderWriteTimestamp",
+    ) else {
+        return;
+    };
 
     if let Err(cause) = gfx_select!(command_encoder_id => context.command_encoder_write_timestamp(
         command_encoder_id,
@@ -1527,12 +2027,12 @@ pub unsafe extern "C" fn wgpuCommandEncoderWriteTimestamp(
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuCommandEncoderReference(command_encoder: native::WGPUCommandEncoder) {
-    assert!(!command_encoder.is_null(), "invalid command encoder");
+    validate_handle!(command_encoder, "invalid command encoder");
     Arc::increment_strong_count(command_encoder);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuCommandEncoderRelease(command_encoder: native::WGPUCommandEncoder) {
-    assert!(!command_encoder.is_null(), "invalid command encoder");
+    validate_handle!(command_encoder, "invalid command encoder");
     Arc::decrement_strong_count(command_encoder);
 }
 
@@ -1545,7 +2045,7 @@ pub unsafe extern "C" fn wgpuComputePassEncoderDispatchWorkgroups(
     workgroup_count_y: u32,
     workgroup_count_z: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid compute pass");
+    let pass = validate_handle!(pass, "invalid compute pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.dispatch_workgroups(
@@ -1570,11 +2070,8 @@ pub unsafe extern "C" fn wgpuComputePassEncoderDispatchWorkgroupsIndirect(
     indirect_buffer: native::WGPUBuffer,
     indirect_offset: u64,
 ) {
-    let pass = pass.as_ref().expect("invalid compute pass");
-    let indirect_buffer_id = indirect_buffer
-        .as_ref()
-        .expect("invalid indirect buffer")
-        .id;
+    let pass = validate_handle!(pass, "invalid compute pass");
+    let indirect_buffer_id = validate_handle!(indirect_buffer, "invalid indirect buffer").id;
 
     let encoder = pass.encoder.as_mut().unwrap();
 
@@ -1591,7 +2088,7 @@ pub unsafe extern "C" fn wgpuComputePassEncoderDispatchWorkgroupsIndirect(
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuComputePassEncoderEnd(pass: native::WGPUComputePassEncoder) {
-    let pass = pass.as_ref().expect("invalid compute pass");
+    let pass = validate_handle!(pass, "invalid compute pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.end(&pass.context) {
@@ -1605,7 +2102,7 @@ pub unsafe extern "C" fn wgpuComputePassEncoderInsertDebugMarker(
     pass: native::WGPUComputePassEncoder,
     marker_label: *const std::ffi::c_char,
 ) {
-    let pass = pass.as_ref().expect("invalid compute pass");
+    let pass = validate_handle!(pass, "invalid compute pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.insert_debug_marker(
@@ -1625,7 +2122,7 @@ pub unsafe extern "C" fn wgpuComputePassEncoderInsertDebugMarker(
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuComputePassEncoderPopDebugGroup(pass: native::WGPUComputePassEncoder) {
-    let pass = pass.as_ref().expect("invalid compute pass");
+    let pass = validate_handle!(pass, "invalid compute pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.pop_debug_group(&pass.context) {
@@ -1644,7 +2141,7 @@ pub unsafe extern "C" fn wgpuComputePassEncoderPushDebugGroup(
     pass: native::WGPUComputePassEncoder,
     group_label: *const std::ffi::c_char,
 ) {
-    let pass = pass.as_ref().expect("invalid compute pass");
+    let pass = validate_handle!(pass, "invalid compute pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.push_debug_group(
@@ -1670,9 +2167,9 @@ pub unsafe extern "C" fn wgpuComputePassEncoderSetBindGroup(
     dynamic_offset_count: usize,
     dynamic_offsets: *const u32,
 ) {
-    let pass = pass.as_ref().expect("invalid compute pass");
+    let pass = validate_handle!(pass, "invalid compute pass");
     //TODO: as per webgpu.h bindgroup is nullable
-    let bind_group_id = bind_group.as_ref().expect("invalid bind group").id;
+    let bind_group_id = validate_handle!(bind_group, "invalid bind group").id;
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.set_bind_group(
@@ -1696,11 +2193,19 @@ pub unsafe extern "C" fn wgpuComputePassEncoderSetPipeline(
     pass: native::WGPUComputePassEncoder,
     compute_pipeline: native::WGPUComputePipeline,
 ) {
-    let pass = pass.as_ref().expect("invalid compute pass");
-    let compute_pipeline_id = compute_pipeline
-        .as_ref()
-        .expect("invalid compute pipeline")
-        .id;
+    let pass = validate_handle!(pass, "invalid compute pass");
+    let compute_pipeline_id =
+        match validate_handle!(compute_pipeline, "invalid compute pipeline").id {
+            Some(id) => id,
+            None => {
+                return handle_error(
+                    &pass.error_sink,
+                    InvalidPipelineError,
+                    None,
+                    "wgpuComputePassEncoderSetPipeline",
+                )
+            }
+        };
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.set_pipeline(&pass.context, compute_pipeline_id) {
@@ -1718,20 +2223,14 @@ pub unsafe extern "C" fn wgpuComputePassEncoderSetPipeline(
 pub unsafe extern "C" fn wgpuComputePassEncoderReference(
     compute_pass_encoder: native::WGPUComputePassEncoder,
 ) {
-    assert!(
-        !compute_pass_encoder.is_null(),
-        "invalid command pass encoder"
-    );
+    validate_handle!(compute_pass_encoder, "invalid command pass encoder");
     Arc::increment_strong_count(compute_pass_encoder);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuComputePassEncoderRelease(
     compute_pass_encoder: native::WGPUComputePassEncoder,
 ) {
-    assert!(
-        !compute_pass_encoder.is_null(),
-        "invalid command pass encoder"
-    );
+    validate_handle!(compute_pass_encoder, "invalid command pass encoder");
     Arc::decrement_strong_count(compute_pass_encoder);
 }
 
@@ -1743,9 +2242,21 @@ pub unsafe extern "C" fn wgpuComputePipelineGetBindGroupLayout(
     group_index: u32,
 ) -> native::WGPUBindGroupLayout {
     let (pipeline_id, context, error_sink) = {
-        let pipeline = pipeline.as_ref().expect("invalid pipeline");
+        let pipeline = validate_handle!(pipeline, "invalid pipeline");
         (pipeline.id, &pipeline.context, &pipeline.error_sink)
     };
+    let pipeline_id = match pipeline_id {
+        Some(id) => id,
+        None => {
+            handle_error(
+                error_sink,
+                InvalidPipelineError,
+                None,
+                "wgpuComputePipelineGetBindGroupLayout",
+            );
+            return std::ptr::null();
+        }
+    };
 
     let (bind_group_layout_id, error) = gfx_select!(pipeline_id => context.compute_pipeline_get_bind_group_layout(pipeline_id, group_index, None));
     if let Some(cause) = error {
@@ -1757,22 +2268,25 @@ pub unsafe extern "C" fn wgpuComputePipelineGetBindGroupLayout(
         );
     }
 
-    Arc::into_raw(Arc::new(WGPUBindGroupLayoutImpl {
-        context: context.clone(),
-        id: bind_group_layout_id,
-    }))
+    handles::into_raw(
+        Arc::new(WGPUBindGroupLayoutImpl {
+            context: context.clone(),
+            id: bind_group_layout_id,
+        }),
+        None,
+    )
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuComputePipelineReference(
     compute_pipeline: native::WGPUComputePipeline,
 ) {
-    assert!(!compute_pipeline.is_null(), "invalid command pipeline");
+    validate_handle!(compute_pipeline, "invalid command pipeline");
     Arc::increment_strong_count(compute_pipeline);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuComputePipelineRelease(compute_pipeline: native::WGPUComputePipeline) {
-    assert!(!compute_pipeline.is_null(), "invalid command pipeline");
+    validate_handle!(compute_pipeline, "invalid command pipeline");
     Arc::decrement_strong_count(compute_pipeline);
 }
 
@@ -1784,24 +2298,25 @@ pub unsafe extern "C" fn wgpuDeviceCreateBindGroup(
     descriptor: Option<&native::WGPUBindGroupDescriptor>,
 ) -> native::WGPUBindGroup {
     let (device_id, context, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context, &device.error_sink)
     };
     let descriptor = descriptor.expect("invalid descriptor");
-    let bind_group_layout_id = descriptor
-        .layout
-        .as_ref()
-        .expect("invalid bind group layout for bind group descriptor")
-        .id;
-
-    let entries = make_slice(descriptor.entries, descriptor.entryCount)
-        .iter()
-        .map(|entry| {
-            follow_chain!(map_bind_group_entry((entry),
-                WGPUSType_BindGroupEntryExtras => native::WGPUBindGroupEntryExtras)
-            )
-        })
-        .collect::<Vec<_>>();
+    let bind_group_layout_id = validate_handle!(
+        descriptor.layout,
+        "invalid bind group layout for bind group descriptor"
+    )
+    .id;
+
+    let mut entries = Vec::with_capacity(descriptor.entryCount);
+    for entry in make_slice(descriptor.entries, descriptor.entryCount) {
+        match follow_chain!(map_bind_group_entry((entry),
+            WGPUSType_BindGroupEntryExtras => native::WGPUBindGroupEntryExtras)
+        ) {
+            Some(entry) => entries.push(entry),
+            None => return std::ptr::null(),
+        }
+    }
 
     let desc = wgc::binding_model::BindGroupDescriptor {
         label: ptr_into_label(descriptor.label),
@@ -1811,13 +2326,21 @@ pub unsafe extern "C" fn wgpuDeviceCreateBindGroup(
     let (bind_group_id, error) =
         gfx_select!(device_id => context.device_create_bind_group(device_id, &desc, None));
     if let Some(cause) = error {
-        handle_error(error_sink, cause, desc.label, "wgpuDeviceCreateBindGroup");
+        handle_error(
+            error_sink,
+            cause,
+            desc.label.clone(),
+            "wgpuDeviceCreateBindGroup",
+        );
     }
 
-    Arc::into_raw(Arc::new(WGPUBindGroupImpl {
-        context: context.clone(),
-        id: bind_group_id,
-    }))
+    handles::into_raw(
+        Arc::new(WGPUBindGroupImpl {
+            context: context.clone(),
+            id: bind_group_id,
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -1826,7 +2349,7 @@ pub unsafe extern "C" fn wgpuDeviceCreateBindGroupLayout(
     descriptor: Option<&native::WGPUBindGroupLayoutDescriptor>,
 ) -> native::WGPUBindGroupLayout {
     let (device_id, context, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context, &device.error_sink)
     };
     let descriptor = descriptor.expect("invalid descriptor");
@@ -1850,15 +2373,18 @@ pub unsafe extern "C" fn wgpuDeviceCreateBindGroupLayout(
         handle_error(
             error_sink,
             cause,
-            desc.label,
+            desc.label.clone(),
             "wgpuDeviceCreateBindGroupLayout",
         );
     }
 
-    Arc::into_raw(Arc::new(WGPUBindGroupLayoutImpl {
-        context: context.clone(),
-        id: bind_group_layout_id,
-    }))
+    handles::into_raw(
+        Arc::new(WGPUBindGroupLayoutImpl {
+            context: context.clone(),
+            id: bind_group_layout_id,
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -1867,7 +2393,7 @@ pub unsafe extern "C" fn wgpuDeviceCreateBuffer(
     descriptor: Option<&native::WGPUBufferDescriptor>,
 ) -> native::WGPUBuffer {
     let (device_id, context, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context, &device.error_sink)
     };
     let descriptor = descriptor.expect("invalid descriptor");
@@ -1882,18 +2408,26 @@ pub unsafe extern "C" fn wgpuDeviceCreateBuffer(
     let (buffer_id, error) =
         gfx_select!(device_id => context.device_create_buffer(device_id, &desc, None));
     if let Some(cause) = error {
-        handle_error(error_sink, cause, desc.label, "wgpuDeviceCreateBuffer");
+        handle_error(
+            error_sink,
+            cause,
+            desc.label.clone(),
+            "wgpuDeviceCreateBuffer",
+        );
     }
 
-    Arc::into_raw(Arc::new(WGPUBufferImpl {
-        context: context.clone(),
-        id: buffer_id,
-        error_sink: error_sink.clone(),
-        data: BufferData {
-            usage: descriptor.usage,
-            size: descriptor.size,
-        },
-    }))
+    handles::into_raw(
+        Arc::new(WGPUBufferImpl {
+            context: context.clone(),
+            id: buffer_id,
+            error_sink: error_sink.clone(),
+            data: BufferData {
+                usage: descriptor.usage,
+                size: descriptor.size,
+            },
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -1901,9 +2435,14 @@ pub unsafe extern "C" fn wgpuDeviceCreateCommandEncoder(
     device: native::WGPUDevice,
     descriptor: Option<&native::WGPUCommandEncoderDescriptor>,
 ) -> native::WGPUCommandEncoder {
-    let (device_id, context, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
-        (device.id, &device.context, &device.error_sink)
+    let (device_id, context, error_sink, mipmap_pipelines) = {
+        let device = validate_handle!(device, "invalid device");
+        (
+            device.id,
+            &device.context,
+            &device.error_sink,
+            &device.mipmap_pipelines,
+        )
     };
     let desc = match descriptor {
         Some(descriptor) => wgt::CommandEncoderDescriptor {
@@ -1917,17 +2456,22 @@ pub unsafe extern "C" fn wgpuDeviceCreateCommandEncoder(
         handle_error(
             error_sink,
             cause,
-            desc.label,
+            desc.label.clone(),
             "wgpuDeviceCreateCommandEncoder",
         );
     }
 
-    Arc::into_raw(Arc::new(WGPUCommandEncoderImpl {
-        context: context.clone(),
-        id: command_encoder_id,
-        error_sink: error_sink.clone(),
-        open: atomic::AtomicBool::new(true),
-    }))
+    handles::into_raw(
+        Arc::new(WGPUCommandEncoderImpl {
+            context: context.clone(),
+            id: command_encoder_id,
+            error_sink: error_sink.clone(),
+            open: atomic::AtomicBool::new(true),
+            mipmap_pipelines: mipmap_pipelines.clone(),
+            used_query_sets: Default::default(),
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -1936,37 +2480,52 @@ pub unsafe extern "C" fn wgpuDeviceCreateComputePipeline(
     descriptor: Option<&native::WGPUComputePipelineDescriptor>,
 ) -> native::WGPUComputePipeline {
     let (device_id, context, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context, &device.error_sink)
     };
     let descriptor = descriptor.expect("invalid descriptor");
+    let module = validate_handle!(
+        descriptor.compute.module,
+        "invalid compute shader module for compute pipeline descriptor"
+    );
+
+    let constants = match conv::map_pipeline_constants(
+        naga::ShaderStage::Compute,
+        module.overrides.as_ref(),
+        make_slice(
+            descriptor.compute.constants,
+            descriptor.compute.constantCount,
+        ),
+    ) {
+        Ok(constants) => constants,
+        Err(cause) => {
+            let label = ptr_into_label(descriptor.label);
+            handle_error(
+                error_sink,
+                cause,
+                label.clone(),
+                "wgpuDeviceCreateComputePipeline",
+            );
+            return handles::into_raw(
+                Arc::new(WGPUComputePipelineImpl {
+                    context: context.clone(),
+                    id: None,
+                    error_sink: error_sink.clone(),
+                }),
+                label.as_deref(),
+            );
+        }
+    };
 
     let desc = wgc::pipeline::ComputePipelineDescriptor {
         label: ptr_into_label(descriptor.label),
         layout: descriptor.layout.as_ref().map(|v| v.id),
         stage: wgc::pipeline::ProgrammableStageDescriptor {
-            module: descriptor
-                .compute
-                .module
-                .as_ref()
-                .expect("invalid fragment shader module for render pipeline descriptor")
+            module: module
                 .id
-                .expect("invalid fragment shader module for render pipeline descriptor"),
+                .expect("invalid compute shader module for compute pipeline descriptor"),
             entry_point: ptr_into_label(descriptor.compute.entryPoint),
-            constants: Cow::Owned(
-                make_slice(
-                    descriptor.compute.constants,
-                    descriptor.compute.constantCount,
-                )
-                .iter()
-                .map(|entry| {
-                    (
-                        CStr::from_ptr(entry.key).to_str().unwrap().to_string(),
-                        entry.value,
-                    )
-                })
-                .collect(),
-            ),
+            constants: Cow::Owned(constants),
             // TODO(wgpu.h)
             zero_initialize_workgroup_memory: false,
             // TODO(wgpu.h)
@@ -1994,16 +2553,19 @@ pub unsafe extern "C" fn wgpuDeviceCreateComputePipeline(
         handle_error(
             error_sink,
             cause,
-            desc.label,
+            desc.label.clone(),
             "wgpuDeviceCreateComputePipeline",
         );
     }
 
-    Arc::into_raw(Arc::new(WGPUComputePipelineImpl {
-        context: context.clone(),
-        id: compute_pipeline_id,
-        error_sink: error_sink.clone(),
-    }))
+    handles::into_raw(
+        Arc::new(WGPUComputePipelineImpl {
+            context: context.clone(),
+            id: Some(compute_pipeline_id),
+            error_sink: error_sink.clone(),
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -2012,31 +2574,38 @@ pub unsafe extern "C" fn wgpuDeviceCreatePipelineLayout(
     descriptor: Option<&native::WGPUPipelineLayoutDescriptor>,
 ) -> native::WGPUPipelineLayout {
     let (device_id, context, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context, &device.error_sink)
     };
     let descriptor = descriptor.expect("invalid descriptor");
 
-    let desc = follow_chain!(
+    let desc = match follow_chain!(
         map_pipeline_layout_descriptor(
             (descriptor),
             WGPUSType_PipelineLayoutExtras => native::WGPUPipelineLayoutExtras)
-    );
+    ) {
+        Some(desc) => desc,
+        None => return std::ptr::null(),
+    };
     let (pipeline_layout_id, error) =
         gfx_select!(device_id => context.device_create_pipeline_layout(device_id, &desc, None));
     if let Some(cause) = error {
         handle_error(
             error_sink,
             cause,
-            desc.label,
+            desc.label.clone(),
             "wgpuDeviceCreatePipelineLayout",
         );
     }
 
-    Arc::into_raw(Arc::new(WGPUPipelineLayoutImpl {
-        context: context.clone(),
-        id: pipeline_layout_id,
-    }))
+    handles::into_raw(
+        Arc::new(WGPUPipelineLayoutImpl {
+            context: context.clone(),
+            id: pipeline_layout_id,
+            bind_group_layout_count: descriptor.bindGroupLayoutCount,
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -2045,31 +2614,66 @@ pub unsafe extern "C" fn wgpuDeviceCreateQuerySet(
     descriptor: Option<&native::WGPUQuerySetDescriptor>,
 ) -> native::WGPUQuerySet {
     let (device_id, context, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context, &device.error_sink)
     };
     let descriptor = descriptor.expect("invalid query set descriptor");
 
-    let desc = follow_chain!(
+    let desc = match follow_chain!(
         map_query_set_descriptor(
             (descriptor),
             WGPUSType_QuerySetDescriptorExtras => native::WGPUQuerySetDescriptorExtras)
-    );
+    ) {
+        Ok(desc) => desc,
+        Err(cause) => {
+            let label = ptr_into_label(descriptor.label);
+            handle_error(error_sink, cause, label.clone(), "wgpuDeviceCreateQuerySet");
+
+            return handles::into_raw(
+                Arc::new(WGPUQuerySetImpl {
+                    context: context.clone(),
+                    id: Mutex::new(None),
+                    data: QuerySetData {
+                        query_type: descriptor.type_,
+                        query_count: descriptor.count,
+                        pipeline_statistics: Vec::new(),
+                    },
+                    destroyed: Arc::new(atomic::AtomicBool::new(false)),
+                }),
+                label.as_deref(),
+            );
+        }
+    };
 
     let (query_set_id, error) =
         gfx_select!(device_id => context.device_create_query_set(device_id, &desc, None));
     if let Some(cause) = error {
-        handle_error(error_sink, cause, desc.label, "wgpuDeviceCreateQuerySet");
+        handle_error(
+            error_sink,
+            cause,
+            desc.label.clone(),
+            "wgpuDeviceCreateQuerySet",
+        );
     }
 
-    Arc::into_raw(Arc::new(WGPUQuerySetImpl {
-        context: context.clone(),
-        id: query_set_id,
-        data: QuerySetData {
-            query_type: descriptor.type_,
-            query_count: descriptor.count,
-        },
-    }))
+    handles::into_raw(
+        Arc::new(WGPUQuerySetImpl {
+            context: context.clone(),
+            id: Mutex::new(Some(query_set_id)),
+            data: QuerySetData {
+                query_type: descriptor.type_,
+                query_count: descriptor.count,
+                pipeline_statistics: match desc.ty {
+                    wgt::QueryType::PipelineStatistics(types) => {
+                        conv::map_pipeline_statistics_layout(types)
+                    }
+                    _ => Vec::new(),
+                },
+            },
+            destroyed: Arc::new(atomic::AtomicBool::new(false)),
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -2078,7 +2682,7 @@ pub unsafe extern "C" fn wgpuDeviceCreateRenderBundleEncoder(
     descriptor: Option<&native::WGPURenderBundleEncoderDescriptor>,
 ) -> native::WGPURenderBundleEncoder {
     let (device_id, context) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context)
     };
     let descriptor = descriptor.expect("invalid descriptor");
@@ -2101,10 +2705,13 @@ pub unsafe extern "C" fn wgpuDeviceCreateRenderBundleEncoder(
     };
 
     match wgc::command::RenderBundleEncoder::new(&desc, device_id, None) {
-        Ok(encoder) => Arc::into_raw(Arc::new(WGPURenderBundleEncoderImpl {
-            context: context.clone(),
-            encoder: Box::into_raw(Box::new(Some(Box::into_raw(Box::new(encoder))))),
-        })),
+        Ok(encoder) => handles::into_raw(
+            Arc::new(WGPURenderBundleEncoderImpl {
+                context: context.clone(),
+                encoder: Box::into_raw(Box::new(Some(Box::into_raw(Box::new(encoder))))),
+            }),
+            desc.label.as_deref(),
+        ),
         Err(cause) => {
             handle_error_fatal(cause, "wgpuDeviceCreateRenderBundleEncoder");
         }
@@ -2116,36 +2723,91 @@ pub unsafe extern "C" fn wgpuDeviceCreateRenderPipeline(
     device: native::WGPUDevice,
     descriptor: Option<&native::WGPURenderPipelineDescriptor>,
 ) -> native::WGPURenderPipeline {
-    let (device_id, context, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
-        (device.id, &device.context, &device.error_sink)
-    };
+    let device = validate_handle!(device, "invalid device");
+    let (device_id, context, error_sink) = (device.id, &device.context, &device.error_sink);
     let descriptor = descriptor.expect("invalid descriptor");
+    let vertex_module = validate_handle!(
+        descriptor.vertex.module,
+        "invalid vertex shader module for vertex state"
+    );
+    // Not a closure, validate_handle! may return early.
+    #[allow(clippy::manual_map)]
+    let fragment_module = match descriptor.fragment.as_ref() {
+        Some(fragment) => Some(validate_handle!(
+            fragment.module,
+            "invalid fragment shader module for render pipeline descriptor"
+        )),
+        None => None,
+    };
+
+    let vertex_constants = conv::map_pipeline_constants(
+        naga::ShaderStage::Vertex,
+        vertex_module.overrides.as_ref(),
+        make_slice(descriptor.vertex.constants, descriptor.vertex.constantCount),
+    );
+    let fragment_constants =
+        descriptor
+            .fragment
+            .as_ref()
+            .zip(fragment_module)
+            .map(|(fragment, module)| {
+                conv::map_pipeline_constants(
+                    naga::ShaderStage::Fragment,
+                    module.overrides.as_ref(),
+                    make_slice(fragment.constants, fragment.constantCount),
+                )
+            });
+    let label = ptr_into_label(descriptor.label);
+    let invalid_pipeline = || {
+        handles::into_raw(
+            Arc::new(WGPURenderPipelineImpl {
+                context: context.clone(),
+                id: None,
+                error_sink: error_sink.clone(),
+            }),
+            label.as_deref(),
+        )
+    };
+    let (vertex_constants, fragment_constants) =
+        match (vertex_constants, fragment_constants.transpose()) {
+            (Ok(vertex), Ok(fragment)) => (vertex, fragment.unwrap_or_default()),
+            (Err(cause), _) | (_, Err(cause)) => {
+                handle_error(
+                    error_sink,
+                    cause,
+                    label.clone(),
+                    "wgpuDeviceCreateRenderPipeline",
+                );
+                return invalid_pipeline();
+            }
+        };
+    // Automatic layouts are checked once the pipeline is created.
+    if let Some(layout) = descriptor.layout.as_ref() {
+        if let Err(cause) = BindGroupsPlusVertexBuffersError::check(
+            layout.bind_group_layout_count,
+            descriptor.vertex.bufferCount,
+            device.max_bind_groups_plus_vertex_buffers,
+        ) {
+            handle_error(
+                error_sink,
+                cause,
+                label.clone(),
+                "wgpuDeviceCreateRenderPipeline",
+            );
+            return invalid_pipeline();
+        }
+    }
 
     let desc = wgc::pipeline::RenderPipelineDescriptor {
-        label: ptr_into_label(descriptor.label),
+        label: label.clone(),
         layout: descriptor.layout.as_ref().map(|v| v.id),
         vertex: wgc::pipeline::VertexState {
             stage: wgc::pipeline::ProgrammableStageDescriptor {
-                module: descriptor
-                    .vertex
-                    .module
-                    .as_ref()
-                    .expect("invalid vertex shader module for vertex state")
+                module: vertex_module
                     .id
                     .expect("invalid vertex shader module for vertex state"),
                 entry_point: ptr_into_label(descriptor.vertex.entryPoint),
-                constants: Cow::Owned(
-                    make_slice(descriptor.vertex.constants, descriptor.vertex.constantCount)
-                        .iter()
-                        .map(|entry| {
-                            (
-                                CStr::from_ptr(entry.key).to_str().unwrap().to_string(),
-                                entry.value,
-                            )
-                        })
-                        .collect(),
-                ),
+                constants: Cow::Owned(vertex_constants),
                 // TODO(wgpu.h)
                 zero_initialize_workgroup_memory: false,
                 // TODO(wgpu.h)
@@ -2228,26 +2890,14 @@ pub unsafe extern "C" fn wgpuDeviceCreateRenderPipeline(
         fragment: descriptor
             .fragment
             .as_ref()
-            .map(|fragment| wgc::pipeline::FragmentState {
+            .zip(fragment_module)
+            .map(|(fragment, module)| wgc::pipeline::FragmentState {
                 stage: wgc::pipeline::ProgrammableStageDescriptor {
-                    module: fragment
-                        .module
-                        .as_ref()
-                        .expect("invalid fragment shader module for render pipeline descriptor")
+                    module: module
                         .id
                         .expect("invalid fragment shader module for render pipeline descriptor"),
                     entry_point: ptr_into_label(fragment.entryPoint),
-                    constants: Cow::Owned(
-                        make_slice(fragment.constants, fragment.constantCount)
-                            .iter()
-                            .map(|entry| {
-                                (
-                                    CStr::from_ptr(entry.key).to_str().unwrap().to_string(),
-                                    entry.value,
-                                )
-                            })
-                            .collect(),
-                    ),
+                    constants: Cow::Owned(fragment_constants),
                     // TODO(wgpu.h)
                     zero_initialize_workgroup_memory: false,
                     // TODO(wgpu.h)
@@ -2289,16 +2939,51 @@ pub unsafe extern "C" fn wgpuDeviceCreateRenderPipeline(
         handle_error(
             error_sink,
             cause,
-            desc.label,
+            desc.label.clone(),
             "wgpuDeviceCreateRenderPipeline",
         );
+    } else if descriptor.layout.is_null() {
+        if let Err(cause) = BindGroupsPlusVertexBuffersError::check(
+            render_pipeline_bind_group_count(context, render_pipeline_id),
+            descriptor.vertex.bufferCount,
+            device.max_bind_groups_plus_vertex_buffers,
+        ) {
+            gfx_select!(render_pipeline_id => context.render_pipeline_drop(render_pipeline_id));
+            handle_error(
+                error_sink,
+                cause,
+                desc.label.clone(),
+                "wgpuDeviceCreateRenderPipeline",
+            );
+            return invalid_pipeline();
+        }
     }
 
-    Arc::into_raw(Arc::new(WGPURenderPipelineImpl {
-        context: context.clone(),
-        id: render_pipeline_id,
-        error_sink: error_sink.clone(),
-    }))
+    handles::into_raw(
+        Arc::new(WGPURenderPipelineImpl {
+            context: context.clone(),
+            id: Some(render_pipeline_id),
+            error_sink: error_sink.clone(),
+        }),
+        desc.label.as_deref(),
+    )
+}
+
+/// Number of bind groups in the layout of a render pipeline, counting the
+/// empty ones before the last used group like wgpu-core does.
+fn render_pipeline_bind_group_count(
+    context: &Context,
+    render_pipeline_id: id::RenderPipelineId,
+) -> usize {
+    let mut count = 0;
+    loop {
+        let (bind_group_layout_id, error) = gfx_select!(render_pipeline_id => context.render_pipeline_get_bind_group_layout(render_pipeline_id, count as u32, None));
+        gfx_select!(bind_group_layout_id => context.bind_group_layout_drop(bind_group_layout_id));
+        if error.is_some() {
+            return count;
+        }
+        count += 1;
+    }
 }
 
 #[no_mangle]
@@ -2307,7 +2992,7 @@ pub unsafe extern "C" fn wgpuDeviceCreateSampler(
     descriptor: Option<&native::WGPUSamplerDescriptor>,
 ) -> native::WGPUSampler {
     let (device_id, context, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context, &device.error_sink)
     };
 
@@ -2353,13 +3038,21 @@ pub unsafe extern "C" fn wgpuDeviceCreateSampler(
     let (sampler_id, error) =
         gfx_select!(device_id => context.device_create_sampler(device_id, &desc, None));
     if let Some(cause) = error {
-        handle_error(error_sink, cause, desc.label, "wgpuDeviceCreateSampler");
+        handle_error(
+            error_sink,
+            cause,
+            desc.label.clone(),
+            "wgpuDeviceCreateSampler",
+        );
     }
 
-    Arc::into_raw(Arc::new(WGPUSamplerImpl {
-        context: context.clone(),
-        id: sampler_id,
-    }))
+    handles::into_raw(
+        Arc::new(WGPUSamplerImpl {
+            context: context.clone(),
+            id: sampler_id,
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -2368,7 +3061,7 @@ pub unsafe extern "C" fn wgpuDeviceCreateShaderModule(
     descriptor: Option<&native::WGPUShaderModuleDescriptor>,
 ) -> native::WGPUShaderModule {
     let (device_id, context, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context, &device.error_sink)
     };
     let descriptor = descriptor.expect("invalid descriptor");
@@ -2378,42 +3071,125 @@ pub unsafe extern "C" fn wgpuDeviceCreateShaderModule(
         shader_bound_checks: wgt::ShaderBoundChecks::default(),
     };
 
-    let source = match follow_chain!(
+    // With SPIRV_SHADER_PASSTHROUGH, SPIR-V is handed to the backend as-is
+    // instead of being parsed and validated by naga. The naga SPIR-V options
+    // don't apply then, and there is no naga module to reflect or translate.
+    let passthrough = match gfx_select!(device_id => context.device_features(device_id)) {
+        Ok(features) => features.contains(wgt::Features::SPIRV_SHADER_PASSTHROUGH),
+        Err(err) => {
+            handle_error(
+                error_sink,
+                err,
+                desc.label.clone(),
+                "wgpuDeviceCreateShaderModule",
+            );
+            false
+        }
+    };
+    if passthrough {
+        if let Some(source) = follow_chain!(
+            map_shader_module_spirv_passthrough((descriptor),
+            WGPUSType_ShaderModuleSPIRVDescriptor => native::WGPUShaderModuleSPIRVDescriptor)
+        ) {
+            let (shader_module_id, error) = gfx_select!(device_id => context.device_create_shader_module_spirv(device_id, &desc, source, None));
+            let mut compilation_messages = Vec::new();
+            if let Some(cause) = error {
+                compilation_messages = shader::compilation_messages(&cause);
+                handle_error(
+                    error_sink,
+                    cause,
+                    desc.label.clone(),
+                    "wgpuDeviceCreateShaderModule",
+                );
+            }
+
+            return handles::into_raw(
+                Arc::new(WGPUShaderModuleImpl {
+                    context: context.clone(),
+                    id: Some(shader_module_id),
+                    #[cfg(feature = "shader_inspection")]
+                    source: None,
+                    overrides: None,
+                    compilation_messages,
+                }),
+                desc.label.as_deref(),
+            );
+        }
+    }
+
+    let (source, line_map) = match follow_chain!(
         map_shader_module((descriptor),
         WGPUSType_ShaderModuleSPIRVDescriptor => native::WGPUShaderModuleSPIRVDescriptor,
         WGPUSType_ShaderModuleWGSLDescriptor => native::WGPUShaderModuleWGSLDescriptor,
-        WGPUSType_ShaderModuleGLSLDescriptor => native::WGPUShaderModuleGLSLDescriptor)
+        WGPUSType_ShaderModuleGLSLDescriptor => native::WGPUShaderModuleGLSLDescriptor,
+        WGPUSType_ShaderModuleSPIRVOptions => native::WGPUShaderModuleSPIRVOptions,
+        WGPUSType_ShaderModuleWGSLPreprocessorOptions => native::WGPUShaderModuleWGSLPreprocessorOptions,
+        WGPUSType_ShaderModuleNagaIRDescriptor => native::WGPUShaderModuleNagaIRDescriptor)
     ) {
         Ok(source) => source,
         Err(cause) => {
+            let compilation_messages = cause.compilation_messages();
             handle_error(
                 error_sink,
                 cause,
-                desc.label,
+                desc.label.clone(),
                 "wgpuDeviceCreateShaderModule",
             );
 
-            return Arc::into_raw(Arc::new(WGPUShaderModuleImpl {
-                context: context.clone(),
-                id: None,
-            }));
+            return handles::into_raw(
+                Arc::new(WGPUShaderModuleImpl {
+                    context: context.clone(),
+                    id: None,
+                    #[cfg(feature = "shader_inspection")]
+                    source: None,
+                    overrides: None,
+                    compilation_messages,
+                }),
+                desc.label.as_deref(),
+            );
         }
     };
 
+    #[cfg(feature = "shader_inspection")]
+    let shader_source = shader::ShaderSource::from_wgc(&source);
+    let overrides = shader::source_overrides(&source);
     let (shader_module_id, error) = gfx_select!(device_id => context.device_create_shader_module(device_id, &desc, source, None));
+    let mut compilation_messages = Vec::new();
     if let Some(cause) = error {
-        handle_error(
-            error_sink,
-            cause,
-            desc.label,
-            "wgpuDeviceCreateShaderModule",
-        );
+        // Point preprocessed WGSL errors at the original files and lines.
+        match line_map.and_then(|line_map| line_map.remap_error(&cause)) {
+            Some((cause, message)) => {
+                compilation_messages.push(message);
+                handle_error(
+                    error_sink,
+                    cause,
+                    desc.label.clone(),
+                    "wgpuDeviceCreateShaderModule",
+                )
+            }
+            None => {
+                compilation_messages = shader::compilation_messages(&cause);
+                handle_error(
+                    error_sink,
+                    cause,
+                    desc.label.clone(),
+                    "wgpuDeviceCreateShaderModule",
+                )
+            }
+        }
     }
 
-    Arc::into_raw(Arc::new(WGPUShaderModuleImpl {
-        context: context.clone(),
-        id: Some(shader_module_id),
-    }))
+    handles::into_raw(
+        Arc::new(WGPUShaderModuleImpl {
+            context: context.clone(),
+            id: Some(shader_module_id),
+            #[cfg(feature = "shader_inspection")]
+            source: shader_source,
+            overrides,
+            compilation_messages,
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -2422,7 +3198,7 @@ pub unsafe extern "C" fn wgpuDeviceCreateTexture(
     descriptor: Option<&native::WGPUTextureDescriptor>,
 ) -> native::WGPUTexture {
     let (device_id, context, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context, &device.error_sink)
     };
     let descriptor = descriptor.expect("invalid descriptor");
@@ -2448,29 +3224,43 @@ pub unsafe extern "C" fn wgpuDeviceCreateTexture(
     let (texture_id, error) =
         gfx_select!(device_id => context.device_create_texture(device_id, &desc, None));
     if let Some(cause) = error {
-        handle_error(error_sink, cause, desc.label, "wgpuDeviceCreateTexture");
-    }
-
-    Arc::into_raw(Arc::new(WGPUTextureImpl {
-        context: context.clone(),
-        id: texture_id,
-        error_sink: error_sink.clone(),
-        surface_id: None,
-        has_surface_presented: Arc::default(),
-        data: TextureData {
-            usage: descriptor.usage,
-            dimension: descriptor.dimension,
-            size: descriptor.size,
-            format: descriptor.format,
-            mip_level_count: descriptor.mipLevelCount,
-            sample_count: descriptor.sampleCount,
-        },
-    }))
+        handle_error(
+            error_sink,
+            cause,
+            desc.label.clone(),
+            "wgpuDeviceCreateTexture",
+        );
+    }
+
+    handles::into_raw(
+        Arc::new(WGPUTextureImpl {
+            context: context.clone(),
+            id: texture_id,
+            error_sink: error_sink.clone(),
+            surface_id: None,
+            has_surface_presented: Arc::default(),
+            data: TextureData {
+                usage: descriptor.usage,
+                dimension: descriptor.dimension,
+                size: descriptor.size,
+                format: descriptor.format,
+                mip_level_count: descriptor.mipLevelCount,
+                sample_count: descriptor.sampleCount,
+            },
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
-pub extern "C" fn wgpuDeviceDestroy(_device: native::WGPUDevice) {
-    //TODO: needs to be implemented in wgpu-core
+pub unsafe extern "C" fn wgpuDeviceDestroy(device: native::WGPUDevice) {
+    let (device_id, context, error_sink) = {
+        let device = validate_handle!(device, "invalid device");
+        (device.id, &device.context, &device.error_sink)
+    };
+
+    error_sink.lock().destroyed = true;
+    gfx_select!(device_id => context.device_destroy(device_id));
 }
 
 #[no_mangle]
@@ -2479,7 +3269,7 @@ pub unsafe extern "C" fn wgpuDeviceEnumerateFeatures(
     features: *mut native::WGPUFeatureName,
 ) -> usize {
     let (device_id, context) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context)
     };
     let device_features = match gfx_select!(device_id => context.device_features(device_id)) {
@@ -2501,10 +3291,8 @@ pub unsafe extern "C" fn wgpuDeviceGetLimits(
     device: native::WGPUDevice,
     limits: Option<&mut native::WGPUSupportedLimits>,
 ) -> native::WGPUBool {
-    let (device_id, context) = {
-        let device = device.as_ref().expect("invalid device");
-        (device.id, &device.context)
-    };
+    let device = validate_handle!(device, "invalid device");
+    let (device_id, context) = (device.id, &device.context);
     let limits = limits.expect("invalid return pointer \"limits\"");
 
     let result = gfx_select!(device_id => context.device_limits(device_id));
@@ -2512,6 +3300,7 @@ pub unsafe extern "C" fn wgpuDeviceGetLimits(
         Ok(wgt_limits) => conv::write_limits_struct(wgt_limits, limits),
         Err(err) => handle_error_fatal(err, "wgpuDeviceGetLimits"),
     }
+    limits.limits.maxBindGroupsPlusVertexBuffers = device.max_bind_groups_plus_vertex_buffers;
 
     true as native::WGPUBool // indicates that we can fill WGPUChainedStructOut
 }
@@ -2519,14 +3308,17 @@ pub unsafe extern "C" fn wgpuDeviceGetLimits(
 #[no_mangle]
 pub unsafe extern "C" fn wgpuDeviceGetQueue(device: native::WGPUDevice) -> native::WGPUQueue {
     let (queue, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (&device.queue, &device.error_sink)
     };
 
-    Arc::into_raw(Arc::new(WGPUQueueImpl {
-        queue: queue.clone(),
-        error_sink: error_sink.clone(),
-    }))
+    handles::into_raw(
+        Arc::new(WGPUQueueImpl {
+            queue: queue.clone(),
+            error_sink: error_sink.clone(),
+        }),
+        None,
+    )
 }
 
 #[no_mangle]
@@ -2535,7 +3327,7 @@ pub unsafe extern "C" fn wgpuDeviceHasFeature(
     feature: native::WGPUFeatureName,
 ) -> native::WGPUBool {
     let (device_id, context) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context)
     };
     let device_features = match gfx_select!(device_id => context.device_features(device_id)) {
@@ -2557,7 +3349,7 @@ pub unsafe extern "C" fn wgpuDevicePopErrorScope(
     callback: native::WGPUErrorCallback,
     userdata: *mut ::std::os::raw::c_void,
 ) {
-    let device = device.as_ref().expect("invalid device");
+    let device = validate_handle!(device, "invalid device");
     let callback = callback.expect("invalid callback");
     let mut error_sink = device.error_sink.lock();
     let scope = error_sink.scopes.pop().unwrap();
@@ -2591,7 +3383,7 @@ pub unsafe extern "C" fn wgpuDevicePushErrorScope(
     device: native::WGPUDevice,
     filter: native::WGPUErrorFilter,
 ) {
-    let device = device.as_ref().expect("invalid device");
+    let device = validate_handle!(device, "invalid device");
     let mut error_sink = device.error_sink.lock();
     error_sink.scopes.push(ErrorScope {
         error: None,
@@ -2605,12 +3397,12 @@ pub unsafe extern "C" fn wgpuDevicePushErrorScope(
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuDeviceReference(device: native::WGPUDevice) {
-    assert!(!device.is_null(), "invalid device");
+    validate_handle!(device, "invalid device");
     Arc::increment_strong_count(device);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuDeviceRelease(device: native::WGPUDevice) {
-    assert!(!device.is_null(), "invalid device");
+    validate_handle!(device, "invalid device");
     Arc::decrement_strong_count(device);
 }
 
@@ -2621,7 +3413,7 @@ pub unsafe extern "C" fn wgpuInstanceCreateSurface(
     instance: native::WGPUInstance,
     descriptor: Option<&native::WGPUSurfaceDescriptor>,
 ) -> native::WGPUSurface {
-    let context = &instance.as_ref().expect("invalid instance").context;
+    let context = &validate_handle!(instance, "invalid instance").context;
     let descriptor = descriptor.expect("invalid descriptor");
 
     let create_surface_params = follow_chain!(
@@ -2650,12 +3442,15 @@ pub unsafe extern "C" fn wgpuInstanceCreateSurface(
         }
     };
 
-    Arc::into_raw(Arc::new(WGPUSurfaceImpl {
-        context: context.clone(),
-        id: surface_id,
-        data: Mutex::default(),
-        has_surface_presented: Arc::default(),
-    }))
+    handles::into_raw(
+        Arc::new(WGPUSurfaceImpl {
+            context: context.clone(),
+            id: surface_id,
+            data: Mutex::default(),
+            has_surface_presented: Arc::default(),
+        }),
+        ptr_into_label(descriptor.label).as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -2665,10 +3460,21 @@ pub unsafe extern "C" fn wgpuInstanceRequestAdapter(
     callback: native::WGPUInstanceRequestAdapterCallback,
     userdata: *mut std::os::raw::c_void,
 ) {
-    let instance = instance.as_ref().expect("invalid instance");
-    let context = &instance.context;
     let callback = callback.expect("invalid callback");
 
+    request_adapter(instance, options, |_, status, adapter, message| {
+        callback(status, adapter, message.as_ptr(), userdata)
+    });
+}
+
+unsafe fn request_adapter(
+    instance: native::WGPUInstance,
+    options: Option<&native::WGPURequestAdapterOptions>,
+    callback: impl FnOnce(&Context, native::WGPURequestAdapterStatus, native::WGPUAdapter, &CStr),
+) {
+    let instance = validate_handle!(instance, "invalid instance");
+    let context = &instance.context;
+
     let (desc, inputs) = match options {
         Some(options) => (
             wgt::RequestAdapterOptions {
@@ -2694,10 +3500,10 @@ pub unsafe extern "C" fn wgpuInstanceRequestAdapter(
                     native::WGPUBackendType_OpenGLES => wgt::Backends::GL,
                     native::WGPUBackendType_D3D11 => {
                         callback(
+                            context,
                             native::WGPURequestAdapterStatus_Error,
                             std::ptr::null_mut(),
-                            "unsupported backend type: d3d11".as_ptr() as _,
-                            userdata,
+                            c"unsupported backend type: d3d11",
                         );
                         return;
                     }
@@ -2716,18 +3522,22 @@ pub unsafe extern "C" fn wgpuInstanceRequestAdapter(
         Ok(adapter_id) => {
             let message = CString::default();
             callback(
+                context,
                 native::WGPURequestAdapterStatus_Success,
-                Arc::into_raw(Arc::new(WGPUAdapterImpl {
-                    context: context.clone(),
-                    id: adapter_id,
-                })),
-                message.as_ptr(),
-                userdata,
+                handles::into_raw(
+                    Arc::new(WGPUAdapterImpl {
+                        context: context.clone(),
+                        id: adapter_id,
+                    }),
+                    None,
+                ),
+                &message,
             );
         }
         Err(err) => {
             let message = CString::new(format_error(&err)).unwrap();
             callback(
+                context,
                 match err {
                     wgc::instance::RequestAdapterError::NotFound => {
                         native::WGPURequestAdapterStatus_Unavailable
@@ -2738,8 +3548,7 @@ pub unsafe extern "C" fn wgpuInstanceRequestAdapter(
                     _ => native::WGPURequestAdapterStatus_Unknown,
                 },
                 std::ptr::null_mut(),
-                message.as_ptr(),
-                userdata,
+                &message,
             );
         }
     };
@@ -2751,7 +3560,7 @@ pub unsafe extern "C" fn wgpuInstanceEnumerateAdapters(
     options: Option<&native::WGPUInstanceEnumerateAdapterOptions>,
     adapters: *mut native::WGPUAdapter,
 ) -> usize {
-    let instance = instance.as_ref().expect("invalid instance");
+    let instance = validate_handle!(instance, "invalid instance");
     let context = &instance.context;
 
     let inputs = match options {
@@ -2772,10 +3581,13 @@ pub unsafe extern "C" fn wgpuInstanceEnumerateAdapters(
             // It's users responsibility to drop the adapters they
             // don't need.
 
-            temp[i] = Arc::into_raw(Arc::new(WGPUAdapterImpl {
-                context: context.clone(),
-                id: *id,
-            }));
+            temp[i] = handles::into_raw(
+                Arc::new(WGPUAdapterImpl {
+                    context: context.clone(),
+                    id: *id,
+                }),
+                None,
+            );
         });
     } else {
         // Drop all the adapters when only counting length.
@@ -2788,59 +3600,102 @@ pub unsafe extern "C" fn wgpuInstanceEnumerateAdapters(
     count
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn wgpuInstanceProcessEvents(instance: native::WGPUInstance) {
+    let instance = validate_handle!(instance, "invalid instance");
+    let context = &instance.context;
+
+    if let Err(cause) = context.poll_all_devices(false) {
+        handle_error_fatal(cause, "wgpuInstanceProcessEvents");
+    }
+    for callback in context.callbacks.futures.take_process_events() {
+        callback();
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn wgpuInstanceReference(instance: native::WGPUInstance) {
-    assert!(!instance.is_null(), "invalid instance");
+    validate_handle!(instance, "invalid instance");
     Arc::increment_strong_count(instance);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuInstanceRelease(instance: native::WGPUInstance) {
-    assert!(!instance.is_null(), "invalid instance");
-    Arc::decrement_strong_count(instance);
+    validate_handle!(instance, "invalid instance");
+    let instance = Arc::from_raw(instance);
+
+    // Objects keep the context alive, so anything still tracked
+    // once the last instance reference goes away was never released.
+    if Arc::strong_count(&instance) == 1 {
+        if let Some(leak_detector) = &instance.context.leak_detector {
+            leak_detector.report();
+        }
+    }
 }
 
 // PipelineLayout methods
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuPipelineLayoutReference(pipeline_layout: native::WGPUPipelineLayout) {
-    assert!(!pipeline_layout.is_null(), "invalid pipeline layout");
+    validate_handle!(pipeline_layout, "invalid pipeline layout");
     Arc::increment_strong_count(pipeline_layout);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuPipelineLayoutRelease(pipeline_layout: native::WGPUPipelineLayout) {
-    assert!(!pipeline_layout.is_null(), "invalid pipeline layout");
+    validate_handle!(pipeline_layout, "invalid pipeline layout");
     Arc::decrement_strong_count(pipeline_layout);
 }
 
 // QuerySet methods
 
 #[no_mangle]
-pub unsafe extern "C" fn wgpuQuerySetDestroy(_query_set: native::WGPUQuerySet) {
-    //TODO: needs to be implemented in wgpu-core
+pub unsafe extern "C" fn wgpuQuerySetDestroy(query_set: native::WGPUQuerySet) {
+    let query_set = validate_handle!(query_set, "invalid query set");
+
+    // Commands recorded with the query set keep it alive in wgpu-core, so
+    // their submission is failed with the flag instead.
+    query_set.destroyed.store(true, atomic::Ordering::SeqCst);
+    if let Some(id) = query_set.id.lock().take() {
+        let context = &query_set.context;
+        gfx_select!(id => context.query_set_drop(id));
+    }
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuQuerySetGetCount(query_set: native::WGPUQuerySet) -> u32 {
-    let query_set = query_set.as_ref().expect("invalid query set");
+    let query_set = validate_handle!(query_set, "invalid query set");
     query_set.data.query_count
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn wgpuQuerySetGetPipelineStatistics(
+    query_set: native::WGPUQuerySet,
+    statistics: *mut native::WGPUPipelineStatisticName,
+) -> usize {
+    let query_set = validate_handle!(query_set, "invalid query set");
+    let layout = &query_set.data.pipeline_statistics;
+
+    if !statistics.is_null() {
+        std::slice::from_raw_parts_mut(statistics, layout.len()).copy_from_slice(layout);
+    }
+    layout.len()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn wgpuQuerySetGetType(
     query_set: native::WGPUQuerySet,
 ) -> native::WGPUQueryType {
-    let query_set = query_set.as_ref().expect("invalid query set");
+    let query_set = validate_handle!(query_set, "invalid query set");
     query_set.data.query_type
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuQuerySetReference(query_set: native::WGPUQuerySet) {
-    assert!(!query_set.is_null(), "invalid query set");
+    validate_handle!(query_set, "invalid query set");
     Arc::increment_strong_count(query_set);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuQuerySetRelease(query_set: native::WGPUQuerySet) {
-    assert!(!query_set.is_null(), "invalid query set");
+    validate_handle!(query_set, "invalid query set");
     Arc::decrement_strong_count(query_set);
 }
 
@@ -2851,16 +3706,24 @@ pub unsafe extern "C" fn wgpuQueueOnSubmittedWorkDone(
     queue: native::WGPUQueue,
     callback: native::WGPUQueueOnSubmittedWorkDoneCallback,
     userdata: *mut ::std::os::raw::c_void,
+) {
+    let callback = callback.expect("invalid callback");
+    let userdata = utils::Userdata::new(userdata);
+
+    queue_on_submitted_work_done(queue, move |status| callback(status, userdata.as_ptr()));
+}
+
+unsafe fn queue_on_submitted_work_done(
+    queue: native::WGPUQueue,
+    callback: impl FnOnce(native::WGPUQueueWorkDoneStatus) + Send + 'static,
 ) {
     let (queue_id, context) = {
-        let queue = queue.as_ref().expect("invalid queue");
+        let queue = validate_handle!(queue, "invalid queue");
         (queue.queue.id, &queue.queue.context)
     };
-    let callback = callback.expect("invalid callback");
-    let userdata = utils::Userdata::new(userdata);
 
     let closure = wgc::device::queue::SubmittedWorkDoneClosure::from_rust(Box::new(move || {
-        callback(native::WGPUQueueWorkDoneStatus_Success, userdata.as_ptr());
+        callback(native::WGPUQueueWorkDoneStatus_Success);
     }));
 
     if let Err(cause) =
@@ -2876,22 +3739,59 @@ pub unsafe extern "C" fn wgpuQueueSubmit(
     command_count: usize,
     commands: *const native::WGPUCommandBuffer,
 ) {
-    let (queue_id, context) = {
-        let queue = queue.as_ref().expect("invalid queue");
-        (queue.queue.id, &queue.queue.context)
+    queue_submit(queue, command_count, commands, "wgpuQueueSubmit");
+}
+
+/// Submits the command buffers, which are consumed even if the submission fails.
+/// A failed submission is reported to the device and followed by an empty one,
+/// so that there still is a submission index to wait on.
+unsafe fn queue_submit(
+    queue: native::WGPUQueue,
+    command_count: usize,
+    commands: *const native::WGPUCommandBuffer,
+    fn_ident: &'static str,
+) -> native::WGPUSubmissionIndex {
+    let (queue_id, context, error_sink) = {
+        let queue = validate_handle!(queue, "invalid queue");
+        (queue.queue.id, &queue.queue.context, &queue.error_sink)
     };
 
-    let command_buffers = make_slice(commands, command_count)
-        .iter()
-        .map(|command_buffer| {
-            let command_buffer = command_buffer.as_ref().expect("invalid command buffer");
-            command_buffer.open.store(false, atomic::Ordering::SeqCst);
-            command_buffer.id
-        })
-        .collect::<SmallVec<[_; 4]>>();
+    // Validated before any of them is consumed.
+    let mut command_buffer_impls = SmallVec::<[&WGPUCommandBufferImpl; 4]>::new();
+    for &command_buffer in make_slice(commands, command_count) {
+        command_buffer_impls.push(validate_handle!(command_buffer, "invalid command buffer"));
+    }
 
-    if let Err(cause) = gfx_select!(queue_id => context.queue_submit(queue_id, &command_buffers)) {
-        handle_error_fatal(cause, "wgpuQueueSubmit");
+    let mut uses_destroyed_query_set = false;
+    let mut command_buffers = SmallVec::<[_; 4]>::new();
+    for command_buffer in command_buffer_impls {
+        command_buffer.open.store(false, atomic::Ordering::SeqCst);
+        uses_destroyed_query_set |= command_buffer.uses_destroyed_query_set();
+        command_buffers.push(command_buffer.id);
+    }
+
+    let cause = match uses_destroyed_query_set {
+        true => None,
+        false => match gfx_select!(queue_id => context.queue_submit(queue_id, &command_buffers)) {
+            Ok(submission_index) => return submission_index.index,
+            Err(cause) => Some(cause),
+        },
+    };
+    // wgpu-core stops at the first invalid command buffer, leaving the rest registered.
+    for &command_buffer_id in &command_buffers {
+        gfx_select!(command_buffer_id => context.command_buffer_drop(command_buffer_id));
+    }
+    match cause {
+        Some(cause) => handle_error(error_sink, cause, None, fn_ident),
+        None => handle_error(error_sink, DestroyedQuerySetError, None, fn_ident),
+    }
+
+    match gfx_select!(queue_id => context.queue_submit(queue_id, &[])) {
+        Ok(submission_index) => submission_index.index,
+        Err(cause) => {
+            handle_error(error_sink, cause, None, fn_ident);
+            0
+        }
     }
 }
 
@@ -2900,14 +3800,14 @@ pub unsafe extern "C" fn wgpuQueueWriteBuffer(
     queue: native::WGPUQueue,
     buffer: native::WGPUBuffer,
     buffer_offset: u64,
-    data: *const u8, // TODO: Check - this might not follow the header
+    data: *const u8,
     data_size: usize,
 ) {
     let (queue_id, context, error_sink) = {
-        let queue = queue.as_ref().expect("invalid queue");
+        let queue = validate_handle!(queue, "invalid queue");
         (queue.queue.id, &queue.queue.context, &queue.error_sink)
     };
-    let buffer_id = buffer.as_ref().expect("invalid buffer").id;
+    let buffer_id = validate_handle!(buffer, "invalid buffer").id;
 
     if let Err(cause) = gfx_select!(queue_id => context.queue_write_buffer(
         queue_id,
@@ -2923,20 +3823,34 @@ pub unsafe extern "C" fn wgpuQueueWriteBuffer(
 pub unsafe extern "C" fn wgpuQueueWriteTexture(
     queue: native::WGPUQueue,
     destination: Option<&native::WGPUImageCopyTexture>,
-    data: *const u8, // TODO: Check - this might not follow the header
+    data: *const u8,
     data_size: usize,
     data_layout: Option<&native::WGPUTextureDataLayout>,
     write_size: Option<&native::WGPUExtent3D>,
 ) {
     let (queue_id, context, error_sink) = {
-        let queue = queue.as_ref().expect("invalid queue");
+        let queue = validate_handle!(queue, "invalid queue");
         (queue.queue.id, &queue.queue.context, &queue.error_sink)
     };
 
+    // A null pointer writes nothing, so wgpu-core reports the overrun if the
+    // layout needs any data.
+    let data = if data.is_null() {
+        &[]
+    } else {
+        make_slice(data, data_size)
+    };
+
+    let destination = match conv::map_image_copy_texture(destination.expect("invalid destination"))
+    {
+        Some(destination) => destination,
+        None => return,
+    };
+
     if let Err(cause) = gfx_select!(queue_id => context.queue_write_texture(
         queue_id,
-        &conv::map_image_copy_texture(destination.expect("invalid destination")),
-        make_slice(data, data_size),
+        &destination,
+        data,
         &conv::map_texture_data_layout(data_layout.expect("invalid data layout")),
         &conv::map_extent3d(write_size.expect("invalid write size"))
     )) {
@@ -2946,12 +3860,12 @@ pub unsafe extern "C" fn wgpuQueueWriteTexture(
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuQueueReference(queue: native::WGPUQueue) {
-    assert!(!queue.is_null(), "invalid queue");
+    validate_handle!(queue, "invalid queue");
     Arc::increment_strong_count(queue);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuQueueRelease(queue: native::WGPUQueue) {
-    assert!(!queue.is_null(), "invalid queue");
+    validate_handle!(queue, "invalid queue");
     Arc::decrement_strong_count(queue);
 }
 
@@ -2959,12 +3873,12 @@ pub unsafe extern "C" fn wgpuQueueRelease(queue: native::WGPUQueue) {
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuRenderBundleReference(render_bundle: native::WGPURenderBundle) {
-    assert!(!render_bundle.is_null(), "invalid render bundle");
+    validate_handle!(render_bundle, "invalid render bundle");
     Arc::increment_strong_count(render_bundle);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuRenderBundleRelease(render_bundle: native::WGPURenderBundle) {
-    assert!(!render_bundle.is_null(), "invalid render bundle");
+    validate_handle!(render_bundle, "invalid render bundle");
     Arc::decrement_strong_count(render_bundle);
 }
 
@@ -2978,7 +3892,7 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderDraw(
     first_vertex: u32,
     first_instance: u32,
 ) {
-    let bundle = bundle.as_ref().expect("invalid render bundle");
+    let bundle = validate_handle!(bundle, "invalid render bundle");
     let encoder = bundle.encoder.as_mut().expect("invalid render bundle");
     let encoder = encoder.expect("invalid render bundle");
     let encoder = encoder.as_mut().unwrap();
@@ -3001,7 +3915,7 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderDrawIndexed(
     base_vertex: i32,
     first_instance: u32,
 ) {
-    let bundle = bundle.as_ref().expect("invalid render bundle");
+    let bundle = validate_handle!(bundle, "invalid render bundle");
     let encoder = bundle.encoder.as_mut().expect("invalid render bundle");
     let encoder = encoder.expect("invalid render bundle");
     let encoder = encoder.as_mut().unwrap();
@@ -3022,11 +3936,8 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderDrawIndexedIndirect(
     indirect_buffer: native::WGPUBuffer,
     indirect_offset: u64,
 ) {
-    let bundle = bundle.as_ref().expect("invalid render bundle");
-    let indirect_buffer_id = indirect_buffer
-        .as_ref()
-        .expect("invalid indirect buffer")
-        .id;
+    let bundle = validate_handle!(bundle, "invalid render bundle");
+    let indirect_buffer_id = validate_handle!(indirect_buffer, "invalid indirect buffer").id;
     let encoder = bundle.encoder.as_mut().expect("invalid render bundle");
     let encoder = encoder.expect("invalid render bundle");
     let encoder = encoder.as_mut().unwrap();
@@ -3044,11 +3955,8 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderDrawIndirect(
     indirect_buffer: native::WGPUBuffer,
     indirect_offset: u64,
 ) {
-    let bundle = bundle.as_ref().expect("invalid render bundle");
-    let indirect_buffer_id = indirect_buffer
-        .as_ref()
-        .expect("invalid indirect buffer")
-        .id;
+    let bundle = validate_handle!(bundle, "invalid render bundle");
+    let indirect_buffer_id = validate_handle!(indirect_buffer, "invalid indirect buffer").id;
     let encoder = bundle.encoder.as_mut().expect("invalid render bundle");
     let encoder = encoder.expect("invalid render bundle");
     let encoder = encoder.as_mut().unwrap();
@@ -3061,7 +3969,7 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderFinish(
     bundle: native::WGPURenderBundleEncoder,
     descriptor: Option<&native::WGPURenderBundleDescriptor>,
 ) -> native::WGPURenderBundle {
-    let bundle = bundle.as_ref().expect("invalid render bundle");
+    let bundle = validate_handle!(bundle, "invalid render bundle");
     let context = &bundle.context;
     let encoder = bundle.encoder.as_mut().expect("invalid render bundle");
     let encoder = encoder.take().expect("invalid render bundle");
@@ -3079,10 +3987,13 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderFinish(
         handle_error_fatal(cause, "wgpuRenderBundleEncoderFinish");
     }
 
-    Arc::into_raw(Arc::new(WGPURenderBundleImpl {
-        context: context.clone(),
-        id: render_bundle_id,
-    }))
+    handles::into_raw(
+        Arc::new(WGPURenderBundleImpl {
+            context: context.clone(),
+            id: render_bundle_id,
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -3090,7 +4001,7 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderInsertDebugMarker(
     bundle: native::WGPURenderBundleEncoder,
     marker_label: *const std::ffi::c_char,
 ) {
-    let bundle = bundle.as_ref().expect("invalid render bundle");
+    let bundle = validate_handle!(bundle, "invalid render bundle");
     let encoder = bundle.encoder.as_mut().expect("invalid render bundle");
     let encoder = encoder.expect("invalid render bundle");
     let encoder = encoder.as_mut().unwrap();
@@ -3102,7 +4013,7 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderInsertDebugMarker(
 pub unsafe extern "C" fn wgpuRenderBundleEncoderPopDebugGroup(
     bundle: native::WGPURenderBundleEncoder,
 ) {
-    let bundle = bundle.as_ref().expect("invalid render bundle");
+    let bundle = validate_handle!(bundle, "invalid render bundle");
     let encoder = bundle.encoder.as_mut().expect("invalid render bundle");
     let encoder = encoder.expect("invalid render bundle");
     let encoder = encoder.as_mut().unwrap();
@@ -3115,7 +4026,7 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderPushDebugGroup(
     bundle: native::WGPURenderBundleEncoder,
     group_label: *const std::ffi::c_char,
 ) {
-    let bundle = bundle.as_ref().expect("invalid render bundle");
+    let bundle = validate_handle!(bundle, "invalid render bundle");
     let encoder = bundle.encoder.as_mut().expect("invalid render bundle");
     let encoder = encoder.expect("invalid render bundle");
     let encoder = encoder.as_mut().unwrap();
@@ -3131,9 +4042,9 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderSetBindGroup(
     dynamic_offset_count: usize,
     dynamic_offsets: *const u32,
 ) {
-    let bundle = bundle.as_ref().expect("invalid render bundle");
+    let bundle = validate_handle!(bundle, "invalid render bundle");
     // TODO: as per webgpu.h bindgroup is nullable
-    let bind_group_id = group.as_ref().expect("invalid bind group").id;
+    let bind_group_id = validate_handle!(group, "invalid bind group").id;
     let encoder = bundle.encoder.as_mut().expect("invalid render bundle");
     let encoder = encoder.expect("invalid render bundle");
     let encoder = encoder.as_mut().unwrap();
@@ -3155,8 +4066,8 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderSetIndexBuffer(
     offset: u64,
     size: u64,
 ) {
-    let bundle = bundle.as_ref().expect("invalid render bundle");
-    let buffer_id = buffer.as_ref().expect("invalid buffer").id;
+    let bundle = validate_handle!(bundle, "invalid render bundle");
+    let buffer_id = validate_handle!(buffer, "invalid buffer").id;
     let encoder = bundle.encoder.as_mut().expect("invalid render bundle");
     let encoder = encoder.expect("invalid render bundle");
     let encoder = encoder.as_mut().unwrap();
@@ -3179,8 +4090,12 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderSetPipeline(
     bundle: native::WGPURenderBundleEncoder,
     pipeline: native::WGPURenderPipeline,
 ) {
-    let bundle = bundle.as_ref().expect("invalid render bundle");
-    let pipeline_id = pipeline.as_ref().expect("invalid render pipeline").id;
+    let bundle = validate_handle!(bundle, "invalid render bundle");
+    let pipeline_id = match validate_handle!(pipeline, "invalid render pipeline").id {
+        Some(id) => id,
+        // Render bundle errors are fatal, see wgpuRenderBundleEncoderFinish.
+        None => handle_error_fatal(InvalidPipelineError, "wgpuRenderBundleEncoderSetPipeline"),
+    };
     let encoder = bundle.encoder.as_mut().expect("invalid render bundle");
     let encoder = encoder.expect("invalid render bundle");
     let encoder = encoder.as_mut().unwrap();
@@ -3196,9 +4111,9 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderSetVertexBuffer(
     offset: u64,
     size: u64,
 ) {
-    let bundle = bundle.as_ref().expect("invalid render bundle");
+    let bundle = validate_handle!(bundle, "invalid render bundle");
     // TODO: as per webgpu.h buffer is nullable
-    let buffer_id = buffer.as_ref().expect("invalid buffer").id;
+    let buffer_id = validate_handle!(buffer, "invalid buffer").id;
     let encoder = bundle.encoder.as_mut().expect("invalid render bundle");
     let encoder = encoder.expect("invalid render bundle");
     let encoder = encoder.as_mut().unwrap();
@@ -3220,20 +4135,14 @@ pub unsafe extern "C" fn wgpuRenderBundleEncoderSetVertexBuffer(
 pub unsafe extern "C" fn wgpuRenderBundleEncoderReference(
     render_bundle_encoder: native::WGPURenderBundleEncoder,
 ) {
-    assert!(
-        !render_bundle_encoder.is_null(),
-        "invalid render bundle encoder"
-    );
+    validate_handle!(render_bundle_encoder, "invalid render bundle encoder");
     Arc::increment_strong_count(render_bundle_encoder);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuRenderBundleEncoderRelease(
     render_bundle_encoder: native::WGPURenderBundleEncoder,
 ) {
-    assert!(
-        !render_bundle_encoder.is_null(),
-        "invalid render bundle encoder"
-    );
+    validate_handle!(render_bundle_encoder, "invalid render bundle encoder");
     Arc::decrement_strong_count(render_bundle_encoder);
 }
 
@@ -3244,7 +4153,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderBeginOcclusionQuery(
     pass: native::WGPURenderPassEncoder,
     query_index: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.begin_occlusion_query(&pass.context, query_index) {
@@ -3266,7 +4175,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderDraw(
     first_vertex: u32,
     first_instance: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.draw(
@@ -3290,7 +4199,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderDrawIndexed(
     base_vertex: i32,
     first_instance: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.draw_indexed(
@@ -3317,11 +4226,8 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderDrawIndexedIndirect(
     indirect_buffer: native::WGPUBuffer,
     indirect_offset: u64,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
-    let indirect_buffer_id = indirect_buffer
-        .as_ref()
-        .expect("invalid indirect buffer")
-        .id;
+    let pass = validate_handle!(pass, "invalid render pass");
+    let indirect_buffer_id = validate_handle!(indirect_buffer, "invalid indirect buffer").id;
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.draw_indexed_indirect(&pass.context, indirect_buffer_id, indirect_offset) {
@@ -3341,11 +4247,8 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderDrawIndirect(
     indirect_buffer: native::WGPUBuffer,
     indirect_offset: u64,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
-    let indirect_buffer_id = indirect_buffer
-        .as_ref()
-        .expect("invalid indirect buffer")
-        .id;
+    let pass = validate_handle!(pass, "invalid render pass");
+    let indirect_buffer_id = validate_handle!(indirect_buffer, "invalid indirect buffer").id;
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.draw_indirect(&pass.context, indirect_buffer_id, indirect_offset) {
@@ -3361,7 +4264,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderDrawIndirect(
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuRenderPassEncoderEnd(pass: native::WGPURenderPassEncoder) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.end(&pass.context) {
@@ -3374,7 +4277,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderEnd(pass: native::WGPURenderPassEn
 pub unsafe extern "C" fn wgpuRenderPassEncoderEndOcclusionQuery(
     pass: native::WGPURenderPassEncoder,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.end_occlusion_query(&pass.context) {
@@ -3394,11 +4297,11 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderExecuteBundles(
     bundle_count: usize,
     bundles: *const native::WGPURenderBundle,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
-    let bundle_ids = make_slice(bundles, bundle_count)
-        .iter()
-        .map(|v| v.as_ref().expect("invalid render bundle").id)
-        .collect::<SmallVec<[_; 4]>>();
+    let pass = validate_handle!(pass, "invalid render pass");
+    let mut bundle_ids = SmallVec::<[_; 4]>::new();
+    for &bundle in make_slice(bundles, bundle_count) {
+        bundle_ids.push(validate_handle!(bundle, "invalid render bundle").id);
+    }
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.execute_bundles(&pass.context, &bundle_ids) {
@@ -3417,7 +4320,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderInsertDebugMarker(
     pass: native::WGPURenderPassEncoder,
     marker_label: *const std::ffi::c_char,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.insert_debug_marker(
@@ -3437,7 +4340,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderInsertDebugMarker(
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuRenderPassEncoderPopDebugGroup(pass: native::WGPURenderPassEncoder) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.pop_debug_group(&pass.context) {
@@ -3456,7 +4359,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderPushDebugGroup(
     pass: native::WGPURenderPassEncoder,
     group_label: *const std::ffi::c_char,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.push_debug_group(
@@ -3482,9 +4385,9 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderSetBindGroup(
     dynamic_offset_count: usize,
     dynamic_offsets: *const u32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     // TODO: as per webgpu.h bindgroup is nullable
-    let bind_group_id = bind_group.as_ref().expect("invalid bind group").id;
+    let bind_group_id = validate_handle!(bind_group, "invalid bind group").id;
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.set_bind_group(
@@ -3508,7 +4411,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderSetBlendConstant(
     pass: native::WGPURenderPassEncoder,
     color: Option<&native::WGPUColor>,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.set_blend_constant(
@@ -3533,8 +4436,8 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderSetIndexBuffer(
     offset: u64,
     size: u64,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
-    let buffer_id = buffer.as_ref().expect("invalid buffer").id;
+    let pass = validate_handle!(pass, "invalid render pass");
+    let buffer_id = validate_handle!(buffer, "invalid buffer").id;
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.set_index_buffer(
@@ -3563,11 +4466,18 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderSetPipeline(
     pass: native::WGPURenderPassEncoder,
     render_pipeline: native::WGPURenderPipeline,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
-    let render_pipeline_id = render_pipeline
-        .as_ref()
-        .expect("invalid render pipeline")
-        .id;
+    let pass = validate_handle!(pass, "invalid render pass");
+    let render_pipeline_id = match validate_handle!(render_pipeline, "invalid render pipeline").id {
+        Some(id) => id,
+        None => {
+            return handle_error(
+                &pass.error_sink,
+                InvalidPipelineError,
+                None,
+                "wgpuRenderPassEncoderSetPipeline",
+            )
+        }
+    };
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.set_pipeline(&pass.context, render_pipeline_id) {
@@ -3589,7 +4499,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderSetScissorRect(
     width: u32,
     height: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.set_scissor_rect(&pass.context, x, y, width, height) {
@@ -3608,7 +4518,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderSetStencilReference(
     pass: native::WGPURenderPassEncoder,
     reference: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.set_stencil_reference(&pass.context, reference) {
@@ -3630,9 +4540,9 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderSetVertexBuffer(
     offset: u64,
     size: u64,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     // TODO: as per webgpu.h buffer is nullable
-    let buffer_id = buffer.as_ref().expect("invalid buffer").id;
+    let buffer_id = validate_handle!(buffer, "invalid buffer").id;
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.set_vertex_buffer(
@@ -3666,7 +4576,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderSetViewport(
     min_depth: f32,
     max_depth: f32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.set_viewport(&pass.context, x, y, width, height, min_depth, max_depth) {
@@ -3684,20 +4594,14 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderSetViewport(
 pub unsafe extern "C" fn wgpuRenderPassEncoderReference(
     render_pass_encoder: native::WGPURenderPassEncoder,
 ) {
-    assert!(
-        !render_pass_encoder.is_null(),
-        "invalid render pass encoder"
-    );
+    validate_handle!(render_pass_encoder, "invalid render pass encoder");
     Arc::increment_strong_count(render_pass_encoder);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuRenderPassEncoderRelease(
     render_pass_encoder: native::WGPURenderPassEncoder,
 ) {
-    assert!(
-        !render_pass_encoder.is_null(),
-        "invalid render pass encoder"
-    );
+    validate_handle!(render_pass_encoder, "invalid render pass encoder");
     Arc::decrement_strong_count(render_pass_encoder);
 }
 
@@ -3709,13 +4613,25 @@ pub unsafe extern "C" fn wgpuRenderPipelineGetBindGroupLayout(
     group_index: u32,
 ) -> native::WGPUBindGroupLayout {
     let (render_pipeline_id, context, error_sink) = {
-        let render_pipeline = render_pipeline.as_ref().expect("invalid render pipeline");
+        let render_pipeline = validate_handle!(render_pipeline, "invalid render pipeline");
         (
             render_pipeline.id,
             &render_pipeline.context,
             &render_pipeline.error_sink,
         )
     };
+    let render_pipeline_id = match render_pipeline_id {
+        Some(id) => id,
+        None => {
+            handle_error(
+                error_sink,
+                InvalidPipelineError,
+                None,
+                "wgpuRenderPipelineGetBindGroupLayout",
+            );
+            return std::ptr::null();
+        }
+    };
     let (bind_group_layout_id, error) = gfx_select!(render_pipeline_id => context.render_pipeline_get_bind_group_layout(render_pipeline_id, group_index, None));
     if let Some(cause) = error {
         handle_error(
@@ -3726,20 +4642,23 @@ pub unsafe extern "C" fn wgpuRenderPipelineGetBindGroupLayout(
         );
     }
 
-    Arc::into_raw(Arc::new(WGPUBindGroupLayoutImpl {
-        context: context.clone(),
-        id: bind_group_layout_id,
-    }))
+    handles::into_raw(
+        Arc::new(WGPUBindGroupLayoutImpl {
+            context: context.clone(),
+            id: bind_group_layout_id,
+        }),
+        None,
+    )
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuRenderPipelineReference(render_pipeline: native::WGPURenderPipeline) {
-    assert!(!render_pipeline.is_null(), "invalid render pipeline");
+    validate_handle!(render_pipeline, "invalid render pipeline");
     Arc::increment_strong_count(render_pipeline);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuRenderPipelineRelease(render_pipeline: native::WGPURenderPipeline) {
-    assert!(!render_pipeline.is_null(), "invalid render pipeline");
+    validate_handle!(render_pipeline, "invalid render pipeline");
     Arc::decrement_strong_count(render_pipeline);
 }
 
@@ -3747,28 +4666,181 @@ pub unsafe extern "C" fn wgpuRenderPipelineRelease(render_pipeline: native::WGPU
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuSamplerReference(sampler: native::WGPUSampler) {
-    assert!(!sampler.is_null(), "invalid sampler");
+    validate_handle!(sampler, "invalid sampler");
     Arc::increment_strong_count(sampler);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuSamplerRelease(sampler: native::WGPUSampler) {
-    assert!(!sampler.is_null(), "invalid sampler");
+    validate_handle!(sampler, "invalid sampler");
     Arc::decrement_strong_count(sampler);
 }
 
 // ShaderModule methods
 
+#[no_mangle]
+pub unsafe extern "C" fn wgpuShaderModuleGetCompilationInfo(
+    shader_module: native::WGPUShaderModule,
+    callback: native::WGPUShaderModuleGetCompilationInfoCallback,
+    userdata: *mut std::os::raw::c_void,
+) {
+    let shader_module = validate_handle!(shader_module, "invalid shader module");
+    let callback = callback.expect("invalid callback");
+
+    let texts = shader_module
+        .compilation_messages
+        .iter()
+        .map(|message| CString::new(message.message.as_str()).unwrap())
+        .collect::<Vec<_>>();
+    let messages = shader_module
+        .compilation_messages
+        .iter()
+        .zip(texts.iter())
+        .map(|(message, text)| {
+            let mut native_message = native::WGPUCompilationMessage {
+                nextInChain: std::ptr::null(),
+                message: text.as_ptr(),
+                type_: native::WGPUCompilationMessageType_Error,
+                lineNum: 0,
+                linePos: 0,
+                offset: 0,
+                length: 0,
+                utf16LinePos: 0,
+                utf16Offset: 0,
+                utf16Length: 0,
+            };
+            if let Some((location, utf16_location)) = message.location {
+                native_message.lineNum = location.line_number as u64;
+                native_message.linePos = location.line_position as u64;
+                native_message.offset = location.offset as u64;
+                native_message.length = location.length as u64;
+                native_message.utf16LinePos = utf16_location.line_position as u64;
+                native_message.utf16Offset = utf16_location.offset as u64;
+                native_message.utf16Length = utf16_location.length as u64;
+            }
+            native_message
+        })
+        .collect::<Vec<_>>();
+
+    callback(
+        native::WGPUCompilationInfoRequestStatus_Success,
+        &native::WGPUCompilationInfo {
+            nextInChain: std::ptr::null(),
+            messageCount: messages.len(),
+            messages: messages.as_ptr(),
+        },
+        userdata,
+    );
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn wgpuShaderModuleReference(shader_module: native::WGPUShaderModule) {
-    assert!(!shader_module.is_null(), "invalid shader module");
+    validate_handle!(shader_module, "invalid shader module");
     Arc::increment_strong_count(shader_module);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuShaderModuleRelease(shader_module: native::WGPUShaderModule) {
-    assert!(!shader_module.is_null(), "invalid shader module");
+    validate_handle!(shader_module, "invalid shader module");
     Arc::decrement_strong_count(shader_module);
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn wgpuShaderModuleGetTranslatedSource(
+    shader_module: native::WGPUShaderModule,
+    backend: native::WGPUBackendType,
+    entry_point: *const std::ffi::c_char,
+    callback: native::WGPUShaderModuleGetTranslatedSourceCallback,
+    userdata: *mut std::os::raw::c_void,
+) {
+    let shader_module = validate_handle!(shader_module, "invalid shader module");
+    let callback = callback.expect("invalid callback");
+
+    #[cfg(feature = "shader_inspection")]
+    let result = match (&shader_module.source, conv::map_translation_target(backend)) {
+        (None, _) => Err((
+            native::WGPUTranslatedSourceStatus_Unavailable,
+            "shader module has no naga representation".to_string(),
+        )),
+        (_, None) => Err((
+            native::WGPUTranslatedSourceStatus_Error,
+            format!("no shading language to translate to for backend {backend}"),
+        )),
+        (Some(source), Some(target)) => source
+            .module()
+            .and_then(|module| {
+                shader::translate(module, target, ptr_into_label(entry_point).as_deref())
+            })
+            .map_err(|err| (native::WGPUTranslatedSourceStatus_Error, err.to_string())),
+    };
+    #[cfg(not(feature = "shader_inspection"))]
+    let result: Result<Vec<u8>, _> = {
+        let _ = (shader_module, backend, entry_point);
+        Err((
+            native::WGPUTranslatedSourceStatus_Unavailable,
+            "wgpu-native was built without the shader_inspection feature".to_string(),
+        ))
+    };
+
+    match result {
+        Ok(source) => callback(
+            native::WGPUTranslatedSourceStatus_Success,
+            source.as_ptr() as _,
+            source.len(),
+            std::ptr::null(),
+            userdata,
+        ),
+        Err((status, message)) => {
+            let message = CString::new(message).unwrap();
+            callback(status, std::ptr::null(), 0, message.as_ptr(), userdata);
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuShaderModuleGetReflection(
+    shader_module: native::WGPUShaderModule,
+    callback: native::WGPUShaderModuleGetReflectionCallback,
+    userdata: *mut std::os::raw::c_void,
+) {
+    let shader_module = validate_handle!(shader_module, "invalid shader module");
+    let callback = callback.expect("invalid callback");
+
+    #[cfg(feature = "shader_inspection")]
+    let result = match &shader_module.source {
+        None => Err((
+            native::WGPUShaderReflectionStatus_Unavailable,
+            "shader module has no naga representation".to_string(),
+        )),
+        Some(source) => source
+            .module()
+            .and_then(shader::reflect)
+            .map_err(|err| (native::WGPUShaderReflectionStatus_Error, err.to_string())),
+    };
+    #[cfg(not(feature = "shader_inspection"))]
+    let result: Result<shader::Reflection, _> = {
+        let _ = shader_module;
+        Err((
+            native::WGPUShaderReflectionStatus_Unavailable,
+            "wgpu-native was built without the shader_inspection feature".to_string(),
+        ))
+    };
+
+    match result {
+        Ok(reflection) => {
+            let native_reflection = reflection.to_native();
+            callback(
+                native::WGPUShaderReflectionStatus_Success,
+                &native_reflection,
+                std::ptr::null(),
+                userdata,
+            );
+        }
+        Err((status, message)) => {
+            let message = CString::new(message).unwrap();
+            callback(status, std::ptr::null(), message.as_ptr(), userdata);
+        }
+    }
+}
+
 // Surface methods
 
 #[no_mangle]
@@ -3776,12 +4848,9 @@ pub unsafe extern "C" fn wgpuSurfaceConfigure(
     surface: native::WGPUSurface,
     config: Option<&native::WGPUSurfaceConfiguration>,
 ) {
-    let surface = surface.as_ref().expect("invalid surface");
+    let surface = validate_handle!(surface, "invalid surface");
     let config = config.expect("invalid config");
-    let device = config
-        .device
-        .as_ref()
-        .expect("invalid device for surface configuration");
+    let device = validate_handle!(config.device, "invalid device for surface configuration");
     let context = &device.context;
 
     let surface_config = follow_chain!(map_surface_configuration(
@@ -3824,10 +4893,10 @@ pub unsafe extern "C" fn wgpuSurfaceGetCapabilities(
     capabilities: Option<&mut native::WGPUSurfaceCapabilities>,
 ) {
     let (adapter_id, context) = {
-        let adapter = adapter.as_ref().expect("invalid adapter");
+        let adapter = validate_handle!(adapter, "invalid adapter");
         (adapter.id, &adapter.context)
     };
-    let surface_id = surface.as_ref().expect("invalid surface").id;
+    let surface_id = validate_handle!(surface, "invalid surface").id;
     let capabilities = capabilities.expect("invalid return pointer \"capabilities\"");
 
     let caps = match wgc::gfx_select!(adapter_id => context.surface_get_capabilities(surface_id, adapter_id))
@@ -3899,7 +4968,7 @@ pub unsafe extern "C" fn wgpuSurfaceGetCurrentTexture(
     surface: native::WGPUSurface,
     surface_texture: Option<&mut native::WGPUSurfaceTexture>,
 ) {
-    let surface = surface.as_ref().expect("invalid surface");
+    let surface = validate_handle!(surface, "invalid surface");
     let context = &surface.context;
     let surface_texture = surface_texture.expect("invalid return pointer \"surface_texture\"");
 
@@ -3932,14 +5001,17 @@ pub unsafe extern "C" fn wgpuSurfaceGetCurrentTexture(
                 _ => false as native::WGPUBool,
             };
             surface_texture.texture = match texture_id {
-                Some(texture_id) => Arc::into_raw(Arc::new(WGPUTextureImpl {
-                    context: context.clone(),
-                    id: texture_id,
-                    error_sink: surface_data.error_sink.clone(),
-                    data: surface_data.texture_data,
-                    surface_id: Some(surface.id),
-                    has_surface_presented: surface.has_surface_presented.clone(),
-                })),
+                Some(texture_id) => handles::into_raw(
+                    Arc::new(WGPUTextureImpl {
+                        context: context.clone(),
+                        id: texture_id,
+                        error_sink: surface_data.error_sink.clone(),
+                        data: surface_data.texture_data,
+                        surface_id: Some(surface.id),
+                        has_surface_presented: surface.has_surface_presented.clone(),
+                    }),
+                    None,
+                ),
                 None => std::ptr::null_mut(),
             };
         }
@@ -3949,7 +5021,7 @@ pub unsafe extern "C" fn wgpuSurfaceGetCurrentTexture(
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuSurfacePresent(surface: native::WGPUSurface) {
-    let surface = surface.as_ref().expect("invalid surface");
+    let surface = validate_handle!(surface, "invalid surface");
     let context = &surface.context;
     let surface_data_guard = surface.data.lock();
     let surface_data = match surface_data_guard.as_ref() {
@@ -3970,7 +5042,7 @@ pub unsafe extern "C" fn wgpuSurfacePresent(surface: native::WGPUSurface) {
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuSurfaceUnconfigure(surface: native::WGPUSurface) {
-    let surface = surface.as_ref().expect("invalid surface");
+    let surface = validate_handle!(surface, "invalid surface");
     let mut surface_data_guard = surface.data.lock();
     let _ = surface_data_guard.take(); // drop SurfaceData
     surface
@@ -3980,12 +5052,12 @@ pub unsafe extern "C" fn wgpuSurfaceUnconfigure(surface: native::WGPUSurface) {
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuSurfaceReference(surface: native::WGPUSurface) {
-    assert!(!surface.is_null(), "invalid surface");
+    validate_handle!(surface, "invalid surface");
     Arc::increment_strong_count(surface);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuSurfaceRelease(surface: native::WGPUSurface) {
-    assert!(!surface.is_null(), "invalid surface");
+    validate_handle!(surface, "invalid surface");
     Arc::decrement_strong_count(surface);
 }
 
@@ -4026,7 +5098,7 @@ pub unsafe extern "C" fn wgpuTextureCreateView(
     descriptor: Option<&native::WGPUTextureViewDescriptor>,
 ) -> native::WGPUTextureView {
     let (texture_id, context, error_sink) = {
-        let texture = texture.as_ref().expect("invalid texture");
+        let texture = validate_handle!(texture, "invalid texture");
         (texture.id, &texture.context, &texture.error_sink)
     };
 
@@ -4060,16 +5132,19 @@ pub unsafe extern "C" fn wgpuTextureCreateView(
         handle_error(error_sink, cause, None, "wgpuTextureCreateView");
     }
 
-    Arc::into_raw(Arc::new(WGPUTextureViewImpl {
-        context: context.clone(),
-        id: texture_view_id,
-    }))
+    handles::into_raw(
+        Arc::new(WGPUTextureViewImpl {
+            context: context.clone(),
+            id: texture_view_id,
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuTextureDestroy(texture: native::WGPUTexture) {
     let (texture_id, context) = {
-        let texture = texture.as_ref().expect("invalid texture");
+        let texture = validate_handle!(texture, "invalid texture");
         (texture.id, &texture.context)
     };
 
@@ -4079,7 +5154,7 @@ pub unsafe extern "C" fn wgpuTextureDestroy(texture: native::WGPUTexture) {
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuTextureGetDepthOrArrayLayers(texture: native::WGPUTexture) -> u32 {
-    let texture = texture.as_ref().expect("invalid texture");
+    let texture = validate_handle!(texture, "invalid texture");
     texture.data.size.depthOrArrayLayers
 }
 
@@ -4087,7 +5162,7 @@ pub unsafe extern "C" fn wgpuTextureGetDepthOrArrayLayers(texture: native::WGPUT
 pub unsafe extern "C" fn wgpuTextureGetDimension(
     texture: native::WGPUTexture,
 ) -> native::WGPUTextureDimension {
-    let texture = texture.as_ref().expect("invalid texture");
+    let texture = validate_handle!(texture, "invalid texture");
     texture.data.dimension
 }
 
@@ -4095,25 +5170,25 @@ pub unsafe extern "C" fn wgpuTextureGetDimension(
 pub unsafe extern "C" fn wgpuTextureGetFormat(
     texture: native::WGPUTexture,
 ) -> native::WGPUTextureFormat {
-    let texture = texture.as_ref().expect("invalid texture");
+    let texture = validate_handle!(texture, "invalid texture");
     texture.data.format
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuTextureGetHeight(texture: native::WGPUTexture) -> u32 {
-    let texture = texture.as_ref().expect("invalid texture");
+    let texture = validate_handle!(texture, "invalid texture");
     texture.data.size.height
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuTextureGetMipLevelCount(texture: native::WGPUTexture) -> u32 {
-    let texture = texture.as_ref().expect("invalid texture");
+    let texture = validate_handle!(texture, "invalid texture");
     texture.data.mip_level_count
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuTextureGetSampleCount(texture: native::WGPUTexture) -> u32 {
-    let texture = texture.as_ref().expect("invalid texture");
+    let texture = validate_handle!(texture, "invalid texture");
     texture.data.sample_count
 }
 
@@ -4121,24 +5196,24 @@ pub unsafe extern "C" fn wgpuTextureGetSampleCount(texture: native::WGPUTexture)
 pub unsafe extern "C" fn wgpuTextureGetUsage(
     texture: native::WGPUTexture,
 ) -> native::WGPUTextureUsageFlags {
-    let texture = texture.as_ref().expect("invalid texture");
+    let texture = validate_handle!(texture, "invalid texture");
     texture.data.usage
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuTextureGetWidth(texture: native::WGPUTexture) -> u32 {
-    let texture = texture.as_ref().expect("invalid texture");
+    let texture = validate_handle!(texture, "invalid texture");
     texture.data.size.width
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuTextureReference(texture: native::WGPUTexture) {
-    assert!(!texture.is_null(), "invalid texture");
+    validate_handle!(texture, "invalid texture");
     Arc::increment_strong_count(texture);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuTextureRelease(texture: native::WGPUTexture) {
-    assert!(!texture.is_null(), "invalid texture");
+    validate_handle!(texture, "invalid texture");
     Arc::decrement_strong_count(texture);
 }
 
@@ -4146,12 +5221,12 @@ pub unsafe extern "C" fn wgpuTextureRelease(texture: native::WGPUTexture) {
 
 #[no_mangle]
 pub unsafe extern "C" fn wgpuTextureViewReference(texture_view: native::WGPUTextureView) {
-    assert!(!texture_view.is_null(), "invalid texture");
+    validate_handle!(texture_view, "invalid texture");
     Arc::increment_strong_count(texture_view);
 }
 #[no_mangle]
 pub unsafe extern "C" fn wgpuTextureViewRelease(texture_view: native::WGPUTextureView) {
-    assert!(!texture_view.is_null(), "invalid texture");
+    validate_handle!(texture_view, "invalid texture");
     Arc::decrement_strong_count(texture_view);
 }
 
@@ -4162,7 +5237,7 @@ pub unsafe extern "C" fn wgpuGenerateReport(
     instance: native::WGPUInstance,
     native_report: Option<&mut native::WGPUGlobalReport>,
 ) {
-    let context = &instance.as_ref().expect("invalid instance").context;
+    let context = &validate_handle!(instance, "invalid instance").context;
     let native_report = native_report.expect("invalid return pointer \"native_report\"");
     conv::write_global_report(native_report, &context.generate_report());
 }
@@ -4173,24 +5248,7 @@ pub unsafe extern "C" fn wgpuQueueSubmitForIndex(
     command_count: usize,
     commands: *const native::WGPUCommandBuffer,
 ) -> native::WGPUSubmissionIndex {
-    let (queue_id, context) = {
-        let queue = queue.as_ref().expect("invalid queue");
-        (queue.queue.id, &queue.queue.context)
-    };
-
-    let command_buffers = make_slice(commands, command_count)
-        .iter()
-        .map(|command_buffer| {
-            let command_buffer = command_buffer.as_ref().expect("invalid command buffer");
-            command_buffer.open.store(false, atomic::Ordering::SeqCst);
-            command_buffer.id
-        })
-        .collect::<SmallVec<[_; 4]>>();
-
-    match gfx_select!(queue_id => context.queue_submit(queue_id, &command_buffers)) {
-        Ok(submission_index) => submission_index.index,
-        Err(cause) => handle_error_fatal(cause, "wgpuQueueSubmitForIndex"),
-    }
+    queue_submit(queue, command_count, commands, "wgpuQueueSubmitForIndex")
 }
 
 #[no_mangle]
@@ -4200,7 +5258,7 @@ pub unsafe extern "C" fn wgpuDevicePoll(
     wrapped_submission_index: Option<&native::WGPUWrappedSubmissionIndex>,
 ) -> bool {
     let (device_id, context) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context)
     };
 
@@ -4236,7 +5294,7 @@ pub unsafe extern "C" fn wgpuDeviceCreateShaderModuleSpirV(
     descriptor: Option<&native::WGPUShaderModuleDescriptorSpirV>,
 ) -> native::WGPUShaderModule {
     let (device_id, context, error_sink) = {
-        let device = device.as_ref().expect("invalid device");
+        let device = validate_handle!(device, "invalid device");
         (device.id, &device.context, &device.error_sink)
     };
     let descriptor = descriptor.expect("invalid descriptor");
@@ -4251,19 +5309,28 @@ pub unsafe extern "C" fn wgpuDeviceCreateShaderModuleSpirV(
         descriptor.sourceSize as usize,
     ));
     let (shader_module_id, error) = gfx_select!(device_id => context.device_create_shader_module_spirv(device_id, &desc, source, None));
+    let mut compilation_messages = Vec::new();
     if let Some(cause) = error {
+        compilation_messages = shader::compilation_messages(&cause);
         handle_error(
             error_sink,
             cause,
-            desc.label,
+            desc.label.clone(),
             "wgpuDeviceCreateShaderModuleSpirV",
         );
     }
 
-    Arc::into_raw(Arc::new(WGPUShaderModuleImpl {
-        context: context.clone(),
-        id: Some(shader_module_id),
-    }))
+    handles::into_raw(
+        Arc::new(WGPUShaderModuleImpl {
+            context: context.clone(),
+            id: Some(shader_module_id),
+            #[cfg(feature = "shader_inspection")]
+            source: None,
+            overrides: None,
+            compilation_messages,
+        }),
+        desc.label.as_deref(),
+    )
 }
 
 #[no_mangle]
@@ -4274,7 +5341,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderSetPushConstants(
     size_bytes: u32,
     data: *const u8,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.set_push_constants(
@@ -4300,7 +5367,7 @@ pub unsafe extern "C" fn wgpuComputePassEncoderSetPushConstants(
     size_bytes: u32,
     data: *const u8,
 ) {
-    let pass = pass.as_ref().expect("invalid compute pass");
+    let pass = validate_handle!(pass, "invalid compute pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.set_push_constants(&pass.context, offset, make_slice(data, size_bytes as usize)) {
@@ -4321,8 +5388,8 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderMultiDrawIndirect(
     offset: u64,
     count: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
-    let buffer_id = buffer.as_ref().expect("invalid buffer").id;
+    let pass = validate_handle!(pass, "invalid render pass");
+    let buffer_id = validate_handle!(buffer, "invalid buffer").id;
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.multi_draw_indirect(&pass.context, buffer_id, offset, count) {
@@ -4343,8 +5410,8 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderMultiDrawIndexedIndirect(
     offset: u64,
     count: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
-    let buffer_id = buffer.as_ref().expect("invalid buffer").id;
+    let pass = validate_handle!(pass, "invalid render pass");
+    let buffer_id = validate_handle!(buffer, "invalid buffer").id;
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.multi_draw_indexed_indirect(&pass.context, buffer_id, offset, count) {
@@ -4367,9 +5434,9 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderMultiDrawIndirectCount(
     count_buffer_offset: u64,
     max_count: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
-    let buffer_id = buffer.as_ref().expect("invalid buffer").id;
-    let count_buffer_id = count_buffer.as_ref().expect("invalid count buffer").id;
+    let pass = validate_handle!(pass, "invalid render pass");
+    let buffer_id = validate_handle!(buffer, "invalid buffer").id;
+    let count_buffer_id = validate_handle!(count_buffer, "invalid count buffer").id;
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.multi_draw_indirect_count(
@@ -4399,9 +5466,9 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderMultiDrawIndexedIndirectCount(
     count_buffer_offset: u64,
     max_count: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
-    let buffer_id = buffer.as_ref().expect("invalid buffer").id;
-    let count_buffer_id = count_buffer.as_ref().expect("invalid count buffer").id;
+    let pass = validate_handle!(pass, "invalid render pass");
+    let buffer_id = validate_handle!(buffer, "invalid buffer").id;
+    let count_buffer_id = validate_handle!(count_buffer, "invalid count buffer").id;
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.multi_draw_indexed_indirect_count(
@@ -4428,8 +5495,14 @@ pub unsafe extern "C" fn wgpuComputePassEncoderBeginPipelineStatisticsQuery(
     query_set: native::WGPUQuerySet,
     query_index: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid compute pass");
-    let query_set_id = query_set.as_ref().expect("invalid query set").id;
+    let pass = validate_handle!(pass, "invalid compute pass");
+    let Some(query_set_id) = validate_handle!(query_set, "invalid query set").record_use(
+        &pass.used_query_sets,
+        &pass.error_sink,
+        "wgpuComputePassEncoderBeginPipelineStatisticsQuery",
+    ) else {
+        return;
+    };
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.begin_pipeline_statistics_query(&pass.context, query_set_id, query_index) {
@@ -4447,7 +5520,7 @@ pub unsafe extern "C" fn wgpuComputePassEncoderBeginPipelineStatisticsQuery(
 pub unsafe extern "C" fn wgpuComputePassEncoderEndPipelineStatisticsQuery(
     pass: native::WGPUComputePassEncoder,
 ) {
-    let pass = pass.as_ref().expect("invalid compute pass");
+    let pass = validate_handle!(pass, "invalid compute pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.end_pipeline_statistics_query(&pass.context) {
@@ -4467,8 +5540,14 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderBeginPipelineStatisticsQuery(
     query_set: native::WGPUQuerySet,
     query_index: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
-    let query_set_id = query_set.as_ref().expect("invalid query set").id;
+    let pass = validate_handle!(pass, "invalid render pass");
+    let Some(query_set_id) = validate_handle!(query_set, "invalid query set").record_use(
+        &pass.used_query_sets,
+        &pass.error_sink,
+        "wgpuRenderPassEncoderBeginPipelineStatisticsQuery",
+    ) else {
+        return;
+    };
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.begin_pipeline_statistics_query(&pass.context, query_set_id, query_index) {
@@ -4486,7 +5565,7 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderBeginPipelineStatisticsQuery(
 pub unsafe extern "C" fn wgpuRenderPassEncoderEndPipelineStatisticsQuery(
     pass: native::WGPURenderPassEncoder,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
+    let pass = validate_handle!(pass, "invalid render pass");
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.end_pipeline_statistics_query(&pass.context) {
@@ -4506,8 +5585,14 @@ pub unsafe extern "C" fn wgpuComputePassEncoderWriteTimestamp(
     query_set: native::WGPUQuerySet,
     query_index: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid compute pass");
-    let query_set_id = query_set.as_ref().expect("invalid query set").id;
+    let pass = validate_handle!(pass, "invalid compute pass");
+    let Some(query_set_id) = validate_handle!(query_set, "invalid query set").record_use(
+        &pass.used_query_sets,
+        &pass.error_sink,
+        "wgpuComputePassEncoderWriteTimestamp",
+    ) else {
+        return;
+    };
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.write_timestamp(&pass.context, query_set_id, query_index) {
@@ -4527,8 +5612,14 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderWriteTimestamp(
     query_set: native::WGPUQuerySet,
     query_index: u32,
 ) {
-    let pass = pass.as_ref().expect("invalid render pass");
-    let query_set_id = query_set.as_ref().expect("invalid query set").id;
+    let pass = validate_handle!(pass, "invalid render pass");
+    let Some(query_set_id) = validate_handle!(query_set, "invalid query set").record_use(
+        &pass.used_query_sets,
+        &pass.error_sink,
+        "wgpuRenderPassEncoderWriteTimestamp",
+    ) else {
+        return;
+    };
     let encoder = pass.encoder.as_mut().unwrap();
 
     match encoder.write_timestamp(&pass.context, query_set_id, query_index) {
@@ -4541,3 +5632,1130 @@ pub unsafe extern "C" fn wgpuRenderPassEncoderWriteTimestamp(
         ),
     }
 }
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuQueueGetTimestampPeriod(queue: native::WGPUQueue) -> f32 {
+    let (queue_id, context) = {
+        let queue = validate_handle!(queue, "invalid queue");
+        (queue.queue.id, &queue.queue.context)
+    };
+
+    match gfx_select!(queue_id => context.queue_get_timestamp_period(queue_id)) {
+        Ok(timestamp_period) => timestamp_period,
+        Err(cause) => handle_error_fatal(cause, "wgpuQueueGetTimestampPeriod"),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuDeviceCreateGpuProfiler(
+    device: native::WGPUDevice,
+    descriptor: Option<&native::WGPUGpuProfilerDescriptor>,
+) -> native::WGPUGpuProfiler {
+    let (device_id, queue_id, context, error_sink) = {
+        let device = validate_handle!(device, "invalid device");
+        (
+            device.id,
+            device.queue.id,
+            &device.context,
+            &device.error_sink,
+        )
+    };
+    let descriptor = descriptor.expect("invalid gpu profiler descriptor");
+    let label = ptr_into_label(descriptor.label);
+
+    if descriptor.maxScopes == 0 {
+        handle_error(
+            error_sink,
+            profiler::GpuProfilerError::NoScopes,
+            label,
+            "wgpuDeviceCreateGpuProfiler",
+        );
+        return std::ptr::null();
+    }
+    let scopes = profiler::Scopes::new(descriptor.maxScopes);
+    let query_count = scopes.query_capacity();
+    let size = query_count as u64 * wgt::QUERY_SIZE as u64;
+
+    let (query_set_id, error) = gfx_select!(device_id => context.device_create_query_set(
+        device_id,
+        &wgt::QuerySetDescriptor {
+            label: label.clone(),
+            ty: wgt::QueryType::Timestamp,
+            count: query_count,
+        },
+        None
+    ));
+    if let Some(cause) = error {
+        handle_error(
+            error_sink,
+            cause,
+            label.clone(),
+            "wgpuDeviceCreateGpuProfiler",
+        );
+    }
+    let (resolve_buffer_id, error) = gfx_select!(device_id => context.device_create_buffer(
+        device_id,
+        &wgt::BufferDescriptor {
+            label: label.clone(),
+            size,
+            usage: wgt::BufferUsages::QUERY_RESOLVE | wgt::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        },
+        None
+    ));
+    if let Some(cause) = error {
+        handle_error(
+            error_sink,
+            cause,
+            label.clone(),
+            "wgpuDeviceCreateGpuProfiler",
+        );
+    }
+    let (read_buffer_id, error) = gfx_select!(device_id => context.device_create_buffer(
+        device_id,
+        &wgt::BufferDescriptor {
+            label: label.clone(),
+            size,
+            usage: wgt::BufferUsages::MAP_READ | wgt::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        },
+        None
+    ));
+    if let Some(cause) = error {
+        handle_error(
+            error_sink,
+            cause,
+            label.clone(),
+            "wgpuDeviceCreateGpuProfiler",
+        );
+    }
+
+    let timestamp_period = match gfx_select!(queue_id => context.queue_get_timestamp_period(queue_id))
+    {
+        Ok(timestamp_period) => timestamp_period,
+        Err(cause) => handle_error_fatal(cause, "wgpuDeviceCreateGpuProfiler"),
+    };
+
+    handles::into_raw(
+        Arc::new(WGPUGpuProfilerImpl {
+            context: context.clone(),
+            query_set_id,
+            resolve_buffer_id,
+            read_buffer_id,
+            timestamp_period,
+            error_sink: error_sink.clone(),
+            scopes: Arc::new(Mutex::new(scopes)),
+        }),
+        label.as_deref(),
+    )
+}
+
+unsafe fn gpu_profiler_write_timestamp(
+    profiler: &WGPUGpuProfilerImpl,
+    command_encoder: &WGPUCommandEncoderImpl,
+    query_index: Result<u32, profiler::GpuProfilerError>,
+    fn_ident: &'static str,
+) {
+    let query_index = match query_index {
+        Ok(query_index) => query_index,
+        Err(cause) => return handle_error(&profiler.error_sink, cause, None, fn_ident),
+    };
+
+    let (command_encoder_id, context) = (command_encoder.id, &profiler.context);
+    if let Err(cause) = gfx_select!(command_encoder_id => context.command_encoder_write_timestamp(
+        command_encoder_id,
+        profiler.query_set_id,
+        query_index
+    )) {
+        handle_error(&command_encoder.error_sink, cause, None, fn_ident);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuGpuProfilerBeginScope(
+    profiler: native::WGPUGpuProfiler,
+    command_encoder: native::WGPUCommandEncoder,
+    label: *const std::ffi::c_char,
+) {
+    let profiler = validate_handle!(profiler, "invalid gpu profiler");
+    let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
+    let label = match label.as_ref() {
+        Some(label) => CStr::from_ptr(label).to_owned(),
+        None => CString::default(),
+    };
+
+    let query_index = profiler.scopes.lock().begin(label);
+    gpu_profiler_write_timestamp(
+        profiler,
+        command_encoder,
+        query_index,
+        "wgpuGpuProfilerBeginScope",
+    );
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuGpuProfilerEndScope(
+    profiler: native::WGPUGpuProfiler,
+    command_encoder: native::WGPUCommandEncoder,
+) {
+    let profiler = validate_handle!(profiler, "invalid gpu profiler");
+    let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
+
+    let query_index = profiler.scopes.lock().end();
+    gpu_profiler_write_timestamp(
+        profiler,
+        command_encoder,
+        query_index,
+        "wgpuGpuProfilerEndScope",
+    );
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuGpuProfilerResolve(
+    profiler: native::WGPUGpuProfiler,
+    command_encoder: native::WGPUCommandEncoder,
+) {
+    let profiler = validate_handle!(profiler, "invalid gpu profiler");
+    let (command_encoder_id, context, error_sink) = {
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
+        (
+            command_encoder.id,
+            &command_encoder.context,
+            &command_encoder.error_sink,
+        )
+    };
+
+    let query_count = match profiler.scopes.lock().resolve() {
+        Ok(0) => return,
+        Ok(query_count) => query_count,
+        Err(cause) => {
+            return handle_error(&profiler.error_sink, cause, None, "wgpuGpuProfilerResolve")
+        }
+    };
+
+    if let Err(cause) = gfx_select!(command_encoder_id => context.command_encoder_resolve_query_set(
+        command_encoder_id,
+        profiler.query_set_id,
+        0,
+        query_count,
+        profiler.resolve_buffer_id,
+        0
+    )) {
+        return handle_error(error_sink, cause, None, "wgpuGpuProfilerResolve");
+    }
+    if let Err(cause) = gfx_select!(command_encoder_id => context.command_encoder_copy_buffer_to_buffer(
+        command_encoder_id,
+        profiler.resolve_buffer_id,
+        0,
+        profiler.read_buffer_id,
+        0,
+        query_count as u64 * wgt::QUERY_SIZE as u64
+    )) {
+        handle_error(error_sink, cause, None, "wgpuGpuProfilerResolve");
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuGpuProfilerGetResults(
+    profiler: native::WGPUGpuProfiler,
+    callback: native::WGPUGpuProfilerGetResultsCallback,
+    userdata: *mut std::ffi::c_void,
+) {
+    let profiler = validate_handle!(profiler, "invalid gpu profiler");
+    let callback = callback.expect("invalid callback");
+    let userdata = utils::Userdata::new(userdata);
+
+    let report = move |results: Result<Vec<profiler::ScopeResult>, String>| match results {
+        Ok(results) => {
+            let scopes = results
+                .iter()
+                .map(|result| native::WGPUGpuProfilerScope {
+                    label: result.label.as_ptr(),
+                    depth: result.depth,
+                    durationNs: result.duration_ns,
+                })
+                .collect::<Vec<_>>();
+            callback(
+                native::WGPUGpuProfilerResultsStatus_Success,
+                scopes.len(),
+                scopes.as_ptr(),
+                std::ptr::null(),
+                userdata.as_ptr(),
+            );
+        }
+        Err(message) => {
+            let message = CString::new(message).unwrap_or_default();
+            callback(
+                native::WGPUGpuProfilerResultsStatus_Error,
+                0,
+                std::ptr::null(),
+                message.as_ptr(),
+                userdata.as_ptr(),
+            );
+        }
+    };
+
+    let query_count = match profiler.scopes.lock().start_reading() {
+        Ok(query_count) => query_count,
+        Err(cause) => return report(Err(cause.to_string())),
+    };
+    let timestamp_period = profiler.timestamp_period;
+    if query_count == 0 {
+        let results = profiler
+            .scopes
+            .lock()
+            .finish_reading(Some(&[]), timestamp_period);
+        return report(Ok(results));
+    }
+
+    let (read_buffer_id, context) = (profiler.read_buffer_id, profiler.context.clone());
+    let scopes = profiler.scopes.clone();
+    let size = query_count as u64 * wgt::QUERY_SIZE as u64;
+
+    let operation = wgc::resource::BufferMapOperation {
+        host: wgc::device::HostMap::Read,
+        callback: Some(wgc::resource::BufferMapCallback::from_rust(Box::new(
+            move |result: resource::BufferAccessResult| {
+                let timestamps = result
+                    .and_then(|()| {
+                        gfx_select!(read_buffer_id => context.buffer_get_mapped_range(read_buffer_id, 0, Some(size)))
+                    })
+                    .map(|(ptr, size)| {
+                        let timestamps = make_slice(ptr.as_ptr(), size as usize)
+                            .chunks_exact(mem::size_of::<u64>())
+                            .map(|bytes| u64::from_ne_bytes(bytes.try_into().unwrap()))
+                            .collect::<Vec<_>>();
+                        let _ = gfx_select!(read_buffer_id => context.buffer_unmap(read_buffer_id));
+                        timestamps
+                    });
+
+                let results = scopes
+                    .lock()
+                    .finish_reading(timestamps.as_deref().ok(), timestamp_period);
+                report(
+                    timestamps
+                        .map(|_| results)
+                        .map_err(|cause| cause.to_string()),
+                );
+            },
+        ))),
+    };
+
+    // Failures are reported to the map callback.
+    let _ = gfx_select!(read_buffer_id => profiler.context.buffer_map_async(
+        read_buffer_id,
+        0,
+        Some(size),
+        operation
+    ));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuGpuProfilerReference(profiler: native::WGPUGpuProfiler) {
+    validate_handle!(profiler, "invalid gpu profiler");
+    Arc::increment_strong_count(profiler);
+}
+#[no_mangle]
+pub unsafe extern "C" fn wgpuGpuProfilerRelease(profiler: native::WGPUGpuProfiler) {
+    validate_handle!(profiler, "invalid gpu profiler");
+    Arc::decrement_strong_count(profiler);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuDeviceCreateStagingBelt(
+    device: native::WGPUDevice,
+    descriptor: Option<&native::WGPUStagingBeltDescriptor>,
+) -> native::WGPUStagingBelt {
+    let device = validate_handle!(device, "invalid device");
+    let descriptor = descriptor.expect("invalid staging belt descriptor");
+    let label = ptr_into_label(descriptor.label);
+
+    handles::into_raw(
+        Arc::new(WGPUStagingBeltImpl {
+            context: device.context.clone(),
+            device_id: device.id,
+            label: label.as_deref().map(str::to_string),
+            error_sink: device.error_sink.clone(),
+            chunks: Arc::new(Mutex::new(staging_belt::Chunks::new(descriptor.chunkSize))),
+        }),
+        label.as_deref(),
+    )
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuStagingBeltWriteBuffer(
+    belt: native::WGPUStagingBelt,
+    buffer: native::WGPUBuffer,
+    offset: u64,
+    size: u64,
+) -> *mut std::ffi::c_void {
+    let belt = validate_handle!(belt, "invalid staging belt");
+    let buffer_id = validate_handle!(buffer, "invalid buffer").id;
+    let (device_id, context, error_sink) = (belt.device_id, &belt.context, &belt.error_sink);
+
+    if let Err(cause) = staging_belt::Chunks::check_write(size) {
+        handle_error(error_sink, cause, None, "wgpuStagingBeltWriteBuffer");
+        return std::ptr::null_mut();
+    }
+
+    let mut chunks = belt.chunks.lock();
+    let (chunk_id, chunk_offset) = match chunks.allocate(size) {
+        Some(allocation) => allocation,
+        None => {
+            let chunk_size = chunks.new_chunk_size(size);
+            let (chunk_id, error) = gfx_select!(device_id => context.device_create_buffer(
+                device_id,
+                &wgt::BufferDescriptor {
+                    label: belt.label.as_deref().map(Cow::Borrowed),
+                    size: chunk_size,
+                    usage: wgt::BufferUsages::MAP_WRITE | wgt::BufferUsages::COPY_SRC,
+                    mapped_at_creation: true,
+                },
+                None
+            ));
+            if let Some(cause) = error {
+                handle_error(
+                    error_sink,
+                    cause,
+                    belt.label.as_deref().map(Cow::Borrowed),
+                    "wgpuStagingBeltWriteBuffer",
+                );
+                gfx_select!(chunk_id => context.buffer_drop(chunk_id, false));
+                return std::ptr::null_mut();
+            }
+            chunks.add_active(staging_belt::Chunk::new(chunk_id, chunk_size));
+            chunks
+                .allocate(size)
+                .expect("new chunk is large enough for the write")
+        }
+    };
+
+    let ptr = match gfx_select!(chunk_id => context.buffer_get_mapped_range(
+        chunk_id,
+        chunk_offset,
+        Some(size)
+    )) {
+        Ok((ptr, _)) => ptr,
+        Err(cause) => {
+            handle_error(error_sink, cause, None, "wgpuStagingBeltWriteBuffer");
+            return std::ptr::null_mut();
+        }
+    };
+    chunks.push_copy(staging_belt::Copy {
+        source: chunk_id,
+        source_offset: chunk_offset,
+        destination: buffer_id,
+        destination_offset: offset,
+        size,
+    });
+
+    ptr.as_ptr() as *mut std::ffi::c_void
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuStagingBeltFinish(
+    belt: native::WGPUStagingBelt,
+    command_encoder: native::WGPUCommandEncoder,
+) {
+    let belt = validate_handle!(belt, "invalid staging belt");
+    let (command_encoder_id, error_sink) = {
+        let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
+        (command_encoder.id, &command_encoder.error_sink)
+    };
+    let context = &belt.context;
+
+    let (buffer_ids, copies) = belt.chunks.lock().finish();
+    for buffer_id in buffer_ids {
+        if let Err(cause) = gfx_select!(buffer_id => context.buffer_unmap(buffer_id)) {
+            handle_error(&belt.error_sink, cause, None, "wgpuStagingBeltFinish");
+        }
+    }
+    for copy in copies {
+        if let Err(cause) = gfx_select!(command_encoder_id => context.command_encoder_copy_buffer_to_buffer(
+            command_encoder_id,
+            copy.source,
+            copy.source_offset,
+            copy.destination,
+            copy.destination_offset,
+            copy.size
+        )) {
+            handle_error(error_sink, cause, None, "wgpuStagingBeltFinish");
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuStagingBeltRecall(belt: native::WGPUStagingBelt) {
+    let belt = validate_handle!(belt, "invalid staging belt");
+
+    let closed = belt.chunks.lock().recall();
+    for chunk in closed {
+        let buffer_id = chunk.buffer_id;
+        let context = belt.context.clone();
+        // The chunks are never handed out as WGPUBuffers, so their mapping
+        // runs no user callback and doesn't go through the callback executor.
+        // Chunks mapped after the belt is released are dropped instead of reused.
+        let chunks = Arc::downgrade(&belt.chunks);
+        let operation = wgc::resource::BufferMapOperation {
+            host: wgc::device::HostMap::Write,
+            callback: Some(wgc::resource::BufferMapCallback::from_rust(Box::new(
+                move |result: resource::BufferAccessResult| match (result, chunks.upgrade()) {
+                    (Ok(()), Some(chunks)) => chunks.lock().add_free(chunk),
+                    _ => gfx_select!(buffer_id => context.buffer_drop(buffer_id, false)),
+                },
+            ))),
+        };
+
+        // Failures are reported to the map callback.
+        let _ = gfx_select!(buffer_id => belt.context.buffer_map_async(
+            buffer_id,
+            0,
+            None,
+            operation
+        ));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuStagingBeltReference(belt: native::WGPUStagingBelt) {
+    validate_handle!(belt, "invalid staging belt");
+    Arc::increment_strong_count(belt);
+}
+#[no_mangle]
+pub unsafe extern "C" fn wgpuStagingBeltRelease(belt: native::WGPUStagingBelt) {
+    validate_handle!(belt, "invalid staging belt");
+    Arc::decrement_strong_count(belt);
+}
+
+impl MipmapPipelines {
+    fn create_objects(&self) -> Result<MipmapObjects, mipmaps::MipmapError> {
+        let (context, device_id) = (&self.context, self.device_id);
+        let label = Some(Cow::Borrowed("mipmap generation"));
+        let source = mipmaps::shader_source()?;
+
+        let (shader_module_id, shader_module_error) = gfx_select!(device_id => context.device_create_shader_module(
+            device_id,
+            &wgc::pipeline::ShaderModuleDescriptor {
+                label: label.clone(),
+                shader_bound_checks: wgt::ShaderBoundChecks::default(),
+            },
+            source,
+            None
+        ));
+        let (bind_group_layout_id, bind_group_layout_error) = gfx_select!(device_id => context.device_create_bind_group_layout(
+            device_id,
+            &wgc::binding_model::BindGroupLayoutDescriptor {
+                label: label.clone(),
+                entries: Cow::Owned(vec![
+                    wgt::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgt::ShaderStages::FRAGMENT,
+                        ty: wgt::BindingType::Texture {
+                            sample_type: wgt::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgt::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgt::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgt::ShaderStages::FRAGMENT,
+                        ty: wgt::BindingType::Sampler(wgt::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ]),
+            },
+            None
+        ));
+        let (pipeline_layout_id, pipeline_layout_error) = gfx_select!(device_id => context.device_create_pipeline_layout(
+            device_id,
+            &wgc::binding_model::PipelineLayoutDescriptor {
+                label: label.clone(),
+                bind_group_layouts: Cow::Owned(vec![bind_group_layout_id]),
+                push_constant_ranges: Cow::Borrowed(&[]),
+            },
+            None
+        ));
+        let (sampler_id, sampler_error) = gfx_select!(device_id => context.device_create_sampler(
+            device_id,
+            &wgc::resource::SamplerDescriptor {
+                label,
+                address_modes: [wgt::AddressMode::ClampToEdge; 3],
+                mag_filter: wgt::FilterMode::Linear,
+                min_filter: wgt::FilterMode::Linear,
+                mipmap_filter: wgt::FilterMode::Nearest,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: 32.0,
+                compare: None,
+                anisotropy_clamp: 1,
+                border_color: None,
+            },
+            None
+        ));
+
+        let objects = MipmapObjects {
+            shader_module_id,
+            bind_group_layout_id,
+            pipeline_layout_id,
+            sampler_id,
+            pipelines: HashMap::new(),
+        };
+        let error = shader_module_error
+            .map(mipmaps::MipmapError::from)
+            .or(bind_group_layout_error.map(Into::into))
+            .or(pipeline_layout_error.map(Into::into))
+            .or(sampler_error.map(Into::into));
+        match error {
+            Some(cause) => {
+                objects.release(context);
+                Err(cause)
+            }
+            None => Ok(objects),
+        }
+    }
+
+    /// Returns the pipeline for `format`, with its bind group layout and sampler.
+    fn get(
+        &self,
+        format: wgt::TextureFormat,
+    ) -> Result<(id::RenderPipelineId, id::BindGroupLayoutId, id::SamplerId), mipmaps::MipmapError>
+    {
+        let (context, device_id) = (&self.context, self.device_id);
+        let mut objects = self.objects.lock();
+        let objects = match &mut *objects {
+            Some(objects) => objects,
+            objects => objects.insert(self.create_objects()?),
+        };
+
+        if let Some(&pipeline_id) = objects.pipelines.get(&format) {
+            return Ok((
+                pipeline_id,
+                objects.bind_group_layout_id,
+                objects.sampler_id,
+            ));
+        }
+        let stage = |entry_point| wgc::pipeline::ProgrammableStageDescriptor {
+            module: objects.shader_module_id,
+            entry_point: Some(Cow::Borrowed(entry_point)),
+            constants: Default::default(),
+            zero_initialize_workgroup_memory: false,
+            vertex_pulling_transform: false,
+        };
+        let desc = wgc::pipeline::RenderPipelineDescriptor {
+            label: Some(Cow::Borrowed("mipmap generation")),
+            layout: Some(objects.pipeline_layout_id),
+            vertex: wgc::pipeline::VertexState {
+                stage: stage("vs_main"),
+                buffers: Cow::Borrowed(&[]),
+            },
+            primitive: wgt::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgt::MultisampleState::default(),
+            fragment: Some(wgc::pipeline::FragmentState {
+                stage: stage("fs_main"),
+                targets: Cow::Owned(vec![Some(format.into())]),
+            }),
+            multiview: None,
+            cache: None,
+        };
+        let (pipeline_id, error) = gfx_select!(device_id => context.device_create_render_pipeline(device_id, &desc, None, None));
+        if let Some(cause) = error {
+            gfx_select!(pipeline_id => context.render_pipeline_drop(pipeline_id));
+            return Err(cause.into());
+        }
+
+        objects.pipelines.insert(format, pipeline_id);
+        Ok((
+            pipeline_id,
+            objects.bind_group_layout_id,
+            objects.sampler_id,
+        ))
+    }
+
+    /// Renders `level` of `layer` from the level above it.
+    fn record_level(
+        &self,
+        command_encoder_id: id::CommandEncoderId,
+        texture_id: id::TextureId,
+        layer: u32,
+        level: u32,
+        format: wgt::TextureFormat,
+    ) -> Result<(), mipmaps::MipmapError> {
+        let (context, device_id) = (&self.context, self.device_id);
+        let (pipeline_id, bind_group_layout_id, sampler_id) = self.get(format)?;
+
+        let view_desc = |mip_level| wgc::resource::TextureViewDescriptor {
+            label: None,
+            format: None,
+            dimension: Some(wgt::TextureViewDimension::D2),
+            range: wgt::ImageSubresourceRange {
+                aspect: wgt::TextureAspect::All,
+                base_mip_level: mip_level,
+                mip_level_count: Some(1),
+                base_array_layer: layer,
+                array_layer_count: Some(1),
+            },
+        };
+        let (source_view_id, source_view_error) = gfx_select!(texture_id => context.texture_create_view(texture_id, &view_desc(level - 1), None));
+        let (target_view_id, target_view_error) = gfx_select!(texture_id => context.texture_create_view(texture_id, &view_desc(level), None));
+        let (bind_group_id, bind_group_error) = gfx_select!(device_id => context.device_create_bind_group(
+            device_id,
+            &wgc::binding_model::BindGroupDescriptor {
+                label: None,
+                layout: bind_group_layout_id,
+                entries: Cow::Owned(vec![
+                    wgc::binding_model::BindGroupEntry {
+                        binding: 0,
+                        resource: wgc::binding_model::BindingResource::TextureView(source_view_id),
+                    },
+                    wgc::binding_model::BindGroupEntry {
+                        binding: 1,
+                        resource: wgc::binding_model::BindingResource::Sampler(sampler_id),
+                    },
+                ]),
+            },
+            None
+        ));
+
+        let result = match (source_view_error, target_view_error, bind_group_error) {
+            (Some(cause), ..) | (_, Some(cause), _) => Err(cause.into()),
+            (.., Some(cause)) => Err(cause.into()),
+            (None, None, None) => {
+                let desc = wgc::command::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: Cow::Owned(vec![Some(
+                        wgc::command::RenderPassColorAttachment {
+                            view: target_view_id,
+                            resolve_target: None,
+                            channel: wgc::command::PassChannel {
+                                load_op: wgc::command::LoadOp::Clear,
+                                store_op: wgc::command::StoreOp::Store,
+                                clear_value: wgt::Color::TRANSPARENT,
+                                read_only: false,
+                            },
+                        },
+                    )]),
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                };
+                let (mut pass, error) = gfx_select!(command_encoder_id => context.command_encoder_create_render_pass_dyn(command_encoder_id, &desc));
+                match error {
+                    Some(cause) => Err(cause.into()),
+                    None => {
+                        let mut record = || -> Result<(), mipmaps::MipmapError> {
+                            pass.set_pipeline(context, pipeline_id)?;
+                            pass.set_bind_group(context, 0, bind_group_id, &[])?;
+                            pass.draw(context, 3, 1, 0, 0)?;
+                            pass.end(context)?;
+                            Ok(())
+                        };
+                        record()
+                    }
+                }
+            }
+        };
+
+        // The recorded pass keeps what it uses alive.
+        gfx_select!(bind_group_id => context.bind_group_drop(bind_group_id));
+        let _ = gfx_select!(source_view_id => context.texture_view_drop(source_view_id, false));
+        let _ = gfx_select!(target_view_id => context.texture_view_drop(target_view_id, false));
+        result
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuCommandEncoderGenerateMipmaps(
+    command_encoder: native::WGPUCommandEncoder,
+    texture: native::WGPUTexture,
+    base_mip_level: u32,
+    mip_level_count: u32,
+) {
+    let command_encoder = validate_handle!(command_encoder, "invalid command encoder");
+    let texture = validate_handle!(texture, "invalid texture");
+    let (command_encoder_id, context, error_sink, mipmap_pipelines) = (
+        command_encoder.id,
+        &command_encoder.context,
+        &command_encoder.error_sink,
+        &command_encoder.mipmap_pipelines,
+    );
+    let device_id = mipmap_pipelines.device_id;
+
+    let device_features = match gfx_select!(device_id => context.device_features(device_id)) {
+        Ok(device_features) => device_features,
+        Err(cause) => {
+            return handle_error(error_sink, cause, None, "wgpuCommandEncoderGenerateMipmaps")
+        }
+    };
+    let target = mipmaps::MipmapTarget {
+        format: conv::map_texture_format(texture.data.format).expect("invalid texture format"),
+        dimension: texture.data.dimension,
+        usage: conv::map_texture_usage_flags(texture.data.usage as native::WGPUTextureUsage),
+        sample_count: texture.data.sample_count,
+        mip_level_count: texture.data.mip_level_count,
+    };
+    let levels =
+        match mipmaps::level_range(&target, base_mip_level, mip_level_count, device_features) {
+            Ok(levels) => levels,
+            Err(cause) => {
+                return handle_error(error_sink, cause, None, "wgpuCommandEncoderGenerateMipmaps")
+            }
+        };
+
+    for layer in 0..texture.data.size.depthOrArrayLayers {
+        for level in levels.start + 1..levels.end {
+            if let Err(cause) = mipmap_pipelines.record_level(
+                command_encoder_id,
+                texture.id,
+                layer,
+                level,
+                target.format,
+            ) {
+                return handle_error(error_sink, cause, None, "wgpuCommandEncoderGenerateMipmaps");
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuAdapterRequestDevice2(
+    adapter: native::WGPUAdapter,
+    descriptor: Option<&native::WGPUDeviceDescriptor>,
+    callback_info: native::WGPURequestDeviceCallbackInfo2,
+) -> native::WGPUFuture {
+    let context = &validate_handle!(adapter, "invalid adapter").context;
+    let callback = callback_info.callback.expect("invalid callback");
+    let userdata1 = utils::Userdata::new(callback_info.userdata1);
+    let userdata2 = utils::Userdata::new(callback_info.userdata2);
+    let id = context
+        .callbacks
+        .futures
+        .create(conv::map_callback_mode(callback_info.mode));
+
+    request_device(adapter, descriptor, |context, status, device, message| {
+        // Only carried over to the callback, which may be called from another thread.
+        let device = utils::Userdata::new(device as *mut std::ffi::c_void);
+        let message = message.to_owned();
+        context.callbacks.complete_future(
+            id,
+            Box::new(move || {
+                callback(
+                    status,
+                    device.as_ptr() as native::WGPUDevice,
+                    message.as_ptr(),
+                    userdata1.as_ptr(),
+                    userdata2.as_ptr(),
+                )
+            }),
+        );
+    });
+    native::WGPUFuture { id }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuBufferMapAsync2(
+    buffer: native::WGPUBuffer,
+    mode: native::WGPUMapModeFlags,
+    offset: usize,
+    size: usize,
+    callback_info: native::WGPUBufferMapCallbackInfo2,
+) -> native::WGPUFuture {
+    let context = &validate_handle!(buffer, "invalid buffer").context;
+    let callback = callback_info.callback.expect("invalid callback");
+    let userdata1 = utils::Userdata::new(callback_info.userdata1);
+    let userdata2 = utils::Userdata::new(callback_info.userdata2);
+    let id = context
+        .callbacks
+        .futures
+        .create(conv::map_callback_mode(callback_info.mode));
+
+    let callbacks = context.callbacks.clone();
+    buffer_map_async(buffer, mode, offset, size, move |status| {
+        callbacks.complete_future(
+            id,
+            Box::new(move || callback(status, userdata1.as_ptr(), userdata2.as_ptr())),
+        );
+    });
+    native::WGPUFuture { id }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuInstanceRequestAdapter2(
+    instance: native::WGPUInstance,
+    options: Option<&native::WGPURequestAdapterOptions>,
+    callback_info: native::WGPURequestAdapterCallbackInfo2,
+) -> native::WGPUFuture {
+    let context = &validate_handle!(instance, "invalid instance").context;
+    let callback = callback_info.callback.expect("invalid callback");
+    let userdata1 = utils::Userdata::new(callback_info.userdata1);
+    let userdata2 = utils::Userdata::new(callback_info.userdata2);
+    let id = context
+        .callbacks
+        .futures
+        .create(conv::map_callback_mode(callback_info.mode));
+
+    request_adapter(instance, options, |context, status, adapter, message| {
+        // Only carried over to the callback, which may be called from another thread.
+        let adapter = utils::Userdata::new(adapter as *mut std::ffi::c_void);
+        let message = message.to_owned();
+        context.callbacks.complete_future(
+            id,
+            Box::new(move || {
+                callback(
+                    status,
+                    adapter.as_ptr() as native::WGPUAdapter,
+                    message.as_ptr(),
+                    userdata1.as_ptr(),
+                    userdata2.as_ptr(),
+                )
+            }),
+        );
+    });
+    native::WGPUFuture { id }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuInstanceWaitAny(
+    instance: native::WGPUInstance,
+    future_count: usize,
+    futures: *mut native::WGPUFutureWaitInfo,
+    timeout_ns: u64,
+) -> native::WGPUWaitStatus {
+    let context = &validate_handle!(instance, "invalid instance").context;
+    let wait_infos = match future_count {
+        0 => &mut [],
+        _ => std::slice::from_raw_parts_mut(futures, future_count),
+    };
+    if !wait_infos
+        .iter()
+        .all(|wait_info| context.callbacks.futures.is_valid(wait_info.future.id))
+    {
+        return native::WGPUWaitStatus_Unknown;
+    }
+    // Too far away to be reached when it overflows.
+    let deadline = Instant::now().checked_add(Duration::from_nanos(timeout_ns));
+    let mut poll_interval = futures::MIN_POLL_INTERVAL;
+
+    loop {
+        let completions = context.callbacks.futures.completions();
+        if let Err(cause) = context.poll_all_devices(false) {
+            handle_error_fatal(cause, "wgpuInstanceWaitAny");
+        }
+        let mut any_completed = false;
+        for wait_info in wait_infos.iter_mut() {
+            if let futures::Poll::Completed(callback) =
+                context.callbacks.futures.poll(wait_info.future.id)
+            {
+                if let Some(callback) = callback {
+                    callback();
+                }
+                wait_info.completed = true as native::WGPUBool;
+                any_completed = true;
+            }
+        }
+        if any_completed {
+            return native::WGPUWaitStatus_Success;
+        }
+
+        let remaining = match deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+            None => poll_interval,
+        };
+        if remaining.is_zero() {
+            return native::WGPUWaitStatus_TimedOut;
+        }
+        context
+            .callbacks
+            .futures
+            .wait(completions, remaining.min(poll_interval));
+        poll_interval = (poll_interval * 2).min(futures::MAX_POLL_INTERVAL);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wgpuQueueOnSubmittedWorkDone2(
+    queue: native::WGPUQueue,
+    callback_info: native::WGPUQueueWorkDoneCallbackInfo2,
+) -> native::WGPUFuture {
+    let context = &validate_handle!(queue, "invalid queue").queue.context;
+    let callback = callback_info.callback.expect("invalid callback");
+    let userdata1 = utils::Userdata::new(callback_info.userdata1);
+    let userdata2 = utils::Userdata::new(callback_info.userdata2);
+    let id = context
+        .callbacks
+        .futures
+        .create(conv::map_callback_mode(callback_info.mode));
+
+    let callbacks = context.callbacks.clone();
+    queue_on_submitted_work_done(queue, move |status| {
+        callbacks.complete_future(
+            id,
+            Box::new(move || callback(status, userdata1.as_ptr(), userdata2.as_ptr())),
+        );
+    });
+    native::WGPUFuture { id }
+}
+
+#[test]
+pub fn test_bind_groups_plus_vertex_buffers() {
+    assert!(BindGroupsPlusVertexBuffersError::check(4, 8, 12).is_ok());
+    assert_eq!(
+        BindGroupsPlusVertexBuffersError::check(4, 9, 12)
+            .unwrap_err()
+            .to_string(),
+        "Pipeline uses 4 bind groups and 9 vertex buffers, more than the maxBindGroupsPlusVertexBuffers limit of 12"
+    );
+}
+
+#[test]
+pub fn test_device_lost_after_destroy() {
+    unsafe extern "C" fn record(
+        reason: native::WGPUDeviceLostReason,
+        _message: *const ::std::os::raw::c_char,
+        userdata: *mut ::std::os::raw::c_void,
+    ) {
+        unsafe { (*(userdata as *mut Vec<native::WGPUDeviceLostReason>)).push(reason) };
+    }
+
+    let mut reasons: Vec<native::WGPUDeviceLostReason> = Vec::new();
+    let mut sink = ErrorSinkRaw::new(DeviceLostCallback {
+        callback: Some(record),
+        userdata: &mut reasons as *mut _ as *mut ::std::os::raw::c_void,
+    });
+    sink.destroyed = true;
+    for _ in 0..2 {
+        sink.handle_error(Error::DeviceLost {
+            source: Box::new(wgc::device::DeviceError::Lost),
+        });
+    }
+    // The device lost closure of wgpu-core doesn't call it again either.
+    sink.take_device_lost_handler()
+        .call(native::WGPUDeviceLostReason_Destroyed, "Device destroyed");
+
+    assert_eq!(reasons, [native::WGPUDeviceLostReason_Destroyed]);
+}
+
+#[test]
+pub fn test_write_texture_zero_stride() {
+    unsafe extern "C" fn adapter_callback(
+        status: native::WGPURequestAdapterStatus,
+        adapter: native::WGPUAdapter,
+        _message: *const ::std::os::raw::c_char,
+        userdata: *mut ::std::os::raw::c_void,
+    ) {
+        if status == native::WGPURequestAdapterStatus_Success {
+            unsafe { *(userdata as *mut native::WGPUAdapter) = adapter };
+        }
+    }
+    unsafe extern "C" fn device_callback(
+        status: native::WGPURequestDeviceStatus,
+        device: native::WGPUDevice,
+        _message: *const ::std::os::raw::c_char,
+        userdata: *mut ::std::os::raw::c_void,
+    ) {
+        if status == native::WGPURequestDeviceStatus_Success {
+            unsafe { *(userdata as *mut native::WGPUDevice) = device };
+        }
+    }
+    unsafe extern "C" fn error_callback(
+        typ: native::WGPUErrorType,
+        _message: *const ::std::os::raw::c_char,
+        userdata: *mut ::std::os::raw::c_void,
+    ) {
+        unsafe { *(userdata as *mut native::WGPUErrorType) = typ };
+    }
+
+    unsafe {
+        let instance = wgpuCreateInstance(None);
+        let mut adapter: native::WGPUAdapter = std::ptr::null();
+        wgpuInstanceRequestAdapter(
+            instance,
+            None,
+            Some(adapter_callback),
+            &mut adapter as *mut _ as *mut ::std::os::raw::c_void,
+        );
+        // Needs an adapter that supports block-compressed formats.
+        if adapter.is_null()
+            || wgpuAdapterHasFeature(adapter, native::WGPUFeatureName_TextureCompressionBC) == 0
+        {
+            if !adapter.is_null() {
+                wgpuAdapterRelease(adapter);
+            }
+            wgpuInstanceRelease(instance);
+            return;
+        }
+
+        let features = [native::WGPUFeatureName_TextureCompressionBC];
+        let mut descriptor: native::WGPUDeviceDescriptor = std::mem::zeroed();
+        descriptor.requiredFeatureCount = features.len();
+        descriptor.requiredFeatures = features.as_ptr();
+        let mut device: native::WGPUDevice = std::ptr::null();
+        wgpuAdapterRequestDevice(
+            adapter,
+            Some(&descriptor),
+            Some(device_callback),
+            &mut device as *mut _ as *mut ::std::os::raw::c_void,
+        );
+        assert!(!device.is_null());
+        let queue = wgpuDeviceGetQueue(device);
+
+        let size = native::WGPUExtent3D {
+            width: 8,
+            height: 8,
+            depthOrArrayLayers: 1,
+        };
+        let texture = wgpuDeviceCreateTexture(
+            device,
+            Some(&native::WGPUTextureDescriptor {
+                nextInChain: std::ptr::null(),
+                label: std::ptr::null(),
+                usage: native::WGPUTextureUsage_CopyDst as native::WGPUTextureUsageFlags,
+                dimension: native::WGPUTextureDimension_2D,
+                size,
+                format: native::WGPUTextureFormat_BC1RGBAUnorm,
+                mipLevelCount: 1,
+                sampleCount: 1,
+                viewFormatCount: 0,
+                viewFormats: std::ptr::null(),
+            }),
+        );
+        let destination = native::WGPUImageCopyTexture {
+            nextInChain: std::ptr::null(),
+            texture,
+            mipLevel: 0,
+            origin: native::WGPUOrigin3D { x: 0, y: 0, z: 0 },
+            aspect: native::WGPUTextureAspect_All,
+        };
+        // Two rows of 4x4 blocks, 8 bytes each, with zero strides.
+        let layout = native::WGPUTextureDataLayout {
+            nextInChain: std::ptr::null(),
+            offset: 0,
+            bytesPerRow: 0,
+            rowsPerImage: 0,
+        };
+        let data = [0u8; 32];
+
+        let mut error = native::WGPUErrorType_NoError;
+        wgpuDevicePushErrorScope(device, native::WGPUErrorFilter_Validation);
+        wgpuQueueWriteTexture(
+            queue,
+            Some(&destination),
+            data.as_ptr(),
+            data.len(),
+            Some(&layout),
+            Some(&size),
+        );
+        wgpuDevicePopErrorScope(
+            device,
+            Some(error_callback),
+            &mut error as *mut _ as *mut ::std::os::raw::c_void,
+        );
+        assert_eq!(error, native::WGPUErrorType_Validation);
+
+        wgpuTextureRelease(texture);
+        wgpuQueueRelease(queue);
+        wgpuDeviceRelease(device);
+        wgpuAdapterRelease(adapter);
+        wgpuInstanceRelease(instance);
+    }
+}
@@ -0,0 +1,273 @@
+//! Validation and shader for `wgpuCommandEncoderGenerateMipmaps`.
+//!
+//! Each mip level is rendered from the previous one with a full-screen
+//! triangle, sampling it with a linear filter, one array layer at a time.
+
+use crate::native;
+use std::ops::Range;
+
+#[cfg(feature = "wgsl")]
+const SHADER: &str = "
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, in.uv);
+}
+";
+
+#[derive(Debug, thiserror::Error)]
+pub enum MipmapError {
+    #[error("Mipmaps can't be generated for {0:?}, which isn't a filterable color format")]
+    NonFilterableFormat(wgt::TextureFormat),
+    #[error("Mipmaps can only be generated for single-sampled 2D textures")]
+    UnsupportedTexture,
+    #[error("Generating mipmaps needs a texture with {0:?} usages")]
+    MissingUsage(wgt::TextureUsages),
+    #[error(
+        "Mip levels {start}..{end} are out of range for a texture with {mip_level_count} levels"
+    )]
+    LevelsOutOfRange {
+        start: u32,
+        end: u64,
+        mip_level_count: u32,
+    },
+    #[cfg(not(feature = "wgsl"))]
+    #[error("Generating mipmaps needs the `wgsl` feature")]
+    WgslDisabled,
+    #[error(transparent)]
+    Device(Box<wgc::device::DeviceError>),
+    #[error(transparent)]
+    CreateShaderModule(Box<wgc::pipeline::CreateShaderModuleError>),
+    #[error(transparent)]
+    CreateBindGroupLayout(Box<wgc::binding_model::CreateBindGroupLayoutError>),
+    #[error(transparent)]
+    CreatePipelineLayout(Box<wgc::binding_model::CreatePipelineLayoutError>),
+    #[error(transparent)]
+    CreateSampler(Box<wgc::resource::CreateSamplerError>),
+    #[error(transparent)]
+    CreateRenderPipeline(Box<wgc::pipeline::CreateRenderPipelineError>),
+    #[error(transparent)]
+    CreateTextureView(Box<wgc::resource::CreateTextureViewError>),
+    #[error(transparent)]
+    CreateBindGroup(Box<wgc::binding_model::CreateBindGroupError>),
+    #[error(transparent)]
+    CommandEncoder(Box<wgc::command::CommandEncoderError>),
+    #[error(transparent)]
+    RenderPass(Box<wgc::command::RenderPassError>),
+}
+
+// wgpu-core errors are boxed to keep `MipmapError` small.
+macro_rules! impl_from_boxed {
+    ($($variant:ident($error:ty)),* $(,)?) => {$(
+        impl From<$error> for MipmapError {
+            fn from(error: $error) -> Self {
+                MipmapError::$variant(Box::new(error))
+            }
+        }
+    )*};
+}
+
+impl_from_boxed!(
+    Device(wgc::device::DeviceError),
+    CreateShaderModule(wgc::pipeline::CreateShaderModuleError),
+    CreateBindGroupLayout(wgc::binding_model::CreateBindGroupLayoutError),
+    CreatePipelineLayout(wgc::binding_model::CreatePipelineLayoutError),
+    CreateSampler(wgc::resource::CreateSamplerError),
+    CreateRenderPipeline(wgc::pipeline::CreateRenderPipelineError),
+    CreateTextureView(wgc::resource::CreateTextureViewError),
+    CreateBindGroup(wgc::binding_model::CreateBindGroupError),
+    CommandEncoder(wgc::command::CommandEncoderError),
+    RenderPass(wgc::command::RenderPassError),
+);
+
+#[cfg(feature = "wgsl")]
+pub(crate) fn shader_source() -> Result<wgc::pipeline::ShaderModuleSource<'static>, MipmapError> {
+    Ok(wgc::pipeline::ShaderModuleSource::Wgsl(
+        std::borrow::Cow::Borrowed(SHADER),
+    ))
+}
+
+#[cfg(not(feature = "wgsl"))]
+pub(crate) fn shader_source() -> Result<wgc::pipeline::ShaderModuleSource<'static>, MipmapError> {
+    Err(MipmapError::WgslDisabled)
+}
+
+/// What `wgpuCommandEncoderGenerateMipmaps` needs to know about a texture.
+pub(crate) struct MipmapTarget {
+    pub(crate) format: wgt::TextureFormat,
+    pub(crate) dimension: native::WGPUTextureDimension,
+    pub(crate) usage: wgt::TextureUsages,
+    pub(crate) sample_count: u32,
+    pub(crate) mip_level_count: u32,
+}
+
+/// Checks that mipmaps can be generated for `target`, returning the range of
+/// levels to fill from the first one.
+pub(crate) fn level_range(
+    target: &MipmapTarget,
+    base_mip_level: u32,
+    mip_level_count: u32,
+    device_features: wgt::Features,
+) -> Result<Range<u32>, MipmapError> {
+    match target.format.sample_type(None, Some(device_features)) {
+        Some(wgt::TextureSampleType::Float { filterable: true }) => (),
+        _ => return Err(MipmapError::NonFilterableFormat(target.format)),
+    }
+    if target.dimension != native::WGPUTextureDimension_2D || target.sample_count != 1 {
+        return Err(MipmapError::UnsupportedTexture);
+    }
+    let usage = wgt::TextureUsages::TEXTURE_BINDING | wgt::TextureUsages::RENDER_ATTACHMENT;
+    if !target.usage.contains(usage) {
+        return Err(MipmapError::MissingUsage(usage));
+    }
+
+    let end = match mip_level_count {
+        native::WGPU_MIP_LEVEL_COUNT_UNDEFINED => target.mip_level_count as u64,
+        count => base_mip_level as u64 + count as u64,
+    };
+    if base_mip_level >= target.mip_level_count || end > target.mip_level_count as u64 {
+        return Err(MipmapError::LevelsOutOfRange {
+            start: base_mip_level,
+            end,
+            mip_level_count: target.mip_level_count,
+        });
+    }
+    Ok(base_mip_level..end as u32)
+}
+
+#[test]
+pub fn test_level_range() {
+    let target = |format, dimension, usage, sample_count| MipmapTarget {
+        format,
+        dimension,
+        usage,
+        sample_count,
+        mip_level_count: 4,
+    };
+    let usage = wgt::TextureUsages::TEXTURE_BINDING | wgt::TextureUsages::RENDER_ATTACHMENT;
+    let rgba = target(
+        wgt::TextureFormat::Rgba8Unorm,
+        native::WGPUTextureDimension_2D,
+        usage,
+        1,
+    );
+    let range = |target: &MipmapTarget, base_mip_level, mip_level_count| {
+        level_range(
+            target,
+            base_mip_level,
+            mip_level_count,
+            wgt::Features::empty(),
+        )
+        .map_err(|error| error.to_string())
+    };
+
+    assert_eq!(
+        range(&rgba, 0, native::WGPU_MIP_LEVEL_COUNT_UNDEFINED),
+        Ok(0..4)
+    );
+    assert_eq!(
+        range(&rgba, 1, native::WGPU_MIP_LEVEL_COUNT_UNDEFINED),
+        Ok(1..4)
+    );
+    assert_eq!(range(&rgba, 1, 2), Ok(1..3));
+    assert_eq!(range(&rgba, 3, 1), Ok(3..4));
+    assert_eq!(
+        range(&rgba, 4, native::WGPU_MIP_LEVEL_COUNT_UNDEFINED),
+        Err("Mip levels 4..4 are out of range for a texture with 4 levels".to_string())
+    );
+    assert_eq!(
+        range(&rgba, 2, 3),
+        Err("Mip levels 2..5 are out of range for a texture with 4 levels".to_string())
+    );
+    // The end of the range doesn't overflow.
+    assert_eq!(
+        range(&rgba, 1, u32::MAX - 1),
+        Err("Mip levels 1..4294967295 are out of range for a texture with 4 levels".to_string())
+    );
+
+    assert!(matches!(
+        level_range(
+            &target(
+                wgt::TextureFormat::R32Float,
+                native::WGPUTextureDimension_2D,
+                usage,
+                1
+            ),
+            0,
+            native::WGPU_MIP_LEVEL_COUNT_UNDEFINED,
+            wgt::Features::empty(),
+        ),
+        Err(MipmapError::NonFilterableFormat(
+            wgt::TextureFormat::R32Float
+        ))
+    ));
+    // 32-bit float formats are filterable with FLOAT32_FILTERABLE.
+    assert!(level_range(
+        &target(
+            wgt::TextureFormat::R32Float,
+            native::WGPUTextureDimension_2D,
+            usage,
+            1
+        ),
+        0,
+        native::WGPU_MIP_LEVEL_COUNT_UNDEFINED,
+        wgt::Features::FLOAT32_FILTERABLE,
+    )
+    .is_ok());
+    assert_eq!(
+        range(
+            &target(
+                wgt::TextureFormat::Rgba8Unorm,
+                native::WGPUTextureDimension_3D,
+                usage,
+                1
+            ),
+            0,
+            1
+        ),
+        Err("Mipmaps can only be generated for single-sampled 2D textures".to_string())
+    );
+    assert_eq!(
+        range(
+            &target(
+                wgt::TextureFormat::Rgba8Unorm,
+                native::WGPUTextureDimension_2D,
+                usage,
+                4
+            ),
+            0,
+            1
+        ),
+        Err("Mipmaps can only be generated for single-sampled 2D textures".to_string())
+    );
+    assert!(matches!(
+        level_range(
+            &target(
+                wgt::TextureFormat::Rgba8Unorm,
+                native::WGPUTextureDimension_2D,
+                wgt::TextureUsages::TEXTURE_BINDING,
+                1
+            ),
+            0,
+            1,
+            wgt::Features::empty(),
+        ),
+        Err(MipmapError::MissingUsage(missing)) if missing == usage
+    ));
+}
@@ -0,0 +1,68 @@
+//! Decoding of the naga modules passed with `WGPUShaderModuleNagaIRDescriptor`.
+//!
+//! A blob is [`MAGIC`], one byte holding the length of the naga version it was
+//! written with, that version, and then the module serialized with `bincode`,
+//! as written by the `naga-ir` tool in `tools/naga-ir`. `bincode` has no schema,
+//! so blobs from any other naga version are rejected instead of decoded into
+//! garbage. The decoded module is validated by wgpu-core like any other shader.
+
+/// Start of every naga IR blob, bump the trailing digit if the layout changes.
+pub const MAGIC: &[u8; 8] = b"NAGAIR02";
+
+/// The naga version blobs must be written with. Keep in sync with the naga tag
+/// in `Cargo.toml` and `NAGA_VERSION` in `tools/naga-ir`.
+pub const NAGA_VERSION: &str = "22.1.0";
+
+#[derive(Debug, thiserror::Error)]
+pub enum NagaIrError {
+    #[error("naga IR blob doesn't start with the expected header")]
+    InvalidHeader,
+    #[error("naga IR blob was written with naga {found}, expected naga {NAGA_VERSION}")]
+    VersionMismatch { found: String },
+    #[error("failed to decode naga IR: {0}")]
+    Decode(#[from] bincode::Error),
+}
+
+pub(crate) fn decode(data: &[u8]) -> Result<naga::Module, NagaIrError> {
+    let data = data
+        .strip_prefix(MAGIC.as_slice())
+        .ok_or(NagaIrError::InvalidHeader)?;
+    let (&len, data) = data.split_first().ok_or(NagaIrError::InvalidHeader)?;
+    if data.len() < len as usize {
+        return Err(NagaIrError::InvalidHeader);
+    }
+    let (version, module) = data.split_at(len as usize);
+    if version != NAGA_VERSION.as_bytes() {
+        return Err(NagaIrError::VersionMismatch {
+            found: String::from_utf8_lossy(version).into_owned(),
+        });
+    }
+    Ok(bincode::deserialize(module)?)
+}
+
+#[test]
+pub fn test_naga_version() {
+    let manifest = include_str!("../Cargo.toml");
+    let naga = &manifest[manifest.find("[workspace.dependencies.naga]").unwrap()..];
+    let tag = naga.lines().find(|line| line.starts_with("tag")).unwrap();
+    assert_eq!(tag, format!("tag = \"v{NAGA_VERSION}\""));
+
+    let tool = include_str!("../tools/naga-ir/src/main.rs");
+    assert!(tool.contains(&format!("const NAGA_VERSION: &str = \"{NAGA_VERSION}\";")));
+    assert!(tool.contains(&format!(
+        "const MAGIC: &[u8; 8] = b\"{}\";",
+        std::str::from_utf8(MAGIC).unwrap()
+    )));
+
+    let mut blob = MAGIC.to_vec();
+    blob.push(6);
+    blob.extend_from_slice(b"0.20.0");
+    assert!(matches!(
+        decode(&blob),
+        Err(NagaIrError::VersionMismatch { found }) if found == "0.20.0"
+    ));
+    assert!(matches!(
+        decode(&blob[..MAGIC.len() + 3]),
+        Err(NagaIrError::InvalidHeader)
+    ));
+}
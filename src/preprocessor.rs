@@ -0,0 +1,403 @@
+//! A small C-like preprocessor for WGSL, supporting `#define`, `#undef`,
+//! `#ifdef`, `#ifndef`, `#else`, `#endif` and `#include "path"`.
+//!
+//! Directives must start their line. Defines with a value replace matching
+//! identifiers in the lines that follow them.
+
+use crate::shader::CompilationMessage;
+use std::{collections::HashMap, fmt};
+
+/// Name used for the top-level source in locations.
+const ROOT_FILE: &str = "<shader>";
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum PreprocessErrorKind {
+    #[error("unknown directive `#{0}`")]
+    UnknownDirective(String),
+    #[error("`#{0}` needs a name")]
+    MissingName(&'static str),
+    #[error("`#else` without `#ifdef`")]
+    UnexpectedElse,
+    #[error("`#endif` without `#ifdef`")]
+    UnexpectedEndif,
+    #[error("`#ifdef` is never closed with `#endif`")]
+    UnterminatedConditional,
+    #[error("`#include` needs a quoted path")]
+    MissingIncludePath,
+    #[error("cannot resolve include {0:?}")]
+    IncludeNotFound(String),
+    #[error("{0:?} includes itself")]
+    IncludeCycle(String),
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("{file}:{line}: {kind}")]
+pub struct PreprocessError {
+    file: String,
+    line: u32,
+    kind: PreprocessErrorKind,
+}
+
+/// Maps the lines of preprocessed code back to the file and line they came from.
+#[derive(Debug, Default)]
+pub struct LineMap {
+    files: Vec<String>,
+    lines: Vec<(usize, u32)>,
+    /// Line of the top-level source each line came from, the `#include`
+    /// directive for included lines.
+    root_lines: Vec<u32>,
+    root: String,
+}
+
+struct Location<'a> {
+    file: &'a str,
+    line: u32,
+    column: u32,
+}
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl LineMap {
+    fn locate(&self, location: naga::SourceLocation) -> Location<'_> {
+        match self.lines.get(location.line_number as usize - 1) {
+            Some(&(file, line)) => Location {
+                file: &self.files[file],
+                line,
+                column: location.line_position,
+            },
+            None => Location {
+                file: ROOT_FILE,
+                line: location.line_number,
+                column: location.line_position,
+            },
+        }
+    }
+
+    /// Moves a location in the preprocessed code to the top-level source.
+    ///
+    /// Expanded defines can shift columns, so the span is kept within its
+    /// line. Locations in included files point at the whole `#include` line.
+    fn root_location(&self, location: naga::SourceLocation) -> Option<naga::SourceLocation> {
+        let index = (location.line_number as usize).checked_sub(1)?;
+        let &(file, _) = self.lines.get(index)?;
+        let root_line = self.root_lines[index];
+        let line_start: usize = self
+            .root
+            .split_inclusive('\n')
+            .take(root_line as usize - 1)
+            .map(str::len)
+            .sum();
+        let line = self.root[line_start..].lines().next().unwrap_or_default();
+        let (start, length) = match file {
+            0 => {
+                let start = (location.line_position as usize)
+                    .saturating_sub(1)
+                    .min(line.len());
+                (start, (location.length as usize).min(line.len() - start))
+            }
+            _ => (0, line.len()),
+        };
+
+        Some(naga::SourceLocation {
+            line_number: root_line,
+            line_position: start as u32 + 1,
+            offset: (line_start + start) as u32,
+            length: length as u32,
+        })
+    }
+
+    /// Rewrites a WGSL parsing or validation error from wgpu-core so that it
+    /// points at the original files and lines instead of the preprocessed code.
+    /// Also returns the message for `wgpuShaderModuleGetCompilationInfo`,
+    /// located in the top-level source.
+    pub(crate) fn remap_error(
+        &self,
+        error: &wgc::pipeline::CreateShaderModuleError,
+    ) -> Option<(PreprocessedShaderError, CompilationMessage)> {
+        use std::fmt::Write;
+
+        let (label, location, message) = match error {
+            wgc::pipeline::CreateShaderModuleError::Parsing(error) => {
+                let mut message = String::from("parsing error");
+                if let Some(location) = error.inner.location(&error.source) {
+                    write!(message, " at {}", self.locate(location)).unwrap();
+                }
+                write!(message, ": {}", error.inner.message()).unwrap();
+                for (span, label) in error.inner.labels() {
+                    if span.is_defined() && !label.is_empty() {
+                        let location = self.locate(span.location(&error.source));
+                        write!(message, "\n  {location}: {label}").unwrap();
+                    }
+                }
+                (&error.label, error.inner.location(&error.source), message)
+            }
+            wgc::pipeline::CreateShaderModuleError::Validation(error) => {
+                let mut message = String::from("validation error");
+                if let Some(location) = error.inner.location(&error.source) {
+                    write!(message, " at {}", self.locate(location)).unwrap();
+                }
+                write!(message, ": {}", error.inner).unwrap();
+                let mut source = std::error::Error::source(&*error.inner);
+                while let Some(cause) = source {
+                    write!(message, "\n  caused by: {cause}").unwrap();
+                    source = cause.source();
+                }
+                for (span, description) in error.inner.spans() {
+                    if span.is_defined() {
+                        let location = self.locate(span.location(&error.source));
+                        write!(message, "\n  {location}: {description}").unwrap();
+                    }
+                }
+                (&error.label, error.inner.location(&error.source), message)
+            }
+            _ => return None,
+        };
+
+        let error = PreprocessedShaderError {
+            label: label.clone().unwrap_or_default(),
+            message,
+        };
+        let message = match location.and_then(|location| self.root_location(location)) {
+            Some(location) => CompilationMessage::located(error.to_string(), &self.root, location),
+            None => CompilationMessage::new(error.to_string()),
+        };
+        Some((error, message))
+    }
+}
+
+/// A shader creation error whose locations were remapped through a [`LineMap`].
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("Shader '{label}' {message}")]
+pub struct PreprocessedShaderError {
+    label: String,
+    message: String,
+}
+
+struct Conditional {
+    /// Whether lines are currently emitted.
+    active: bool,
+    /// Whether the `#else` branch would be emitted.
+    else_active: bool,
+    seen_else: bool,
+    line: u32,
+}
+
+struct Preprocessor<'a> {
+    defines: HashMap<String, String>,
+    include: &'a mut dyn FnMut(&str) -> Option<String>,
+    include_stack: Vec<String>,
+    /// Line of the top-level `#include` being processed.
+    root_line: u32,
+    output: String,
+    line_map: LineMap,
+}
+
+impl Preprocessor<'_> {
+    fn process(&mut self, file: usize, code: &str) -> Result<(), PreprocessError> {
+        let file_name = self.line_map.files[file].clone();
+        let mut conditionals: Vec<Conditional> = Vec::new();
+
+        for (index, line) in code.lines().enumerate() {
+            let line_number = index as u32 + 1;
+            let error = |kind| PreprocessError {
+                file: file_name.clone(),
+                line: line_number,
+                kind,
+            };
+            let active = conditionals.last().map(|cond| cond.active).unwrap_or(true);
+
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                if active {
+                    let expanded = self.expand(line);
+                    self.output.push_str(&expanded);
+                    self.output.push('\n');
+                    self.line_map.lines.push((file, line_number));
+                    self.line_map.root_lines.push(match file {
+                        0 => line_number,
+                        _ => self.root_line,
+                    });
+                }
+                continue;
+            };
+
+            let directive = directive.trim();
+            let (name, argument) = directive
+                .split_once(char::is_whitespace)
+                .map_or((directive, ""), |(name, argument)| (name, argument.trim()));
+            match name {
+                "ifdef" | "ifndef" => {
+                    let define = argument.split_whitespace().next().ok_or_else(|| {
+                        error(PreprocessErrorKind::MissingName(match name {
+                            "ifdef" => "ifdef",
+                            _ => "ifndef",
+                        }))
+                    })?;
+                    let taken = self.defines.contains_key(define) == (name == "ifdef");
+                    conditionals.push(Conditional {
+                        active: active && taken,
+                        else_active: active && !taken,
+                        seen_else: false,
+                        line: line_number,
+                    });
+                }
+                "else" => match conditionals.last_mut() {
+                    Some(cond) if !cond.seen_else => {
+                        cond.active = cond.else_active;
+                        cond.seen_else = true;
+                    }
+                    _ => return Err(error(PreprocessErrorKind::UnexpectedElse)),
+                },
+                "endif" => {
+                    if conditionals.pop().is_none() {
+                        return Err(error(PreprocessErrorKind::UnexpectedEndif));
+                    }
+                }
+                _ if !active => {}
+                "define" => {
+                    let (define, value) = argument
+                        .split_once(char::is_whitespace)
+                        .map_or((argument, ""), |(define, value)| (define, value.trim()));
+                    if define.is_empty() {
+                        return Err(error(PreprocessErrorKind::MissingName("define")));
+                    }
+                    self.defines.insert(define.to_string(), value.to_string());
+                }
+                "undef" => {
+                    if argument.is_empty() {
+                        return Err(error(PreprocessErrorKind::MissingName("undef")));
+                    }
+                    self.defines.remove(argument);
+                }
+                "include" => {
+                    let path = argument
+                        .strip_prefix('"')
+                        .and_then(|path| path.strip_suffix('"'))
+                        .ok_or_else(|| error(PreprocessErrorKind::MissingIncludePath))?;
+                    if self.include_stack.iter().any(|included| included == path) {
+                        return Err(error(PreprocessErrorKind::IncludeCycle(path.to_string())));
+                    }
+                    let included = (self.include)(path).ok_or_else(|| {
+                        error(PreprocessErrorKind::IncludeNotFound(path.to_string()))
+                    })?;
+
+                    if file == 0 {
+                        self.root_line = line_number;
+                    }
+                    self.line_map.files.push(path.to_string());
+                    self.include_stack.push(path.to_string());
+                    self.process(self.line_map.files.len() - 1, &included)?;
+                    self.include_stack.pop();
+                }
+                _ => {
+                    return Err(error(PreprocessErrorKind::UnknownDirective(
+                        name.to_string(),
+                    )))
+                }
+            }
+        }
+
+        match conditionals.first() {
+            Some(cond) => Err(PreprocessError {
+                file: file_name,
+                line: cond.line,
+                kind: PreprocessErrorKind::UnterminatedConditional,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces the identifiers of `line` that have a define with a value.
+    fn expand(&self, line: &str) -> String {
+        let mut expanded = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(start) = rest.find(|c: char| c.is_alphabetic() || c == '_') {
+            let (before, ident_start) = rest.split_at(start);
+            let end = ident_start
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(ident_start.len());
+            let (ident, after) = ident_start.split_at(end);
+
+            expanded.push_str(before);
+            // Don't replace the tail of identifiers like `x1` or numbers like `1u`.
+            let glued = before.ends_with(|c: char| c.is_alphanumeric());
+            match self.defines.get(ident) {
+                Some(value) if !value.is_empty() && !glued => expanded.push_str(value),
+                _ => expanded.push_str(ident),
+            }
+            rest = after;
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+}
+
+/// Runs the preprocessor on `code`, resolving `#include`s with `include`.
+pub(crate) fn preprocess(
+    code: &str,
+    defines: HashMap<String, String>,
+    include: &mut dyn FnMut(&str) -> Option<String>,
+) -> Result<(String, LineMap), PreprocessError> {
+    let mut preprocessor = Preprocessor {
+        defines,
+        include,
+        include_stack: Vec::new(),
+        root_line: 0,
+        output: String::with_capacity(code.len()),
+        line_map: LineMap {
+            files: vec![ROOT_FILE.to_string()],
+            lines: Vec::new(),
+            root_lines: Vec::new(),
+            root: code.to_string(),
+        },
+    };
+    preprocessor.process(0, code)?;
+    Ok((preprocessor.output, preprocessor.line_map))
+}
+
+#[test]
+pub fn test_preprocess_defines_and_includes() {
+    let code = "#define SCALE 2.0\n#include \"common.wgsl\"\n#ifdef SHADOWS\nfn shadows() {}\n#else\nfn no_shadows() -> f32 { return SCALE; }\n#endif\n";
+    let mut include = |path: &str| match path {
+        "common.wgsl" => Some("#ifndef COMMON\nfn common() {}\n#endif".to_string()),
+        _ => None,
+    };
+
+    let (output, line_map) = preprocess(code, HashMap::new(), &mut include).unwrap();
+    assert_eq!(
+        output,
+        "fn common() {}\nfn no_shadows() -> f32 { return 2.0; }\n"
+    );
+    assert_eq!(line_map.lines, [(1, 2), (0, 6)]);
+    assert_eq!(line_map.files, ["<shader>", "common.wgsl"]);
+
+    // Errors are located in the top-level source, the ones of included code
+    // at the `#include` directive.
+    let code = "#include \"common.wgsl\"\nfn f() -> f32 { return undefined; }\n";
+    let locate = |included: &str| {
+        let mut include = |_: &str| Some(included.to_string());
+        let (output, line_map) = preprocess(code, HashMap::new(), &mut include).unwrap();
+        let error = wgc::pipeline::CreateShaderModuleError::Parsing(naga::error::ShaderError {
+            inner: Box::new(naga::front::wgsl::parse_str(&output).unwrap_err()),
+            source: output,
+            label: None,
+        });
+        let (_, message) = line_map.remap_error(&error).unwrap();
+        let (location, _) = message.location.unwrap();
+        (
+            location.line_number,
+            &code[location.offset as usize..][..location.length as usize],
+        )
+    };
+    assert_eq!(locate("fn g() {}"), (2, "undefined"));
+    assert_eq!(locate("fn g( {}"), (1, "#include \"common.wgsl\""));
+
+    let error = preprocess("#ifdef A\n", HashMap::new(), &mut include).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "<shader>:1: `#ifdef` is never closed with `#endif`"
+    );
+}
@@ -0,0 +1,149 @@
+//! Scope bookkeeping for `WGPUGpuProfiler`.
+//!
+//! Every scope takes two consecutive timestamp queries, one written when the
+//! scope begins and one when it ends, so scope `i` owns queries `2 * i` and
+//! `2 * i + 1`. Scopes are recorded until the profiler is resolved, and the
+//! profiler can't record again until the results have been read back.
+
+use std::ffi::CString;
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum GpuProfilerError {
+    #[error("GPU profiler needs maxScopes to be at least 1")]
+    NoScopes,
+    #[error("GPU profiler can't record more than {0} scopes before its results are read")]
+    TooManyScopes(u32),
+    #[error("GPU profiler has no open scope to end")]
+    NoOpenScope,
+    #[error("GPU profiler can't be resolved with {0} scope(s) still open")]
+    OpenScopes(usize),
+    #[error("GPU profiler was already resolved, its results must be read before recording again")]
+    AlreadyResolved,
+    #[error("GPU profiler must be resolved before reading its results")]
+    NotResolved,
+    #[error("GPU profiler results are already being read")]
+    ReadPending,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Recording,
+    Resolved,
+    Reading,
+}
+
+struct Scope {
+    label: CString,
+    depth: u32,
+}
+
+/// A scope whose duration was read back.
+pub(crate) struct ScopeResult {
+    pub(crate) label: CString,
+    pub(crate) depth: u32,
+    pub(crate) duration_ns: f64,
+}
+
+pub(crate) struct Scopes {
+    max_scopes: u32,
+    scopes: Vec<Scope>,
+    open: Vec<usize>,
+    state: State,
+}
+
+impl Scopes {
+    pub(crate) fn new(max_scopes: u32) -> Scopes {
+        Scopes {
+            max_scopes,
+            scopes: Vec::new(),
+            open: Vec::new(),
+            state: State::Recording,
+        }
+    }
+
+    /// Number of timestamp queries needed for `max_scopes` scopes.
+    pub(crate) fn query_capacity(&self) -> u32 {
+        self.max_scopes.saturating_mul(2)
+    }
+
+    fn check_recording(&self) -> Result<(), GpuProfilerError> {
+        match self.state {
+            State::Recording => Ok(()),
+            State::Resolved => Err(GpuProfilerError::AlreadyResolved),
+            State::Reading => Err(GpuProfilerError::ReadPending),
+        }
+    }
+
+    /// Opens a scope nested in the currently open ones, returning the query
+    /// to write the beginning timestamp to.
+    pub(crate) fn begin(&mut self, label: CString) -> Result<u32, GpuProfilerError> {
+        self.check_recording()?;
+        if self.scopes.len() as u32 >= self.max_scopes {
+            return Err(GpuProfilerError::TooManyScopes(self.max_scopes));
+        }
+
+        let index = self.scopes.len();
+        self.scopes.push(Scope {
+            label,
+            depth: self.open.len() as u32,
+        });
+        self.open.push(index);
+        Ok(index as u32 * 2)
+    }
+
+    /// Closes the innermost open scope, returning the query to write the
+    /// ending timestamp to.
+    pub(crate) fn end(&mut self) -> Result<u32, GpuProfilerError> {
+        self.check_recording()?;
+        let index = self.open.pop().ok_or(GpuProfilerError::NoOpenScope)?;
+        Ok(index as u32 * 2 + 1)
+    }
+
+    /// Stops recording, returning the number of queries to resolve.
+    pub(crate) fn resolve(&mut self) -> Result<u32, GpuProfilerError> {
+        self.check_recording()?;
+        if !self.open.is_empty() {
+            return Err(GpuProfilerError::OpenScopes(self.open.len()));
+        }
+
+        self.state = State::Resolved;
+        Ok(self.scopes.len() as u32 * 2)
+    }
+
+    /// Starts reading the resolved queries back, returning their number.
+    pub(crate) fn start_reading(&mut self) -> Result<u32, GpuProfilerError> {
+        match self.state {
+            State::Resolved => {
+                self.state = State::Reading;
+                Ok(self.scopes.len() as u32 * 2)
+            }
+            State::Recording => Err(GpuProfilerError::NotResolved),
+            State::Reading => Err(GpuProfilerError::ReadPending),
+        }
+    }
+
+    /// Turns the timestamps read back into durations, and starts recording
+    /// again. `timestamps` is `None` if reading them failed.
+    pub(crate) fn finish_reading(
+        &mut self,
+        timestamps: Option<&[u64]>,
+        timestamp_period: f32,
+    ) -> Vec<ScopeResult> {
+        let scopes = std::mem::take(&mut self.scopes);
+        self.state = State::Recording;
+
+        let Some(timestamps) = timestamps else {
+            return Vec::new();
+        };
+        scopes
+            .into_iter()
+            .zip(timestamps.chunks_exact(2))
+            .map(|(scope, timestamps)| ScopeResult {
+                label: scope.label,
+                depth: scope.depth,
+                duration_ns: timestamps[1].wrapping_sub(timestamps[0]) as f64
+                    * timestamp_period as f64,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,911 @@
+use crate::native;
+#[cfg(feature = "glsl")]
+use std::borrow::Cow;
+use std::collections::HashMap;
+#[cfg(feature = "shader_inspection")]
+use std::ffi::CString;
+#[cfg(all(feature = "wgsl", feature = "shader_inspection"))]
+use std::sync::OnceLock;
+
+/// Source of a shader module, kept around so the module can be inspected
+/// after creation, see `wgpuShaderModuleGetTranslatedSource` and
+/// `wgpuShaderModuleGetReflection`. Only kept with the `shader_inspection`
+/// feature, as it costs a copy of the source for every module.
+#[cfg(feature = "shader_inspection")]
+pub(crate) enum ShaderSource {
+    #[cfg(feature = "wgsl")]
+    Wgsl {
+        code: String,
+        /// Parsed on first use, pipeline creation included, then reused.
+        module: OnceLock<Result<Box<naga::Module>, String>>,
+    },
+    Naga(Box<naga::Module>),
+}
+
+#[cfg(feature = "shader_inspection")]
+impl ShaderSource {
+    pub(crate) fn from_wgc(source: &wgc::pipeline::ShaderModuleSource) -> Option<ShaderSource> {
+        match source {
+            #[cfg(feature = "wgsl")]
+            wgc::pipeline::ShaderModuleSource::Wgsl(code) => Some(ShaderSource::Wgsl {
+                code: code.to_string(),
+                module: OnceLock::new(),
+            }),
+            wgc::pipeline::ShaderModuleSource::Naga(module) => {
+                Some(ShaderSource::Naga(Box::new(module.as_ref().clone())))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the naga representation of the shader, parsing it if needed.
+    pub(crate) fn module(&self) -> Result<&naga::Module, TranslationError> {
+        match self {
+            #[cfg(feature = "wgsl")]
+            ShaderSource::Wgsl { code, module } => module
+                .get_or_init(|| {
+                    naga::front::wgsl::parse_str(code)
+                        .map(Box::new)
+                        .map_err(|err| err.emit_to_string(code))
+                })
+                .as_deref()
+                .map_err(|err| TranslationError::Wgsl(err.clone())),
+            ShaderSource::Naga(module) => Ok(module),
+        }
+    }
+}
+
+#[cfg(feature = "shader_inspection")]
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum TranslationTarget {
+    SpirV,
+    Msl,
+    Hlsl,
+    Glsl { es: bool },
+}
+
+#[cfg(feature = "shader_inspection")]
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum TranslationError {
+    #[cfg(feature = "wgsl")]
+    #[error("{0}")]
+    Wgsl(String),
+    #[error("entry point {0:?} not found")]
+    EntryPointNotFound(String),
+    #[error("GLSL output needs an entry point when the module has more than one")]
+    AmbiguousEntryPoint,
+    #[error(transparent)]
+    Validation(#[from] naga::WithSpan<naga::valid::ValidationError>),
+    #[error(transparent)]
+    Overrides(#[from] naga::back::pipeline_constants::PipelineConstantError),
+    #[error(transparent)]
+    SpirV(#[from] naga::back::spv::Error),
+    #[error(transparent)]
+    Msl(#[from] naga::back::msl::Error),
+    #[error(transparent)]
+    Hlsl(#[from] naga::back::hlsl::Error),
+    #[error(transparent)]
+    Glsl(#[from] naga::back::glsl::Error),
+}
+
+/// A message reported by `wgpuShaderModuleGetCompilationInfo`.
+pub(crate) struct CompilationMessage {
+    pub(crate) message: String,
+    /// Location in bytes and in UTF-16 code units, if the message points into the source.
+    pub(crate) location: Option<(naga::SourceLocation, naga::SourceLocation)>,
+}
+
+impl CompilationMessage {
+    pub(crate) fn new(message: String) -> Self {
+        CompilationMessage {
+            message,
+            location: None,
+        }
+    }
+
+    pub(crate) fn located(message: String, source: &str, location: naga::SourceLocation) -> Self {
+        let start = location.offset as usize;
+        let end = start + location.length as usize;
+        let line_start = source
+            .get(..start)
+            .and_then(|prefix| prefix.rfind('\n'))
+            .map_or(0, |pos| pos + 1);
+        let utf16_len = |range: std::ops::Range<usize>| {
+            source
+                .get(range)
+                .map_or(0, |text| text.encode_utf16().count() as u32)
+        };
+
+        let utf16_location = naga::SourceLocation {
+            line_number: location.line_number,
+            line_position: utf16_len(line_start..start) + 1,
+            offset: utf16_len(0..start),
+            length: utf16_len(start..end),
+        };
+        CompilationMessage {
+            message,
+            location: Some((location, utf16_location)),
+        }
+    }
+}
+
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!("\n  caused by: {cause}"));
+        source = cause.source();
+    }
+    message
+}
+
+/// Splits a shader creation error from wgpu-core into compilation messages,
+/// locating parsing and validation errors in the source.
+pub(crate) fn compilation_messages(
+    error: &wgc::pipeline::CreateShaderModuleError,
+) -> Vec<CompilationMessage> {
+    let message = match error {
+        #[cfg(feature = "wgsl")]
+        wgc::pipeline::CreateShaderModuleError::Parsing(error) => {
+            let message = error.inner.message().to_string();
+            match error.inner.location(&error.source) {
+                Some(location) => CompilationMessage::located(message, &error.source, location),
+                None => CompilationMessage::new(message),
+            }
+        }
+        wgc::pipeline::CreateShaderModuleError::Validation(error) => {
+            let message = error_chain(error.inner.as_inner());
+            match error.inner.location(&error.source) {
+                Some(location) => CompilationMessage::located(message, &error.source, location),
+                None => CompilationMessage::new(message),
+            }
+        }
+        _ => CompilationMessage::new(error_chain(error)),
+    };
+    vec![message]
+}
+
+/// GLSL parsing errors along with the source they point into.
+#[cfg(feature = "glsl")]
+#[derive(Debug)]
+pub struct GlslParseErrors {
+    source: String,
+    errors: Vec<naga::front::glsl::Error>,
+}
+
+#[cfg(feature = "glsl")]
+impl GlslParseErrors {
+    pub(crate) fn new(errors: naga::front::glsl::ParseErrors, source: &str) -> Self {
+        GlslParseErrors {
+            source: source.to_string(),
+            errors: errors.errors,
+        }
+    }
+
+    pub(crate) fn compilation_messages(&self) -> Vec<CompilationMessage> {
+        self.errors
+            .iter()
+            .map(|error| match error.meta.is_defined() {
+                true => CompilationMessage::located(
+                    error.kind.to_string(),
+                    &self.source,
+                    error.meta.location(&self.source),
+                ),
+                false => CompilationMessage::new(error.kind.to_string()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "glsl")]
+impl std::fmt::Display for GlslParseErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, error) in self.errors.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            if error.meta.is_defined() {
+                let location = error.meta.location(&self.source);
+                write!(f, "{}:{}: ", location.line_number, location.line_position)?;
+            }
+            write!(f, "{}", error.kind)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "glsl")]
+impl std::error::Error for GlslParseErrors {}
+
+/// Keeps the part of a combined GLSL source that belongs to `stage`.
+///
+/// Sections start with `#pragma shader_stage(vertex)`, `(fragment)` or
+/// `(compute)` and run until the next one, code before the first section is
+/// shared. Other sections are blanked out rather than removed, so that byte
+/// offsets and lines of errors still match the original source.
+///
+/// naga only sees the selected part, so a section missing the `#version`
+/// directive that the other sections have, or repeating the one of the shared
+/// code, is reported here with its location in the combined source.
+#[cfg(feature = "glsl")]
+pub(crate) fn select_glsl_stage(
+    code: &str,
+    stage: naga::ShaderStage,
+) -> Result<Cow<'_, str>, naga::front::glsl::ParseErrors> {
+    fn section_stage(line: &str) -> Option<naga::ShaderStage> {
+        let pragma = line.trim_start().strip_prefix('#')?.trim_start();
+        let arguments = pragma.strip_prefix("pragma")?;
+        if !arguments.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let name = arguments
+            .trim_start()
+            .strip_prefix("shader_stage")?
+            .trim_start()
+            .strip_prefix('(')?
+            .trim_end()
+            .strip_suffix(')')?;
+        match name.trim() {
+            "vertex" => Some(naga::ShaderStage::Vertex),
+            "fragment" => Some(naga::ShaderStage::Fragment),
+            "compute" => Some(naga::ShaderStage::Compute),
+            _ => None,
+        }
+    }
+
+    fn is_version(line: &str) -> bool {
+        line.trim_start()
+            .strip_prefix('#')
+            .and_then(|directive| directive.trim_start().strip_prefix("version"))
+            .is_some_and(|arguments| arguments.starts_with(char::is_whitespace))
+    }
+
+    fn error(message: String, meta: naga::Span) -> naga::front::glsl::Error {
+        naga::front::glsl::Error {
+            kind: naga::front::glsl::ErrorKind::SemanticError(message.into()),
+            meta,
+        }
+    }
+
+    if !code.lines().any(|line| section_stage(line).is_some()) {
+        return Ok(Cow::Borrowed(code));
+    }
+
+    let stage_name = match stage {
+        naga::ShaderStage::Vertex => "vertex",
+        naga::ShaderStage::Fragment => "fragment",
+        naga::ShaderStage::Compute => "compute",
+    };
+
+    let mut selected = String::with_capacity(code.len());
+    let mut active = true;
+    let mut offset = 0;
+    // First `#pragma shader_stage` of the selected stage.
+    let mut stage_start = None;
+    // `#version` directives seen by the selected stage, and whether
+    // the other sections have any.
+    let mut versions = Vec::new();
+    let mut other_versions = false;
+    for line in code.split_inclusive('\n') {
+        let start = offset + line.len() - line.trim_start().len();
+        let span = naga::Span::new(start as u32, (offset + line.trim_end().len()) as u32);
+        offset += line.len();
+
+        let section = section_stage(line);
+        if let Some(section) = section {
+            active = section == stage;
+            if active && stage_start.is_none() {
+                stage_start = Some(span);
+            }
+        } else if is_version(line) {
+            match active {
+                true => versions.push((span, line.trim())),
+                false => other_versions = true,
+            }
+        }
+
+        match active && section.is_none() {
+            true => selected.push_str(line),
+            // Keep the byte length of the line, multi-byte characters included.
+            false => selected.extend(line.bytes().map(|byte| match byte {
+                b'\n' | b'\r' => byte as char,
+                _ => ' ',
+            })),
+        }
+    }
+
+    let mut errors = Vec::new();
+    match (versions.as_slice(), stage_start) {
+        ([], Some(stage_start)) if other_versions => errors.push(error(
+            format!(
+                "the {stage_name} section has no #version directive, unlike the other sections"
+            ),
+            stage_start,
+        )),
+        ([(_, first), repeated @ ..], _) => {
+            for &(span, _) in repeated {
+                errors.push(error(
+                    format!("#version directive repeated, the {stage_name} stage already uses `{first}`"),
+                    span,
+                ));
+            }
+        }
+        _ => {}
+    }
+
+    match errors.is_empty() {
+        true => Ok(Cow::Owned(selected)),
+        false => Err(naga::front::glsl::ParseErrors { errors }),
+    }
+}
+
+/// Scalar types of the overrides of `module`, keyed like pipeline constants:
+/// by id for overrides that have one, by name otherwise.
+pub(crate) fn override_scalars(module: &naga::Module) -> HashMap<String, naga::Scalar> {
+    module
+        .overrides
+        .iter()
+        .filter_map(|(_, override_)| {
+            let key = match (override_.id, &override_.name) {
+                (Some(id), _) => id.to_string(),
+                (None, Some(name)) => name.clone(),
+                (None, None) => return None,
+            };
+            match module.types[override_.ty].inner {
+                naga::TypeInner::Scalar(scalar) => Some((key, scalar)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Scalar types of the overrides of a shader about to be created, to check
+/// pipeline constants against. WGSL is only parsed here if it may declare
+/// overrides. `None` if they are unknown, e.g. for SPIR-V passed through to
+/// the backend or WGSL that fails to parse.
+pub(crate) fn source_overrides(
+    source: &wgc::pipeline::ShaderModuleSource,
+) -> Option<HashMap<String, naga::Scalar>> {
+    match source {
+        #[cfg(feature = "wgsl")]
+        wgc::pipeline::ShaderModuleSource::Wgsl(code) if !code.contains("override") => {
+            Some(HashMap::new())
+        }
+        #[cfg(feature = "wgsl")]
+        wgc::pipeline::ShaderModuleSource::Wgsl(code) => naga::front::wgsl::parse_str(code)
+            .ok()
+            .map(|module| override_scalars(&module)),
+        wgc::pipeline::ShaderModuleSource::Naga(module) => Some(override_scalars(module)),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "shader_inspection")]
+fn validate(module: &naga::Module) -> Result<naga::valid::ModuleInfo, TranslationError> {
+    Ok(naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(module)?)
+}
+
+/// Runs the naga backend writer for `target` on `module`, restricted to
+/// `entry_point` if given. Overrides are replaced by their default values.
+///
+/// The writers use naga's default options (SPIR-V 1.0, MSL 1.0, HLSL shader
+/// model 5.1) or GLSL 4.30 / ES 3.10, not the options the device's backend
+/// compiles pipelines with: wgpu-core doesn't expose those, and the binding
+/// maps among them depend on the pipeline layout. The result can therefore
+/// differ from what the driver is given.
+///
+/// SPIR-V is returned as native-endian words, the other targets as text.
+#[cfg(feature = "shader_inspection")]
+pub(crate) fn translate(
+    module: &naga::Module,
+    target: TranslationTarget,
+    entry_point: Option<&str>,
+) -> Result<Vec<u8>, TranslationError> {
+    let mut module = module.clone();
+    if let Some(entry_point) = entry_point {
+        module.entry_points.retain(|ep| ep.name == entry_point);
+        if module.entry_points.is_empty() {
+            return Err(TranslationError::EntryPointNotFound(
+                entry_point.to_string(),
+            ));
+        }
+    }
+
+    let info = validate(&module)?;
+    let (module, info) =
+        naga::back::pipeline_constants::process_overrides(&module, &info, &Default::default())?;
+
+    match target {
+        TranslationTarget::SpirV => {
+            let words = naga::back::spv::write_vec(&module, &info, &Default::default(), None)?;
+            Ok(words.iter().flat_map(|word| word.to_ne_bytes()).collect())
+        }
+        TranslationTarget::Msl => {
+            let (source, _) = naga::back::msl::write_string(
+                &module,
+                &info,
+                &Default::default(),
+                &Default::default(),
+            )?;
+            Ok(source.into_bytes())
+        }
+        TranslationTarget::Hlsl => {
+            let mut source = String::new();
+            naga::back::hlsl::Writer::new(&mut source, &Default::default())
+                .write(&module, &info, None)?;
+            Ok(source.into_bytes())
+        }
+        TranslationTarget::Glsl { es } => {
+            let entry_point = match module.entry_points.as_slice() {
+                [entry_point] => entry_point,
+                _ => return Err(TranslationError::AmbiguousEntryPoint),
+            };
+            let options = naga::back::glsl::Options {
+                version: match es {
+                    true => naga::back::glsl::Version::new_gles(310),
+                    false => naga::back::glsl::Version::Desktop(430),
+                },
+                ..Default::default()
+            };
+            let pipeline_options = naga::back::glsl::PipelineOptions {
+                shader_stage: entry_point.stage,
+                entry_point: entry_point.name.clone(),
+                multiview: None,
+            };
+
+            let mut source = String::new();
+            naga::back::glsl::Writer::new(
+                &mut source,
+                &module,
+                &info,
+                &options,
+                &pipeline_options,
+                Default::default(),
+            )?
+            .write()?;
+            Ok(source.into_bytes())
+        }
+    }
+}
+
+/// Owns the arrays and strings a `WGPUShaderReflection` points to.
+#[cfg(feature = "shader_inspection")]
+pub(crate) struct Reflection {
+    names: Vec<CString>,
+    bindings: Vec<Vec<native::WGPUShaderBindingReflection>>,
+    vertex_attributes: Vec<Vec<native::WGPUShaderVertexAttributeReflection>>,
+    entry_points: Vec<native::WGPUShaderEntryPointReflection>,
+    overrides: Vec<native::WGPUShaderOverrideReflection>,
+}
+
+#[cfg(feature = "shader_inspection")]
+impl Reflection {
+    pub(crate) fn to_native(&self) -> native::WGPUShaderReflection {
+        native::WGPUShaderReflection {
+            entryPointCount: self.entry_points.len(),
+            entryPoints: self.entry_points.as_ptr(),
+            overrideCount: self.overrides.len(),
+            overrides: self.overrides.as_ptr(),
+        }
+    }
+
+    fn name(&mut self, name: Option<&str>) -> *const std::ffi::c_char {
+        match name.and_then(|name| CString::new(name).ok()) {
+            Some(name) => {
+                let ptr = name.as_ptr();
+                self.names.push(name);
+                ptr
+            }
+            None => std::ptr::null(),
+        }
+    }
+}
+
+/// Reflection needs the source of the module, which is only kept with the
+/// `shader_inspection` feature.
+#[cfg(not(feature = "shader_inspection"))]
+pub(crate) enum Reflection {}
+
+#[cfg(not(feature = "shader_inspection"))]
+impl Reflection {
+    pub(crate) fn to_native(&self) -> native::WGPUShaderReflection {
+        match *self {}
+    }
+}
+
+/// Collects the entry points, the bindings they use and the overrides of `module`.
+#[cfg(feature = "shader_inspection")]
+pub(crate) fn reflect(module: &naga::Module) -> Result<Reflection, TranslationError> {
+    let info = validate(module)?;
+    let mut reflection = Reflection {
+        names: Vec::new(),
+        bindings: Vec::new(),
+        vertex_attributes: Vec::new(),
+        entry_points: Vec::new(),
+        overrides: Vec::new(),
+    };
+
+    for (index, entry_point) in module.entry_points.iter().enumerate() {
+        let function_info = info.get_entry_point(index);
+        let mut bindings = Vec::new();
+        for (handle, var) in module.global_variables.iter() {
+            let Some(ref resource_binding) = var.binding else {
+                continue;
+            };
+            if function_info[handle].is_empty() {
+                continue;
+            }
+            let mut binding = reflect_binding(module, var);
+            binding.name = reflection.name(var.name.as_deref());
+            binding.group = resource_binding.group;
+            binding.binding = resource_binding.binding;
+            bindings.push(binding);
+        }
+        bindings.sort_by_key(|binding| (binding.group, binding.binding));
+
+        let mut vertex_attributes = Vec::new();
+        if entry_point.stage == naga::ShaderStage::Vertex {
+            for argument in entry_point.function.arguments.iter() {
+                match argument.binding {
+                    Some(ref binding) => vertex_attributes.extend(reflect_vertex_attribute(
+                        module,
+                        &mut reflection,
+                        argument.name.as_deref(),
+                        argument.ty,
+                        binding,
+                    )),
+                    None => {
+                        if let naga::TypeInner::Struct { ref members, .. } =
+                            module.types[argument.ty].inner
+                        {
+                            for member in members {
+                                if let Some(ref binding) = member.binding {
+                                    vertex_attributes.extend(reflect_vertex_attribute(
+                                        module,
+                                        &mut reflection,
+                                        member.name.as_deref(),
+                                        member.ty,
+                                        binding,
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            vertex_attributes.sort_by_key(|attribute| attribute.shaderLocation);
+        }
+
+        let name = reflection.name(Some(&entry_point.name));
+        reflection
+            .entry_points
+            .push(native::WGPUShaderEntryPointReflection {
+                name,
+                stage: match entry_point.stage {
+                    naga::ShaderStage::Vertex => native::WGPUShaderStage_Vertex,
+                    naga::ShaderStage::Fragment => native::WGPUShaderStage_Fragment,
+                    naga::ShaderStage::Compute => native::WGPUShaderStage_Compute,
+                },
+                workgroupSize: entry_point.workgroup_size,
+                bindingCount: bindings.len(),
+                bindings: bindings.as_ptr(),
+                vertexAttributeCount: vertex_attributes.len(),
+                vertexAttributes: vertex_attributes.as_ptr(),
+            });
+        reflection.bindings.push(bindings);
+        reflection.vertex_attributes.push(vertex_attributes);
+    }
+
+    for (_, override_) in module.overrides.iter() {
+        let default_value = override_
+            .init
+            .and_then(|init| match module.global_expressions[init] {
+                naga::Expression::Literal(literal) => literal_to_f64(literal),
+                _ => None,
+            });
+        let name = reflection.name(override_.name.as_deref());
+        reflection
+            .overrides
+            .push(native::WGPUShaderOverrideReflection {
+                name,
+                id: override_
+                    .id
+                    .map_or(native::WGPU_LIMIT_U32_UNDEFINED, u32::from),
+                type_: match module.types[override_.ty].inner {
+                    naga::TypeInner::Scalar(scalar) => match (scalar.kind, scalar.width) {
+                        (naga::ScalarKind::Bool, _) => native::WGPUShaderOverrideType_Bool,
+                        (naga::ScalarKind::Float, 2) => native::WGPUShaderOverrideType_Float16,
+                        (naga::ScalarKind::Float, 4) => native::WGPUShaderOverrideType_Float32,
+                        (naga::ScalarKind::Float, 8) => native::WGPUShaderOverrideType_Float64,
+                        (naga::ScalarKind::Sint, 4) => native::WGPUShaderOverrideType_Sint32,
+                        (naga::ScalarKind::Uint, 4) => native::WGPUShaderOverrideType_Uint32,
+                        _ => native::WGPUShaderOverrideType_Undefined,
+                    },
+                    _ => native::WGPUShaderOverrideType_Undefined,
+                },
+                hasDefaultValue: default_value.is_some() as native::WGPUBool,
+                defaultValue: default_value.unwrap_or(0.0),
+            });
+    }
+
+    Ok(reflection)
+}
+
+#[cfg(feature = "shader_inspection")]
+fn reflect_binding(
+    module: &naga::Module,
+    var: &naga::GlobalVariable,
+) -> native::WGPUShaderBindingReflection {
+    let mut binding = native::WGPUShaderBindingReflection {
+        name: std::ptr::null(),
+        group: 0,
+        binding: 0,
+        type_: native::WGPUShaderBindingType_Undefined,
+        bufferType: native::WGPUBufferBindingType_Undefined,
+        minBindingSize: 0,
+        samplerType: native::WGPUSamplerBindingType_Undefined,
+        textureSampleType: native::WGPUTextureSampleType_Undefined,
+        viewDimension: native::WGPUTextureViewDimension_Undefined,
+        multisampled: false as native::WGPUBool,
+        storageTextureAccess: native::WGPUStorageTextureAccess_Undefined,
+        storageTextureFormat: native::WGPUTextureFormat_Undefined,
+        count: 0,
+    };
+
+    let mut ty = var.ty;
+    if let naga::TypeInner::BindingArray { base, size } = module.types[ty].inner {
+        binding.count = match size {
+            naga::ArraySize::Constant(size) => size.get(),
+            naga::ArraySize::Dynamic => native::WGPU_LIMIT_U32_UNDEFINED,
+        };
+        ty = base;
+    }
+
+    match var.space {
+        naga::AddressSpace::Uniform => {
+            binding.type_ = native::WGPUShaderBindingType_Buffer;
+            binding.bufferType = native::WGPUBufferBindingType_Uniform;
+            binding.minBindingSize = module.types[ty].inner.size(module.to_ctx()) as u64;
+            return binding;
+        }
+        naga::AddressSpace::Storage { access } => {
+            binding.type_ = native::WGPUShaderBindingType_Buffer;
+            binding.bufferType = match access.contains(naga::StorageAccess::STORE) {
+                true => native::WGPUBufferBindingType_Storage,
+                false => native::WGPUBufferBindingType_ReadOnlyStorage,
+            };
+            binding.minBindingSize = module.types[ty].inner.size(module.to_ctx()) as u64;
+            return binding;
+        }
+        _ => {}
+    }
+
+    match module.types[ty].inner {
+        naga::TypeInner::Sampler { comparison } => {
+            binding.type_ = native::WGPUShaderBindingType_Sampler;
+            binding.samplerType = match comparison {
+                true => native::WGPUSamplerBindingType_Comparison,
+                false => native::WGPUSamplerBindingType_Filtering,
+            };
+        }
+        naga::TypeInner::Image {
+            dim,
+            arrayed,
+            class,
+        } => {
+            binding.viewDimension = match (dim, arrayed) {
+                (naga::ImageDimension::D1, _) => native::WGPUTextureViewDimension_1D,
+                (naga::ImageDimension::D2, false) => native::WGPUTextureViewDimension_2D,
+                (naga::ImageDimension::D2, true) => native::WGPUTextureViewDimension_2DArray,
+                (naga::ImageDimension::D3, _) => native::WGPUTextureViewDimension_3D,
+                (naga::ImageDimension::Cube, false) => native::WGPUTextureViewDimension_Cube,
+                (naga::ImageDimension::Cube, true) => native::WGPUTextureViewDimension_CubeArray,
+            };
+            match class {
+                naga::ImageClass::Sampled { kind, multi } => {
+                    binding.type_ = native::WGPUShaderBindingType_Texture;
+                    binding.textureSampleType = match kind {
+                        naga::ScalarKind::Sint => native::WGPUTextureSampleType_Sint,
+                        naga::ScalarKind::Uint => native::WGPUTextureSampleType_Uint,
+                        _ => native::WGPUTextureSampleType_Float,
+                    };
+                    binding.multisampled = multi as native::WGPUBool;
+                }
+                naga::ImageClass::Depth { multi } => {
+                    binding.type_ = native::WGPUShaderBindingType_Texture;
+                    binding.textureSampleType = native::WGPUTextureSampleType_Depth;
+                    binding.multisampled = multi as native::WGPUBool;
+                }
+                naga::ImageClass::Storage { format, access } => {
+                    binding.type_ = native::WGPUShaderBindingType_StorageTexture;
+                    binding.storageTextureAccess = match (
+                        access.contains(naga::StorageAccess::LOAD),
+                        access.contains(naga::StorageAccess::STORE),
+                    ) {
+                        (true, true) => native::WGPUStorageTextureAccess_ReadWrite,
+                        (true, false) => native::WGPUStorageTextureAccess_ReadOnly,
+                        _ => native::WGPUStorageTextureAccess_WriteOnly,
+                    };
+                    binding.storageTextureFormat =
+                        crate::conv::to_native_texture_format(map_storage_format(format))
+                            .unwrap_or(native::WGPUTextureFormat_Undefined);
+                }
+            }
+        }
+        naga::TypeInner::AccelerationStructure => {
+            binding.type_ = native::WGPUShaderBindingType_AccelerationStructure;
+        }
+        _ => {}
+    }
+    binding
+}
+
+#[cfg(feature = "shader_inspection")]
+fn reflect_vertex_attribute(
+    module: &naga::Module,
+    reflection: &mut Reflection,
+    name: Option<&str>,
+    ty: naga::Handle<naga::Type>,
+    binding: &naga::Binding,
+) -> Option<native::WGPUShaderVertexAttributeReflection> {
+    let naga::Binding::Location { location, .. } = *binding else {
+        return None;
+    };
+    let (scalar, size) = match module.types[ty].inner {
+        naga::TypeInner::Scalar(scalar) => (scalar, 1),
+        naga::TypeInner::Vector { scalar, size } => (scalar, size as u8),
+        _ => return None,
+    };
+
+    Some(native::WGPUShaderVertexAttributeReflection {
+        name: reflection.name(name),
+        shaderLocation: location,
+        format: match (scalar.kind, scalar.width, size) {
+            (naga::ScalarKind::Float, 4, 1) => native::WGPUVertexFormat_Float32,
+            (naga::ScalarKind::Float, 4, 2) => native::WGPUVertexFormat_Float32x2,
+            (naga::ScalarKind::Float, 4, 3) => native::WGPUVertexFormat_Float32x3,
+            (naga::ScalarKind::Float, 4, 4) => native::WGPUVertexFormat_Float32x4,
+            (naga::ScalarKind::Float, 2, 2) => native::WGPUVertexFormat_Float16x2,
+            (naga::ScalarKind::Float, 2, 4) => native::WGPUVertexFormat_Float16x4,
+            (naga::ScalarKind::Uint, 4, 1) => native::WGPUVertexFormat_Uint32,
+            (naga::ScalarKind::Uint, 4, 2) => native::WGPUVertexFormat_Uint32x2,
+            (naga::ScalarKind::Uint, 4, 3) => native::WGPUVertexFormat_Uint32x3,
+            (naga::ScalarKind::Uint, 4, 4) => native::WGPUVertexFormat_Uint32x4,
+            (naga::ScalarKind::Sint, 4, 1) => native::WGPUVertexFormat_Sint32,
+            (naga::ScalarKind::Sint, 4, 2) => native::WGPUVertexFormat_Sint32x2,
+            (naga::ScalarKind::Sint, 4, 3) => native::WGPUVertexFormat_Sint32x3,
+            (naga::ScalarKind::Sint, 4, 4) => native::WGPUVertexFormat_Sint32x4,
+            _ => native::WGPUVertexFormat_Undefined,
+        },
+    })
+}
+
+#[cfg(feature = "shader_inspection")]
+fn literal_to_f64(literal: naga::Literal) -> Option<f64> {
+    Some(match literal {
+        naga::Literal::F64(value) | naga::Literal::AbstractFloat(value) => value,
+        naga::Literal::F32(value) => value as f64,
+        naga::Literal::U32(value) => value as f64,
+        naga::Literal::I32(value) => value as f64,
+        naga::Literal::U64(value) => value as f64,
+        naga::Literal::I64(value) | naga::Literal::AbstractInt(value) => value as f64,
+        naga::Literal::Bool(value) => value as u8 as f64,
+    })
+}
+
+#[cfg(feature = "shader_inspection")]
+fn map_storage_format(format: naga::StorageFormat) -> wgt::TextureFormat {
+    use naga::StorageFormat as Sf;
+    use wgt::TextureFormat as Tf;
+
+    match format {
+        Sf::R8Unorm => Tf::R8Unorm,
+        Sf::R8Snorm => Tf::R8Snorm,
+        Sf::R8Uint => Tf::R8Uint,
+        Sf::R8Sint => Tf::R8Sint,
+        Sf::R16Uint => Tf::R16Uint,
+        Sf::R16Sint => Tf::R16Sint,
+        Sf::R16Float => Tf::R16Float,
+        Sf::Rg8Unorm => Tf::Rg8Unorm,
+        Sf::Rg8Snorm => Tf::Rg8Snorm,
+        Sf::Rg8Uint => Tf::Rg8Uint,
+        Sf::Rg8Sint => Tf::Rg8Sint,
+        Sf::R32Uint => Tf::R32Uint,
+        Sf::R32Sint => Tf::R32Sint,
+        Sf::R32Float => Tf::R32Float,
+        Sf::Rg16Uint => Tf::Rg16Uint,
+        Sf::Rg16Sint => Tf::Rg16Sint,
+        Sf::Rg16Float => Tf::Rg16Float,
+        Sf::Rgba8Unorm => Tf::Rgba8Unorm,
+        Sf::Rgba8Snorm => Tf::Rgba8Snorm,
+        Sf::Rgba8Uint => Tf::Rgba8Uint,
+        Sf::Rgba8Sint => Tf::Rgba8Sint,
+        Sf::Bgra8Unorm => Tf::Bgra8Unorm,
+        Sf::Rgb10a2Uint => Tf::Rgb10a2Uint,
+        Sf::Rgb10a2Unorm => Tf::Rgb10a2Unorm,
+        Sf::Rg11b10Float => Tf::Rg11b10Float,
+        Sf::Rg32Uint => Tf::Rg32Uint,
+        Sf::Rg32Sint => Tf::Rg32Sint,
+        Sf::Rg32Float => Tf::Rg32Float,
+        Sf::Rgba16Uint => Tf::Rgba16Uint,
+        Sf::Rgba16Sint => Tf::Rgba16Sint,
+        Sf::Rgba16Float => Tf::Rgba16Float,
+        Sf::Rgba32Uint => Tf::Rgba32Uint,
+        Sf::Rgba32Sint => Tf::Rgba32Sint,
+        Sf::Rgba32Float => Tf::Rgba32Float,
+        Sf::R16Unorm => Tf::R16Unorm,
+        Sf::R16Snorm => Tf::R16Snorm,
+        Sf::Rg16Unorm => Tf::Rg16Unorm,
+        Sf::Rg16Snorm => Tf::Rg16Snorm,
+        Sf::Rgba16Unorm => Tf::Rgba16Unorm,
+        Sf::Rgba16Snorm => Tf::Rgba16Snorm,
+    }
+}
+
+#[cfg(feature = "glsl")]
+#[test]
+pub fn test_select_glsl_stage() {
+    let code = "#version 450\n#pragma shader_stage(vertex)\nvoid main() { gl_Position = vec4(0.0); }\n#pragma shader_stage(fragment)\nlayout(location = 0) out vec4 color;\nvoid main() { color = vec4(1.0); }\n";
+    let vertex = select_glsl_stage(code, naga::ShaderStage::Vertex).unwrap();
+    assert_eq!(vertex.len(), code.len());
+    assert_eq!(vertex.lines().count(), code.lines().count());
+    assert_eq!(
+        vertex
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>(),
+        ["#version 450", "void main() { gl_Position = vec4(0.0); }"]
+    );
+
+    let single = "#version 450\nvoid main() {}\n";
+    assert!(matches!(
+        select_glsl_stage(single, naga::ShaderStage::Fragment),
+        Ok(Cow::Borrowed(_))
+    ));
+
+    let missing = "#pragma shader_stage(vertex)\n#version 450\nvoid main() {}\n#pragma shader_stage(fragment)\nvoid main() {}\n";
+    let errors = select_glsl_stage(missing, naga::ShaderStage::Fragment).unwrap_err();
+    let messages = GlslParseErrors::new(errors, missing).to_string();
+    assert_eq!(
+        messages,
+        "4:1: the fragment section has no #version directive, unlike the other sections"
+    );
+
+    let repeated =
+        "#version 450 core\n#pragma shader_stage(vertex)\n  #version 460\nvoid main() {}\n";
+    let errors = select_glsl_stage(repeated, naga::ShaderStage::Vertex).unwrap_err();
+    let messages = GlslParseErrors::new(errors, repeated).to_string();
+    assert_eq!(
+        messages,
+        "3:3: #version directive repeated, the vertex stage already uses `#version 450 core`"
+    );
+}
+
+#[cfg(feature = "glsl")]
+#[test]
+pub fn test_glsl_parse_errors_compilation_messages() {
+    let code = "#version 450\n// é\nvoid main() {\n    float x = undefined;\n}\n";
+    let errors = naga::front::glsl::Frontend::default()
+        .parse(
+            &naga::front::glsl::Options::from(naga::ShaderStage::Vertex),
+            code,
+        )
+        .unwrap_err();
+    let messages = GlslParseErrors::new(errors, code).compilation_messages();
+    assert_eq!(messages.len(), 1);
+
+    let (location, utf16_location) = messages[0].location.unwrap();
+    assert_eq!(messages[0].message, "Unknown variable: undefined");
+    assert_eq!(
+        &code[location.offset as usize..][..location.length as usize],
+        "undefined"
+    );
+    assert_eq!((location.line_number, location.line_position), (4, 15));
+    // The `é` on the line before takes two bytes but one UTF-16 code unit.
+    assert_eq!(utf16_location.offset, location.offset - 1);
+    assert_eq!(utf16_location.line_position, 15);
+}
@@ -0,0 +1,208 @@
+//! Chunk bookkeeping for `WGPUStagingBelt`.
+//!
+//! Writes are suballocated from mapped chunks. Finishing the belt unmaps the
+//! chunks written to and hands out the copies to record, recalling it maps
+//! them again, and chunks come back for reuse once their mapping completes.
+
+use wgc::id;
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum StagingBeltError {
+    #[error("Staging belt writes can't be empty")]
+    ZeroSize,
+    #[error(
+        "Staging belt write size {0} must be a multiple of {}",
+        wgt::COPY_BUFFER_ALIGNMENT
+    )]
+    UnalignedSize(wgt::BufferAddress),
+}
+
+pub(crate) struct Chunk {
+    pub(crate) buffer_id: id::BufferId,
+    pub(crate) size: wgt::BufferAddress,
+    offset: wgt::BufferAddress,
+}
+
+impl Chunk {
+    pub(crate) fn new(buffer_id: id::BufferId, size: wgt::BufferAddress) -> Chunk {
+        Chunk {
+            buffer_id,
+            size,
+            offset: 0,
+        }
+    }
+
+    fn allocate(&mut self, size: wgt::BufferAddress) -> Option<wgt::BufferAddress> {
+        let offset = wgt::math::align_to(self.offset, wgt::MAP_ALIGNMENT);
+        if offset + size > self.size {
+            return None;
+        }
+        self.offset = offset + size;
+        Some(offset)
+    }
+}
+
+/// A copy from a chunk to the buffer it was written for.
+pub(crate) struct Copy {
+    pub(crate) source: id::BufferId,
+    pub(crate) source_offset: wgt::BufferAddress,
+    pub(crate) destination: id::BufferId,
+    pub(crate) destination_offset: wgt::BufferAddress,
+    pub(crate) size: wgt::BufferAddress,
+}
+
+pub(crate) struct Chunks {
+    chunk_size: wgt::BufferAddress,
+    /// Mapped chunks written to since the last finish.
+    active: Vec<Chunk>,
+    /// Unmapped chunks waiting for the copies to be submitted.
+    closed: Vec<Chunk>,
+    /// Mapped chunks ready to be reused.
+    free: Vec<Chunk>,
+    copies: Vec<Copy>,
+}
+
+impl Chunks {
+    pub(crate) fn new(chunk_size: wgt::BufferAddress) -> Chunks {
+        Chunks {
+            chunk_size,
+            active: Vec::new(),
+            closed: Vec::new(),
+            free: Vec::new(),
+            copies: Vec::new(),
+        }
+    }
+
+    // `u64::is_multiple_of` needs Rust 1.87.
+    #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+    pub(crate) fn check_write(size: wgt::BufferAddress) -> Result<(), StagingBeltError> {
+        if size == 0 {
+            return Err(StagingBeltError::ZeroSize);
+        }
+        if size % wgt::COPY_BUFFER_ALIGNMENT != 0 {
+            return Err(StagingBeltError::UnalignedSize(size));
+        }
+        Ok(())
+    }
+
+    /// Size of a new chunk that can hold a write of `size` bytes.
+    pub(crate) fn new_chunk_size(&self, size: wgt::BufferAddress) -> wgt::BufferAddress {
+        wgt::math::align_to(self.chunk_size.max(size), wgt::COPY_BUFFER_ALIGNMENT)
+    }
+
+    /// Finds room for `size` bytes in an active chunk, or in a free one which
+    /// becomes active. Returns the chunk's buffer and the offset of the write.
+    pub(crate) fn allocate(
+        &mut self,
+        size: wgt::BufferAddress,
+    ) -> Option<(id::BufferId, wgt::BufferAddress)> {
+        if let Some(allocation) = self
+            .active
+            .iter_mut()
+            .find_map(|chunk| chunk.allocate(size).map(|offset| (chunk.buffer_id, offset)))
+        {
+            return Some(allocation);
+        }
+
+        let index = self.free.iter().position(|chunk| chunk.size >= size)?;
+        let mut chunk = self.free.swap_remove(index);
+        let offset = chunk.allocate(size)?;
+        let buffer_id = chunk.buffer_id;
+        self.active.push(chunk);
+        Some((buffer_id, offset))
+    }
+
+    /// Adds a freshly created chunk, mapped at creation.
+    pub(crate) fn add_active(&mut self, chunk: Chunk) {
+        self.active.push(chunk);
+    }
+
+    pub(crate) fn push_copy(&mut self, copy: Copy) {
+        self.copies.push(copy);
+    }
+
+    /// Closes the active chunks, returning them to be unmapped along with the
+    /// copies to record.
+    pub(crate) fn finish(&mut self) -> (Vec<id::BufferId>, Vec<Copy>) {
+        let buffer_ids = self.active.iter().map(|chunk| chunk.buffer_id).collect();
+        self.closed.append(&mut self.active);
+        (buffer_ids, std::mem::take(&mut self.copies))
+    }
+
+    /// Takes the closed chunks, to be mapped again.
+    pub(crate) fn recall(&mut self) -> Vec<Chunk> {
+        std::mem::take(&mut self.closed)
+    }
+
+    /// Returns a chunk whose mapping completed.
+    pub(crate) fn add_free(&mut self, mut chunk: Chunk) {
+        chunk.offset = 0;
+        self.free.push(chunk);
+    }
+
+    /// Every chunk still owned by the belt.
+    pub(crate) fn buffer_ids(&self) -> impl Iterator<Item = id::BufferId> + '_ {
+        self.active
+            .iter()
+            .chain(&self.closed)
+            .chain(&self.free)
+            .map(|chunk| chunk.buffer_id)
+    }
+}
+
+#[test]
+pub fn test_chunks() {
+    let buffer_id = |index| id::BufferId::zip(index, 1, wgt::Backend::Empty);
+    let copy = |source, size| Copy {
+        source,
+        source_offset: 0,
+        destination: buffer_id(9),
+        destination_offset: 0,
+        size,
+    };
+
+    assert!(matches!(
+        Chunks::check_write(0),
+        Err(StagingBeltError::ZeroSize)
+    ));
+    assert!(matches!(
+        Chunks::check_write(6),
+        Err(StagingBeltError::UnalignedSize(6))
+    ));
+    assert!(Chunks::check_write(8).is_ok());
+
+    let mut chunks = Chunks::new(256);
+    assert_eq!(chunks.new_chunk_size(16), 256);
+    assert_eq!(chunks.new_chunk_size(1000), 1000);
+    assert_eq!(chunks.allocate(16), None);
+
+    // Writes are packed at MAP_ALIGNMENT.
+    chunks.add_active(Chunk::new(buffer_id(0), 256));
+    assert_eq!(chunks.allocate(16), Some((buffer_id(0), 0)));
+    assert_eq!(chunks.allocate(20), Some((buffer_id(0), 16)));
+    assert_eq!(chunks.allocate(4), Some((buffer_id(0), 40)));
+    assert_eq!(chunks.allocate(256), None);
+    chunks.push_copy(copy(buffer_id(0), 16));
+
+    let (buffer_ids, copies) = chunks.finish();
+    assert_eq!(buffer_ids, [buffer_id(0)]);
+    assert_eq!(copies.len(), 1);
+    // Closed chunks can't be written to until they are mapped again.
+    assert_eq!(chunks.allocate(16), None);
+    assert_eq!(chunks.finish().0, []);
+
+    let recalled = chunks.recall();
+    assert_eq!(recalled.len(), 1);
+    assert!(chunks.recall().is_empty());
+    for chunk in recalled {
+        chunks.add_free(chunk);
+    }
+    assert_eq!(chunks.buffer_ids().collect::<Vec<_>>(), [buffer_id(0)]);
+
+    // Free chunks are only reused for writes they can hold, from the start.
+    assert_eq!(chunks.allocate(512), None);
+    assert_eq!(chunks.allocate(16), Some((buffer_id(0), 0)));
+    chunks.add_active(Chunk::new(buffer_id(1), 512));
+    assert_eq!(chunks.allocate(512), Some((buffer_id(1), 0)));
+    assert_eq!(chunks.finish().0, [buffer_id(0), buffer_id(1)]);
+}
@@ -105,11 +105,6 @@ pub extern "C" fn wgpuInstanceHasWGSLLanguageFeature(
     unimplemented!();
 }
 
-#[no_mangle]
-pub extern "C" fn wgpuInstanceProcessEvents(_instance: native::WGPUInstance) {
-    unimplemented!();
-}
-
 #[no_mangle]
 pub extern "C" fn wgpuPipelineLayoutSetLabel(
     _pipeline_layout: native::WGPUPipelineLayout,
@@ -174,15 +169,6 @@ pub extern "C" fn wgpuSamplerSetLabel(
     unimplemented!();
 }
 
-#[no_mangle]
-pub extern "C" fn wgpuShaderModuleGetCompilationInfo(
-    _shader_module: native::WGPUShaderModule,
-    _callback: native::WGPUShaderModuleGetCompilationInfoCallback,
-    _userdata: *mut ::std::os::raw::c_void,
-) {
-    unimplemented!();
-}
-
 #[no_mangle]
 pub extern "C" fn wgpuShaderModuleSetLabel(
     _shader_module: native::WGPUShaderModule,
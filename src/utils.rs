@@ -1,3 +1,4 @@
+use crate::native;
 use std::{
     borrow::Cow,
     ffi::CStr,
@@ -6,6 +7,7 @@ use std::{
 
 // A dummy wrapper that is `Send` + `Sync` to store userdata pointer
 // to be usable across Rust callbacks.
+#[derive(Clone, Copy)]
 pub(crate) struct Userdata(*mut std::ffi::c_void);
 impl Userdata {
     #[inline]
@@ -21,6 +23,48 @@ impl Userdata {
 unsafe impl Send for Userdata {}
 unsafe impl Sync for Userdata {}
 
+/// Runs callbacks through the executor set with `WGPUInstanceExtras::callbackExecutor`,
+/// or right away on the calling thread when there is none.
+#[derive(Clone)]
+pub(crate) struct CallbackExecutor {
+    executor: native::WGPUCallbackExecutor,
+    userdata: Userdata,
+}
+impl CallbackExecutor {
+    pub(crate) const fn new(
+        executor: native::WGPUCallbackExecutor,
+        userdata: *mut std::ffi::c_void,
+    ) -> CallbackExecutor {
+        CallbackExecutor {
+            executor,
+            userdata: Userdata::new(userdata),
+        }
+    }
+
+    pub(crate) fn execute(&self, task: impl FnOnce() + Send + 'static) {
+        type Task = Box<dyn FnOnce() + Send>;
+
+        unsafe extern "C" fn run(task: *mut std::ffi::c_void) {
+            let task = unsafe { Box::from_raw(task as *mut Task) };
+            task();
+        }
+
+        match self.executor {
+            Some(executor) => {
+                let task: Box<Task> = Box::new(Box::new(task));
+                unsafe {
+                    executor(
+                        Some(run),
+                        Box::into_raw(task) as *mut std::ffi::c_void,
+                        self.userdata.as_ptr(),
+                    )
+                };
+            }
+            None => task(),
+        }
+    }
+}
+
 #[inline]
 pub(crate) fn ptr_into_label<'a>(ptr: *const std::ffi::c_char) -> wgc::Label<'a> {
     unsafe { ptr.as_ref() }.and_then(|ptr| {
@@ -461,3 +505,51 @@ pub fn test_get_base_device_limits_from_adapter_limits() {
         );
     }
 }
+
+#[test]
+pub fn test_callback_executor() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    type Queued = Mutex<Vec<(native::WGPUCallbackTaskProc, usize)>>;
+
+    unsafe extern "C" fn enqueue(
+        run: native::WGPUCallbackTaskProc,
+        task: *mut std::ffi::c_void,
+        userdata: *mut std::ffi::c_void,
+    ) {
+        let queued = unsafe { &*(userdata as *const Queued) };
+        queued.lock().unwrap().push((run, task as usize));
+    }
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = || {
+        let calls = calls.clone();
+        move || {
+            calls.fetch_add(1, Ordering::SeqCst);
+        }
+    };
+
+    // Without an executor, tasks run right away.
+    CallbackExecutor::new(None, std::ptr::null_mut()).execute(counter());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // With one, they only run once the executor calls them.
+    let queued = Queued::default();
+    let executor = CallbackExecutor::new(
+        Some(enqueue),
+        &queued as *const Queued as *mut std::ffi::c_void,
+    );
+    executor.execute(counter());
+    executor.clone().execute(counter());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    let queued = queued.into_inner().unwrap();
+    assert_eq!(queued.len(), 2);
+    for (run, task) in queued {
+        unsafe { run.unwrap()(task as *mut std::ffi::c_void) };
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,69 @@
+//! Parses and validates a shader, then writes it as a naga IR blob for
+//! `WGPUShaderModuleNagaIRDescriptor`.
+//!
+//! Usage: `naga-ir <input> <output>`. The input language is picked from the
+//! extension: `.wgsl`, `.spv`, or `.vert`/`.frag`/`.comp` for GLSL.
+
+use std::{env, fs, path::Path, process};
+
+/// Must match `MAGIC` in wgpu-native's `src/naga_ir.rs`.
+const MAGIC: &[u8; 8] = b"NAGAIR02";
+/// The naga version this tool is built against, wgpu-native rejects blobs
+/// written with any other. Must match `NAGA_VERSION` in `src/naga_ir.rs`.
+const NAGA_VERSION: &str = "22.1.0";
+
+fn parse(path: &Path) -> Result<naga::Module, String> {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let read_to_string = || fs::read_to_string(path).map_err(|err| err.to_string());
+    let glsl = |stage| {
+        let code = read_to_string()?;
+        naga::front::glsl::Frontend::default()
+            .parse(&naga::front::glsl::Options::from(stage), &code)
+            .map_err(|err| err.emit_to_string(&code))
+    };
+
+    match extension {
+        "wgsl" => {
+            let code = read_to_string()?;
+            naga::front::wgsl::parse_str(&code).map_err(|err| err.emit_to_string(&code))
+        }
+        "spv" => {
+            let data = fs::read(path).map_err(|err| err.to_string())?;
+            naga::front::spv::parse_u8_slice(&data, &Default::default())
+                .map_err(|err| err.to_string())
+        }
+        "vert" => glsl(naga::ShaderStage::Vertex),
+        "frag" => glsl(naga::ShaderStage::Fragment),
+        "comp" => glsl(naga::ShaderStage::Compute),
+        _ => Err(format!("unknown shader extension {extension:?}")),
+    }
+}
+
+fn run(input: &Path, output: &Path) -> Result<(), String> {
+    let module = parse(input)?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|err| format!("validation error: {}", err.into_inner()))?;
+
+    let mut data = MAGIC.to_vec();
+    data.push(NAGA_VERSION.len() as u8);
+    data.extend_from_slice(NAGA_VERSION.as_bytes());
+    bincode::serialize_into(&mut data, &module).map_err(|err| err.to_string())?;
+    fs::write(output, data).map_err(|err| err.to_string())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let [_, input, output] = args.as_slice() else {
+        eprintln!("usage: naga-ir <input> <output>");
+        process::exit(2);
+    };
+
+    if let Err(err) = run(Path::new(input), Path::new(output)) {
+        eprintln!("{input}: {err}");
+        process::exit(1);
+    }
+}